@@ -3,13 +3,19 @@
 extern crate indexmap;
 extern crate byteorder;
 extern crate env_logger;
+extern crate sha1;
+extern crate sha2;
+extern crate md5;
 
 use std::env;
-use elfkit::{Elf, Header, types, symbol, relocation, section, Error, loader, dynamic};
-use elfkit::symbolic_linker::{SymbolicLinker};
+use elfkit::{Elf, Header, types, symbol, relocation, section, Error, loader, dynamic, hash, merge, note};
+use elfkit::symbolic_linker::{self, SymbolicLinker};
+use elfkit::linker_script::{self, Script};
 use self::indexmap::{IndexMap};
 use std::collections::hash_map::{self,HashMap};
-use std::fs::OpenOptions;
+use std::collections::HashSet;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write, BufWriter, Cursor};
 use std::os::unix::fs::PermissionsExt;
 use std::process;
 
@@ -21,76 +27,166 @@ fn main() {
 
 
     let args = parse_ld_options();
-    let mut loader: Vec<loader::State> = args.object_paths.into_iter().map(|s|{
-        loader::State::Path{name:s}}).collect();
 
     let mut elf = Elf::default();
     elf.header.ident_class      = types::Class::Class64;
     elf.header.ident_endianness = types::Endianness::LittleEndian;
     elf.header.ident_abi        = types::Abi::SYSV;
-    elf.header.etype            = args.etype;
+    elf.header.etype            = args.etype.clone();
     elf.header.machine          = types::Machine::X86_64;
+    elf.execstack               = args.execstack;
+    elf.separate_code           = args.separate_code;
+    if args.relro {
+        elf.relro = relro_sections(&args);
+    }
 
+    let map;
     let mut elf = match elf.header.etype {
-        types::ElfType::DYN => {
-            loader.push(loader::State::Object{
-                name:     String::from("___linker_entry"),
-                hash:     String::from("___linker_entry"),
-                symbols:  vec![symbol::Symbol{
-                    stype: types::SymbolType::FUNC,
-                    size:  0,
-                    value: 0,
-                    bind:  types::SymbolBind::GLOBAL,
-                    vis:   types::SymbolVis::DEFAULT,
-                    shndx: symbol::SymbolSectionIndex::Undefined,
-                    name:  b"_start".to_vec(),
-                    _name: 0,
-                }],
-                header:   Header::default(),
-                sections: Vec::new(),
-            });
+        types::ElfType::DYN if args.shared => {
+            // everything on the command line goes in, archive members only when they're used
+            let mut loader = vec![undefined_object(&args, false)];
+            if let Some(ref script) = args.script {
+                loader.push(script_object(script));
+            }
+
+            let mut linker = SymbolicLinker::default();
+            linker.allow_undefined = true;
+            linker.wrap = args.wrap.iter().cloned().collect();
+            linker.warn_common = args.warn_common;
+            match linker.link_all(loader).and_then(|_| linker.link_inputs(args.inputs.clone())) {
+                Ok(_)   => {},
+                Err(Error::ConflictingSymbol{sym, obj1_name, obj1_hash, obj2_name, obj2_hash}) => {
+                    error!("conflicting symbol '{}'\n       in {} [LHAS {}]\n       in {} [LHAS {}]",
+                           sym, obj1_name, obj1_hash, obj2_name, obj2_hash);
+                    process::abort();
+                },
+                Err(Error::LoadFailed{name, cause}) => {
+                    error!("{:?} while loading {}", cause, name);
+                    process::abort();
+                },
+                Err(e)  => panic!("{:?}", e),
+            };
+            for w in linker.warnings.drain(..) {
+                warn!("{}", w);
+            }
+
+            info!("lookup complete: {} nodes in link tree", linker.objects.len());
+            linker.allocate_commons();
+            let mut roots = linker.exported();
+            roots.extend(args.roots());
+            gc(&mut linker, &roots, &args.script);
+            info!("  after gc: {}", linker.objects.len());
+            icf(&mut linker, &roots, args.icf);
+
+            elf.sections.push(section::Section::default());
+            if let Some(ref build_id) = args.build_id {
+                elf.sections.push(build_id_section(build_id));
+            }
+            let mut collected = SimpleCollector::new(elf).with_script(args.script.clone())
+                .with_defsym(args.defsym.clone()).collect(linker).into_collected();
+            DynamicRelocator::relocate(&mut collected, &args).unwrap();
+            map = std::mem::take(&mut collected.map);
+            let mut elf = collected.into_elf();
+            elf.make_symtab_gnuld_compat().unwrap();
+            if let Some(chtype) = args.compress_debug {
+                compress_debug_sections(&mut elf, chtype).unwrap();
+            }
+            elf.layout().unwrap();
+            elf
+        },
+        types::ElfType::DYN | types::ElfType::EXEC => {
+            let mut loader = vec![undefined_object(&args, true)];
+            if let Some(ref script) = args.script {
+                loader.push(script_object(script));
+            }
 
             let mut linker = SymbolicLinker::default();
+            linker.wrap = args.wrap.iter().cloned().collect();
+            linker.warn_common = args.warn_common;
+            linker.optional.insert(args.entry.clone());
 
-            match linker.link(loader) {
+            match linker.link_all(loader).and_then(|_| linker.link_inputs(args.inputs.clone())) {
                 Ok(_)   => {},
                 Err(Error::ConflictingSymbol{sym, obj1_name, obj1_hash, obj2_name, obj2_hash}) => {
                     error!("conflicting symbol '{}'\n       in {} [LHAS {}]\n       in {} [LHAS {}]",
                            sym, obj1_name, obj1_hash, obj2_name, obj2_hash);
                     process::abort();
                 },
-                Err(e)  => panic!(e),
+                Err(Error::LoadFailed{name, cause}) => {
+                    error!("{:?} while loading {}", cause, name);
+                    process::abort();
+                },
+                Err(e)  => panic!("{:?}", e),
             };
+            for w in linker.warnings.drain(..) {
+                warn!("{}", w);
+            }
 
             info!("lookup complete: {} nodes in link tree", linker.objects.len());
-            linker.gc();
+            linker.allocate_commons();
+            let mut roots = vec![args.entry.clone()];
+            roots.extend(args.roots());
+            gc(&mut linker, &roots, &args.script);
             info!("  after gc: {}", linker.objects.len());
+            icf(&mut linker, &roots, args.icf);
 
             elf.sections.push(section::Section::default());
-            let mut dl = args.dynamic_linker.into_bytes();
-            if dl.len() > 0 {
+            let mut dl = args.dynamic_linker.clone().into_bytes();
+            if elf.header.etype == types::ElfType::EXEC {
+                elf.image_base = args.image_base.unwrap_or(0x400000);
+                for (name, addr) in &args.section_start {
+                    elf.section_start.insert(name.clone(), *addr);
+                }
+            } else if !dl.is_empty() {
                 dl.push(0);
                 elf.sections.push(section::Section::new(b".interp".to_vec(), types::SectionType::PROGBITS,
                 types::SectionFlags::ALLOC,
                 section::SectionContent::Raw(dl), 0, 0));
             }
+            if let Some(ref build_id) = args.build_id {
+                elf.sections.push(build_id_section(build_id));
+            }
 
-            let mut collected = SimpleCollector::new(elf).collect(linker).into_collected();
-            DynamicRelocator::relocate(&mut collected).unwrap();
+            let mut collected = SimpleCollector::new(elf).with_script(args.script.clone())
+                .with_defsym(args.defsym.clone()).collect(linker).into_collected();
+            DynamicRelocator::relocate(&mut collected, &args).unwrap();
+            map = std::mem::take(&mut collected.map);
             let mut elf = collected.into_elf();
             elf.make_symtab_gnuld_compat().unwrap();
+            if let Some(chtype) = args.compress_debug {
+                compress_debug_sections(&mut elf, chtype).unwrap();
+            }
             elf.layout().unwrap();
             elf
         },
         types::ElfType::REL => {
+            if args.script.is_some() {
+                warn!("linker script ignored for relocatable output");
+            }
+            if args.build_id.is_some() {
+                warn!("--build-id ignored for relocatable output");
+            }
+            if !args.defsym.is_empty() {
+                warn!("--defsym ignored for relocatable output");
+            }
             let mut linker = SymbolicLinker::default();
+            linker.wrap = args.wrap.iter().cloned().collect();
+            linker.warn_common = args.warn_common;
+            let loader = args.object_paths().into_iter().map(|s| loader::State::Path{name: s}).collect();
             linker.link_all(loader).unwrap();
+            for w in linker.warnings.drain(..) {
+                warn!("{}", w);
+            }
             info!("lookup complete: {} nodes in link tree", linker.objects.len());
             elf.sections.push(section::Section::default());
+            // relocations are not applied, but written out against the merged .symtab
             let mut collected = SimpleCollector::new(elf).collect(linker).into_collected();
-            DynamicRelocator::relocate(&mut collected).unwrap();
+            map = std::mem::take(&mut collected.map);
             let mut elf = collected.into_elf();
             elf.make_symtab_gnuld_compat().unwrap();
+            if let Some(chtype) = args.compress_debug {
+                compress_debug_sections(&mut elf, chtype).unwrap();
+            }
             elf.layout().unwrap();
             elf
         },
@@ -99,8 +195,29 @@ fn main() {
 
 
 
-    let mut out_file = OpenOptions::new().write(true).truncate(true).create(true).open(args.output_path).unwrap();
-    elf.to_writer(&mut out_file).unwrap();
+    if let Some(ref path) = args.map_path {
+        let out = File::create(path).and_then(|f| write_map(&elf, &map, BufWriter::new(f)));
+        if let Err(e) = out {
+            error!("cannot write map file {}: {}", path, e);
+            process::abort();
+        }
+    }
+
+    // to_writer takes the sections, so remember where the note goes first
+    let build_id = elf.sections.iter().find(|s| s.name == b".note.gnu.build-id")
+        .map(|s| s.header.offset as usize + 16);
+
+    let mut out = Cursor::new(Vec::new());
+    elf.to_writer(&mut out).unwrap();
+    let mut out = out.into_inner();
+    if let (Some(ref style), Some(off)) = (&args.build_id, build_id) {
+        // the id covers the whole file with the note still zeroed
+        let id = style.compute(&out);
+        out[off..off + id.len()].copy_from_slice(&id);
+    }
+
+    let mut out_file = OpenOptions::new().write(true).truncate(true).create(true).open(&args.output_path).unwrap();
+    out_file.write_all(&out).unwrap();
 
     let mut perms = out_file.metadata().unwrap().permissions();
     perms.set_mode(0o755);
@@ -108,17 +225,648 @@ fn main() {
 
 }
 
+fn compress_debug_sections(elf: &mut Elf, chtype: types::CompressionType) -> Result<(), Error> {
+    for sec in &mut elf.sections {
+        if sec.name.starts_with(b".debug") && !sec.header.flags.contains(types::SectionFlags::ALLOC) {
+            sec.compress(chtype, &elf.header)?;
+        }
+    }
+    Ok(())
+}
+
+/// write a map of the output. see the README for the format
+fn write_map<W: Write>(elf: &Elf, map: &[MapEntry], mut out: W) -> std::io::Result<()> {
+    // TLS symbols are relative to the start of the TLS template
+    let tls = elf.sections.iter()
+        .find(|s| s.header.flags.contains(types::SectionFlags::TLS))
+        .map(|s| s.header.addr)
+        .unwrap_or(0);
+
+    let mut symbols : HashMap<usize, Vec<(u64, &[u8])>> = HashMap::new();
+    for sec in &elf.sections {
+        if let section::SectionContent::Symbols(ref syms) = sec.content {
+            if sec.header.shtype != types::SectionType::SYMTAB {
+                continue;
+            }
+            for sym in syms {
+                let shndx = match sym.shndx {
+                    symbol::SymbolSectionIndex::Section(i) if i > 0 => i as usize,
+                    _ => continue,
+                };
+                if sym.name.is_empty() || sym.stype == types::SymbolType::SECTION ||
+                    sym.stype == types::SymbolType::FILE {
+                    continue;
+                }
+                let addr = if sym.stype == types::SymbolType::TLS {
+                    sym.value + tls
+                } else {
+                    sym.value
+                };
+                symbols.entry(shndx).or_default().push((addr, &sym.name));
+            }
+        }
+    }
+
+    let mut inputs : HashMap<usize, Vec<&MapEntry>> = HashMap::new();
+    for entry in map {
+        inputs.entry(entry.shndx).or_default().push(entry);
+    }
+
+    for (shndx, sec) in elf.sections.iter().enumerate().skip(1) {
+        writeln!(out, "{:<24} {:#018x} {:>#10x}", String::from_utf8_lossy(&sec.name),
+                 sec.header.addr, sec.header.size)?;
+
+        let mut syms = symbols.remove(&shndx).unwrap_or_default();
+        syms.sort();
+        let mut entries = inputs.remove(&shndx).unwrap_or_default();
+        entries.sort_by_key(|e| e.offset);
+
+        for entry in entries {
+            let start = sec.header.addr + entry.offset;
+            writeln!(out, "    {:#018x} {:>#10x} {}", start, entry.size, entry.object)?;
+
+            // half open, so a symbol right at the end of an input goes with the next one
+            let (within, rest) : (Vec<_>, Vec<_>) = syms.into_iter().partition(|&(addr, _)| {
+                addr >= start && (addr < start + entry.size || (entry.size == 0 && addr == start))
+            });
+            for (addr, name) in within {
+                writeln!(out, "        {:#018x} {}", addr, String::from_utf8_lossy(name))?;
+            }
+            syms = rest;
+        }
+
+        // symbols outside of any input, like the ones a linker script assigns
+        for (addr, name) in syms {
+            writeln!(out, "    {:#018x} {}", addr, String::from_utf8_lossy(name))?;
+        }
+    }
+    out.flush()
+}
+
+/// the sections that are only written by the dynamic linker while relocating.
+/// with lazy binding it keeps writing .got.plt later on
+fn relro_sections(args: &LdOptions) -> Vec<Vec<u8>> {
+    let mut relro : Vec<Vec<u8>> = vec![b".tdata".to_vec(), b".tbss".to_vec(),
+        b".init_array".to_vec(), b".fini_array".to_vec(),
+        b".preinit_array".to_vec(), b".data.rel.ro".to_vec(), b".dynamic".to_vec(), b".got".to_vec()];
+    if args.bind_now {
+        relro.push(b".got.plt".to_vec());
+    }
+    relro
+}
+
+/// an empty .note.gnu.build-id. the id is filled in after the output is written
+fn build_id_section(build_id: &BuildId) -> section::Section {
+    let note = note::Note{
+        name:    b"GNU".to_vec(),
+        content: note::NoteContent::GnuBuildId(vec![0; build_id.size()]),
+    };
+    let mut sec = section::Section::new(b".note.gnu.build-id".to_vec(), types::SectionType::NOTE,
+    types::SectionFlags::ALLOC, section::SectionContent::Notes(vec![note]), 0, 0);
+    sec.header.addralign = 4;
+    sec
+}
+
+/// references the entry point, -u symbols and whatever --defsym refers to, so they are linked in.
+/// also defines the --defsym symbols
+fn undefined_object(args: &LdOptions, entry: bool) -> loader::State {
+    let undefined = |name: &[u8], stype| symbol::Symbol{
+        stype,
+        size:  0,
+        value: 0,
+        bind:  types::SymbolBind::GLOBAL,
+        vis:   types::SymbolVis::DEFAULT,
+        shndx: symbol::SymbolSectionIndex::Undefined,
+        name:  name.to_vec(),
+        _name: 0,
+    };
+
+    let mut symbols = Vec::new();
+    if entry {
+        symbols.push(undefined(&args.entry, types::SymbolType::FUNC));
+    }
+    for name in args.roots() {
+        symbols.push(undefined(&name, types::SymbolType::NOTYPE));
+    }
+    for a in &args.defsym {
+        // the value is filled in by the collector
+        symbols.push(symbol::Symbol{
+            shndx: symbol::SymbolSectionIndex::Absolute,
+            ..undefined(&a.symbol, types::SymbolType::NOTYPE)
+        });
+    }
+
+    loader::State::Object{
+        name:     String::from("___linker_entry"),
+        hash:     String::from("___linker_entry"),
+        symbols,
+        header:   Header::default(),
+        sections: Vec::new(),
+    }
+}
+
+/// defines the symbols assigned in the script, so references to them resolve.
+/// the values are filled in once the script is executed
+fn script_object(script: &Script) -> loader::State {
+    let symbols = script.assignments().into_iter().map(|a| symbol::Symbol{
+        stype: types::SymbolType::NOTYPE,
+        size:  0,
+        value: 0,
+        bind:  if a.provide { types::SymbolBind::WEAK } else { types::SymbolBind::GLOBAL },
+        vis:   if a.hidden { types::SymbolVis::HIDDEN } else { types::SymbolVis::DEFAULT },
+        shndx: symbol::SymbolSectionIndex::Absolute,
+        name:  a.symbol.clone(),
+        _name: 0,
+    }).collect();
+
+    loader::State::Object{
+        name:     String::from("___linker_script"),
+        hash:     String::from("___linker_script"),
+        symbols,
+        header:   Header::default(),
+        sections: Vec::new(),
+    }
+}
+
+/// the name of the file an object was loaded from, without the section
+fn object_file(obj: &symbolic_linker::Object) -> &[u8] {
+    let name = obj.name.as_bytes();
+    match obj.name.rfind('(') {
+        Some(i) => &name[..i],
+        None => name,
+    }
+}
+
+/// garbage collect everything not reachable from roots or sections the script says to KEEP
+fn gc(linker: &mut SymbolicLinker, roots: &[Vec<u8>], script: &Option<Script>) {
+    match *script {
+        Some(ref script) => linker.gc_keep(roots, |obj| {
+            obj.section.header.shtype != types::SectionType::NULL &&
+                script.keep(object_file(obj), &obj.section.name)
+        }),
+        None => linker.gc(roots),
+    }
+}
+
+/// fold identical code, and say how much that saved
+fn icf(linker: &mut SymbolicLinker, roots: &[Vec<u8>], mode: symbolic_linker::Icf) {
+    if mode == symbolic_linker::Icf::None {
+        return;
+    }
+    let (sections, bytes) = linker.icf(mode, roots);
+    info!("  icf folded {} sections, {} bytes", sections, bytes);
+}
+
+/// what is left of a TLS access after relax_tls rewrote it
+enum TlsRelax {
+    /// the 32 bits at this offset are the symbol's offset from the thread pointer
+    Le(u64),
+    /// the 32 bits at this offset are a pc relative reference to a GOT slot with the symbol's offset
+    Ie(u64),
+    /// the code doesn't refer to the symbol anymore
+    Done,
+}
+
+/// rewrite the code around a TLS relocation at `at` to one of the models an executable can use,
+/// as described in "ELF Handling For Thread-Local Storage". local symbols get LE, symbols from shared
+/// objects IE. also returns where the rewritten code ends, since the __tls_get_addr call is gone.
+/// None if the code isn't one of the sequences the ABI specifies
+fn relax_tls(code: &mut [u8], rtype: &relocation::RelocationType, at: usize, local: bool)
+    -> Option<(TlsRelax, u64)> {
+    if at < 3 || at + 2 > code.len() {
+        return None;
+    }
+    let end = at as u64 + 4;
+    let reg = (code[at - 1] >> 3) & 7;
+    match *rtype {
+        // data16 lea x@tlsgd(%rip),%rdi; data16 data16 rex.W call __tls_get_addr
+        // or with -fno-plt:              data16 rex.W call *__tls_get_addr@GOTPCREL(%rip)
+        relocation::RelocationType::R_X86_64_TLSGD => {
+            if at < 4 || at + 12 > code.len() || code[at - 4..at] != [0x66, 0x48, 0x8d, 0x3d] ||
+                (code[at + 4..at + 8] != [0x66, 0x66, 0x48, 0xe8] &&
+                 code[at + 4..at + 8] != [0x66, 0x48, 0xff, 0x15]) {
+                return None;
+            }
+            // mov %fs:0,%rax; lea x@tpoff(%rax),%rax  or  add x@gottpoff(%rip),%rax
+            code[at - 4..at + 5].copy_from_slice(&[0x64, 0x48, 0x8b, 0x04, 0x25, 0x00, 0x00, 0x00, 0x00]);
+            if local {
+                code[at + 5..at + 8].copy_from_slice(&[0x48, 0x8d, 0x80]);
+                Some((TlsRelax::Le(at as u64 + 8), at as u64 + 12))
+            } else {
+                code[at + 5..at + 8].copy_from_slice(&[0x48, 0x03, 0x05]);
+                Some((TlsRelax::Ie(at as u64 + 8), at as u64 + 12))
+            }
+        },
+        // lea x@tlsld(%rip),%rdi; call __tls_get_addr  becomes  mov %fs:0,%rax,
+        // after which the x@dtpoff that follow are relative to the thread pointer
+        relocation::RelocationType::R_X86_64_TLSLD => {
+            if code[at - 3..at] != [0x48, 0x8d, 0x3d] {
+                return None;
+            }
+            let (prefix, end) = match code.get(at + 4..at + 6) {
+                Some(&[0xe8, _]) => (3, at + 9),
+                Some(&[0xff, 0x15]) => (4, at + 10),
+                _ => return None,
+            };
+            if end > code.len() {
+                return None;
+            }
+            for b in &mut code[at - 3..at - 3 + prefix] {
+                *b = 0x66;
+            }
+            code[at - 3 + prefix..end].copy_from_slice(&[0x64, 0x48, 0x8b, 0x04, 0x25, 0x00, 0x00, 0x00, 0x00]);
+            Some((TlsRelax::Done, end as u64))
+        },
+        // mov x@gottpoff(%rip),%reg  becomes  mov $x@tpoff,%reg
+        // add x@gottpoff(%rip),%reg  becomes  add $x@tpoff,%reg
+        relocation::RelocationType::R_X86_64_GOTTPOFF => {
+            if !local {
+                return Some((TlsRelax::Ie(at as u64), end));
+            }
+            let rex = match code[at - 3] {
+                0x48 => 0x48,
+                0x4c => 0x49,
+                _ => return None,
+            };
+            let op = match code[at - 2] {
+                0x8b => 0xc7,
+                0x03 => 0x81,
+                _ => return None,
+            };
+            if code[at - 1] & 0xc7 != 0x05 {
+                return None;
+            }
+            code[at - 3..at].copy_from_slice(&[rex, op, 0xc0 | reg]);
+            Some((TlsRelax::Le(at as u64), end))
+        },
+        // lea x@tlsdesc(%rip),%reg  becomes  mov $x@tpoff,%reg  or  mov x@gottpoff(%rip),%reg
+        relocation::RelocationType::R_X86_64_GOTPC32_TLSDESC => {
+            if code[at - 3] & 0xfb != 0x48 || code[at - 2] != 0x8d || code[at - 1] & 0xc7 != 0x05 {
+                return None;
+            }
+            if local {
+                let rex = 0x48 | ((code[at - 3] >> 2) & 1);
+                code[at - 3..at].copy_from_slice(&[rex, 0xc7, 0xc0 | reg]);
+                Some((TlsRelax::Le(at as u64), end))
+            } else {
+                code[at - 2] = 0x8b;
+                Some((TlsRelax::Ie(at as u64), end))
+            }
+        },
+        // call *x@tlscall(%rax)  becomes  xchg %ax,%ax
+        relocation::RelocationType::R_X86_64_TLSDESC_CALL => {
+            if code[at..at + 2] != [0xff, 0x10] {
+                return None;
+            }
+            code[at..at + 2].copy_from_slice(&[0x66, 0x90]);
+            Some((TlsRelax::Done, at as u64 + 2))
+        },
+        _ => None,
+    }
+}
+
+/// rewrite an instruction that loads an address from the GOT to compute it directly instead,
+/// for an R_X86_64_GOTPCRELX or R_X86_64_REX_GOTPCRELX at `at`. returns where the R_X86_64_PC32
+/// to the symbol itself goes, or None if the instruction can't be rewritten
+fn relax_gotpcrel(code: &mut [u8], at: usize) -> Option<u64> {
+    if at < 2 || at + 4 > code.len() {
+        return None;
+    }
+    match (code[at - 2], code[at - 1]) {
+        // call *foo@GOTPCREL(%rip)  becomes  addr32 call foo
+        (0xff, 0x15) => {
+            code[at - 2..at].copy_from_slice(&[0x67, 0xe8]);
+            Some(at as u64)
+        },
+        // jmp *foo@GOTPCREL(%rip)  becomes  jmp foo; nop
+        (0xff, 0x25) => {
+            code[at - 2] = 0xe9;
+            code[at + 3] = 0x90;
+            Some(at as u64 - 1)
+        },
+        // mov foo@GOTPCREL(%rip),%reg  becomes  lea foo(%rip),%reg
+        (0x8b, modrm) if modrm & 0xc7 == 0x05 => {
+            code[at - 2] = 0x8d;
+            Some(at as u64)
+        },
+        _ => None,
+    }
+}
+
+/// the synthetic entries the relocations of a dynamic link need: slots in .got, entries in .plt
+/// and .iplt, and the relocations that fill them in. each one also gets a local symbol in the symtab,
+/// so the relocation that needed it can simply be pointed there
+struct DynamicSlots {
+    shndx_got:  usize,
+    shndx_plt:  usize,
+    shndx_iplt: usize,
+
+    got:        Vec<u8>,
+    iplt:       Vec<u8>,
+
+    // R_X86_64_RELATIVE with the symtab index in sym
+    dynrel:         Vec<relocation::Relocation>,
+    dynrel_addr:    Vec<usize>,
+
+    // relocations against imports, with the dynsym index in sym
+    dynrel_sym:         Vec<relocation::Relocation>,
+    dynrel_sym_addr:    Vec<usize>,
+
+    // relocations in dynrel_sym that get the value of a local symbol as addend, with its symtab index
+    sym_addend: HashMap<usize, usize>,
+
+    // R_X86_64_JUMP_SLOT into .got.plt, one for each plt entry
+    pltrel:     Vec<relocation::Relocation>,
+
+    // R_X86_64_IRELATIVE into .got, one for each .iplt entry, with the resolver's symtab index in sym
+    irel:       Vec<relocation::Relocation>,
+
+    hrel:       Vec<(usize, relocation::Relocation)>,
+
+    sym2got:    HashMap<u32, usize>,
+    sym2plt:    HashMap<u32, usize>,
+    sym2tls:    HashMap<(u32, u32), usize>,
+    sym2iplt:   HashMap<u32, usize>,
+}
+
+impl DynamicSlots {
+    fn new(shndx_got: usize, shndx_plt: usize, shndx_iplt: usize) -> DynamicSlots {
+        DynamicSlots {
+            shndx_got,
+            shndx_plt,
+            shndx_iplt,
+            got:                Vec::new(),
+            iplt:               Vec::new(),
+            dynrel:             Vec::new(),
+            dynrel_addr:        Vec::new(),
+            dynrel_sym:         Vec::new(),
+            dynrel_sym_addr:    Vec::new(),
+            sym_addend:         HashMap::new(),
+            pltrel:             Vec::new(),
+            irel:               Vec::new(),
+            hrel:               Vec::new(),
+            sym2got:            HashMap::new(),
+            sym2plt:            HashMap::new(),
+            sym2tls:            HashMap::new(),
+            sym2iplt:           HashMap::new(),
+        }
+    }
+
+    /// push a symbol for a new slot, named after the symbol it is for, and return its symtab index
+    fn push_symbol(symtab: &mut Vec<symbol::Symbol>, shndx: usize, value: u64, size: u64,
+                   name: Vec<u8>, stype: types::SymbolType) -> usize {
+        symtab.push(symbol::Symbol{
+            shndx:  symbol::SymbolSectionIndex::Section(shndx as u32),
+            value,
+            size,
+            name,
+            stype,
+            bind:   types::SymbolBind::LOCAL,
+            vis:    types::SymbolVis::DEFAULT,
+            _name:  0,
+        });
+        symtab.len() - 1
+    }
+
+    /// the .got slot holding the address of symtab[sym]
+    fn got(&mut self, symtab: &mut Vec<symbol::Symbol>, header: &Header, reloc: &relocation::Relocation,
+           dynamic: Option<usize>, is_static: bool) -> usize {
+        if let Some(got_sym) = self.sym2got.get(&reloc.sym) {
+            return *got_sym;
+        }
+
+        let got_slot = self.got.len() as u64;
+        self.got.append(&mut vec![0;8]);
+        let name = [&symtab[reloc.sym as usize].name[..], b"__GOT"].concat();
+        let got_sym = DynamicSlots::push_symbol(symtab, self.shndx_got, got_slot, 8, name,
+                                                types::SymbolType::OBJECT);
+        symtab[got_sym].bind = types::SymbolBind::GLOBAL;
+
+        if let Some(dynsym_index) = dynamic {
+            self.dynrel_sym.push(relocation::Relocation{
+                addr:   got_slot,
+                sym:    dynsym_index as u32,
+                rtype:  relocation::RelocationType::R_X86_64_GLOB_DAT,
+                addend: 0,
+            });
+            self.dynrel_sym_addr.push(self.shndx_got);
+        } else if symtab[reloc.sym as usize].shndx == symbol::SymbolSectionIndex::Undefined {
+            warn!("{} to undefined symbol {} will be relocated to zeroed out GOT",
+                  reloc.rtype.typename(header).unwrap_or("?"),
+                  String::from_utf8_lossy(&symtab[reloc.sym as usize].name));
+        } else if is_static {
+            self.hrel.push((self.shndx_got, relocation::Relocation{
+                addr:   got_slot,
+                sym:    reloc.sym,
+                rtype:  relocation::RelocationType::R_X86_64_64,
+                addend: 0,
+            }));
+        } else {
+            self.dynrel.push(relocation::Relocation{
+                addr:   got_slot,
+                sym:    reloc.sym,
+                rtype:  relocation::RelocationType::R_X86_64_RELATIVE,
+                addend: 0,
+            });
+            self.dynrel_addr.push(self.shndx_got);
+        }
+
+        self.sym2got.insert(reloc.sym, got_sym);
+        got_sym
+    }
+
+    /// the .plt entry calling the import dynsym[dynsym_index], which symtab[sym] refers to
+    fn plt(&mut self, symtab: &mut Vec<symbol::Symbol>, sym: u32, dynsym_index: usize) -> usize {
+        if let Some(plt_sym) = self.sym2plt.get(&sym) {
+            return *plt_sym;
+        }
+
+        // the first .got.plt slots are reserved for the dynamic linker
+        let n = self.pltrel.len() as u64;
+        self.pltrel.push(relocation::Relocation{
+            addr:   (n + 3) * 8,
+            sym:    dynsym_index as u32,
+            rtype:  relocation::RelocationType::R_X86_64_JUMP_SLOT,
+            addend: 0,
+        });
+
+        let name = [&symtab[sym as usize].name[..], b"__PLT"].concat();
+        let plt_sym = DynamicSlots::push_symbol(symtab, self.shndx_plt, (n + 1) * 16, 16, name,
+                                                types::SymbolType::FUNC);
+        self.sym2plt.insert(sym, plt_sym);
+        plt_sym
+    }
+
+    /// the .iplt entry of the indirect function symtab[sym], along with the .got slot it jumps through
+    fn iplt(&mut self, symtab: &mut Vec<symbol::Symbol>, sym: u32) -> usize {
+        if let Some(iplt_sym) = self.sym2iplt.get(&sym) {
+            return *iplt_sym;
+        }
+
+        // jmp *slot(%rip); nopl 0(%rax,%rax,1); xchg %ax,%ax
+        let entry = self.iplt.len() as u64;
+        self.iplt.extend_from_slice(&[0xff, 0x25, 0x00, 0x00, 0x00, 0x00,
+            0x0f, 0x1f, 0x84, 0x00, 0x00, 0x00, 0x00, 0x00, 0x66, 0x90]);
+
+        let got_slot = self.got.len() as u64;
+        self.got.append(&mut vec![0; 8]);
+        self.irel.push(relocation::Relocation{
+            addr:   got_slot,
+            sym,
+            rtype:  relocation::RelocationType::R_X86_64_IRELATIVE,
+            addend: 0,
+        });
+
+        let name = [&symtab[sym as usize].name[..], b"__IGOT"].concat();
+        let got_sym = DynamicSlots::push_symbol(symtab, self.shndx_got, got_slot, 8, name,
+                                                types::SymbolType::OBJECT);
+        self.hrel.push((self.shndx_iplt, relocation::Relocation{
+            addr:   entry + 2,
+            sym:    got_sym as u32,
+            rtype:  relocation::RelocationType::R_X86_64_PC32,
+            addend: -4,
+        }));
+
+        let name = [&symtab[sym as usize].name[..], b"__IPLT"].concat();
+        let iplt_sym = DynamicSlots::push_symbol(symtab, self.shndx_iplt, entry, 16, name,
+                                                 types::SymbolType::FUNC);
+        self.sym2iplt.insert(sym, iplt_sym);
+        iplt_sym
+    }
+
+    /// the .got slots a TLS access of kind slot to symtab[sym] needs
+    fn tls(&mut self, symtab: &mut Vec<symbol::Symbol>, sym: u32, slot: relocation::RelocationType,
+           dynamic: Option<usize>) -> usize {
+        // all local dynamic accesses of a module share the one slot for its block
+        let key = if slot == relocation::RelocationType::R_X86_64_TLSLD {
+            (0, slot.0)
+        } else {
+            (sym, slot.0)
+        };
+        if let Some(tls_sym) = self.sym2tls.get(&key) {
+            return *tls_sym;
+        }
+
+        let got_slot = self.got.len() as u64;
+        let (rtype, size, suffix) = match slot {
+            relocation::RelocationType::R_X86_64_TLSGD =>
+                (relocation::RelocationType::R_X86_64_DTPMOD64, 16, &b"__TLS_GD"[..]),
+            relocation::RelocationType::R_X86_64_TLSLD =>
+                (relocation::RelocationType::R_X86_64_DTPMOD64, 16, &b"__TLS_LD"[..]),
+            relocation::RelocationType::R_X86_64_GOTTPOFF =>
+                (relocation::RelocationType::R_X86_64_TPOFF64, 8, &b"__TLS_IE"[..]),
+            _ =>
+                (relocation::RelocationType::R_X86_64_TLSDESC, 16, &b"__TLS_DESC"[..]),
+        };
+        self.got.append(&mut vec![0; size]);
+
+        // local symbols are resolved against the module itself,
+        // with the offset into its block as addend
+        if dynamic.is_none() && rtype != relocation::RelocationType::R_X86_64_DTPMOD64 {
+            self.sym_addend.insert(self.dynrel_sym.len(), sym as usize);
+        }
+        self.dynrel_sym.push(relocation::Relocation{
+            addr:   got_slot,
+            sym:    dynamic.unwrap_or(0) as u32,
+            rtype,
+            addend: 0,
+        });
+        self.dynrel_sym_addr.push(self.shndx_got);
+
+        if slot == relocation::RelocationType::R_X86_64_TLSGD {
+            if let Some(dynsym_index) = dynamic {
+                self.dynrel_sym.push(relocation::Relocation{
+                    addr:   got_slot + 8,
+                    sym:    dynsym_index as u32,
+                    rtype:  relocation::RelocationType::R_X86_64_DTPOFF64,
+                    addend: 0,
+                });
+                self.dynrel_sym_addr.push(self.shndx_got);
+            } else {
+                self.hrel.push((self.shndx_got, relocation::Relocation{
+                    addr:   got_slot + 8,
+                    sym,
+                    rtype:  relocation::RelocationType::R_X86_64_DTPOFF64,
+                    addend: 0,
+                }));
+            }
+        }
+
+        let name = if slot == relocation::RelocationType::R_X86_64_TLSLD {
+            suffix.to_vec()
+        } else {
+            [&symtab[sym as usize].name[..], suffix].concat()
+        };
+        let tls_sym = DynamicSlots::push_symbol(symtab, self.shndx_got, got_slot, size as u64, name,
+                                                types::SymbolType::OBJECT);
+        self.sym2tls.insert(key, tls_sym);
+        tls_sym
+    }
+}
 
 struct DynamicRelocator {
 }
 impl DynamicRelocator {
-    pub fn relocate (collected: &mut Collected) -> Result<(), Error>  {
+    pub fn relocate (collected: &mut Collected, args: &LdOptions) -> Result<(), Error>  {
+
+        // static executables are loaded at a fixed address, so everything is resolved right here
+        let is_static = collected.elf.header.etype == types::ElfType::EXEC;
+        let exec = !args.shared;
+        if is_static && !collected.needed.is_empty() {
+            error!("cannot link shared objects into a static executable: {}",
+                   collected.needed.iter().map(|n| String::from_utf8_lossy(n).into_owned())
+                   .collect::<Vec<String>>().join(", "));
+            process::abort();
+        }
 
-        let shndx_com = collected.elf.sections.len();
-        collected.elf.sections.push(section::Section::new(b".com".to_vec(),
-        types::SectionType::NOBITS, types::SectionFlags::ALLOC | types::SectionFlags::WRITE,
-        section::SectionContent::None, 0, 0));
-        collected.elf.sections[shndx_com].header.addralign = 16;
+        // symbols that are still undefined are imported at runtime, if a shared object exports them.
+        // shared objects import all of them and export their own globals.
+        // gnu hash lookups need the hashed symbols at the end, grouped by bucket,
+        // so do the sorting before anything refers to dynsym indices
+        let mut dynsym  = vec![symbol::Symbol::default()];
+        let mut imports = HashMap::new();
+        let mut exports = HashMap::new();
+        for (i, sym) in collected.symtab.iter().enumerate() {
+            if sym.bind == types::SymbolBind::LOCAL || sym.name.is_empty() {
+                continue;
+            }
+            match sym.shndx {
+                symbol::SymbolSectionIndex::Undefined => {
+                    let stype = match collected.shared.get(&sym.name) {
+                        Some(def) => def.stype.clone(),
+                        None if args.shared => sym.stype.clone(),
+                        None => continue,
+                    };
+                    imports.insert(i, dynsym.len());
+                    dynsym.push(symbol::Symbol{
+                        shndx:  symbol::SymbolSectionIndex::Undefined,
+                        value:  0,
+                        size:   0,
+                        name:   sym.name.clone(),
+                        stype:  match stype {
+                            types::SymbolType::GNU_IFUNC => types::SymbolType::FUNC,
+                            any => any,
+                        },
+                        bind:   sym.bind.clone(),
+                        vis:    types::SymbolVis::DEFAULT,
+                        _name:  0,
+                    });
+                },
+                // value and section are filled in after layout
+                symbol::SymbolSectionIndex::Section(_) if args.shared &&
+                    (sym.vis == types::SymbolVis::DEFAULT || sym.vis == types::SymbolVis::PROTECTED) => {
+                    exports.insert(i, dynsym.len());
+                    dynsym.push(sym.clone());
+                },
+                _ => {},
+            }
+        }
+        if args.hash_style != HashStyle::Sysv {
+            let remap = hash::gnu_hash_order(&mut dynsym);
+            for i in imports.values_mut().chain(exports.values_mut()) {
+                *i = remap[*i];
+            }
+        }
 
         let shndx_got = collected.elf.sections.len();
         collected.elf.sections.push(section::Section::new(b".got".to_vec(),
@@ -128,178 +876,221 @@ impl DynamicRelocator {
         section::SectionContent::None, 0, 0));
         collected.elf.sections[shndx_got].header.addralign = 16;
 
-        let mut dynrel      = Vec::new();
-        let mut dynrel_addr = Vec::new();
+        let shndx_plt = collected.elf.sections.len();
+        collected.elf.sections.push(section::Section::new(b".plt".to_vec(),
+        types::SectionType::PROGBITS, types::SectionFlags::ALLOC | types::SectionFlags::EXECINSTR,
+        section::SectionContent::None, 0, 0));
+        collected.elf.sections[shndx_plt].header.addralign = 16;
 
-        let mut hrel        = Vec::new();
-        let mut got         = Vec::new();
-        let mut sym2got     = HashMap::new();
-        let mut sym2tls     = HashMap::new();
+        let shndx_gotplt = collected.elf.sections.len();
+        collected.elf.sections.push(section::Section::new(b".got.plt".to_vec(),
+        types::SectionType::PROGBITS, types::SectionFlags::ALLOC | types::SectionFlags::WRITE,
+        section::SectionContent::None, 0, 0));
+        collected.elf.sections[shndx_gotplt].header.addralign = 8;
+
+        let shndx_iplt = collected.elf.sections.len();
+        collected.elf.sections.push(section::Section::new(b".iplt".to_vec(),
+        types::SectionType::PROGBITS, types::SectionFlags::ALLOC | types::SectionFlags::EXECINSTR,
+        section::SectionContent::None, 0, 0));
+        collected.elf.sections[shndx_iplt].header.addralign = 16;
 
-        for (shndx, relocs) in collected.relocs.drain() {
+        let mut slots = DynamicSlots::new(shndx_got, shndx_plt, shndx_iplt);
+
+        let mut relocs : Vec<(usize, Vec<relocation::Relocation>)> = collected.relocs.drain().collect();
+        relocs.sort_unstable_by_key(|&(shndx, _)| shndx);
+        for (shndx, mut relocs) in relocs {
+            // relaxing TLS code removes the call that follows, along with its relocation
+            relocs.sort_by_key(|reloc| reloc.addr);
+            let mut relaxed_end = 0;
             for mut reloc in relocs {
+                if reloc.addr < relaxed_end {
+                    continue;
+                }
                 let mut sym = collected.symtab.get(reloc.sym as usize).unwrap().clone();
                 match sym.shndx {
-                    symbol::SymbolSectionIndex::Section(_) => {},
+                    symbol::SymbolSectionIndex::Section(_) |
+                    symbol::SymbolSectionIndex::Absolute => {},
                     symbol::SymbolSectionIndex::Common => {
-                        let com_slot = collected.elf.sections[shndx_com].header.size;
-                        collected.elf.sections[shndx_com].header.size += sym.size;
-                        sym.value = com_slot;
-                        sym.shndx = symbol::SymbolSectionIndex::Section(shndx_com as u16);
-                        collected.symtab[reloc.sym as usize] = sym.clone();
+                        panic!("common symbol {:?} was not allocated", sym);
                     },
                     symbol::SymbolSectionIndex::Undefined => {
                         assert_eq!(sym.value, 0);
                     },
-                    _ => panic!("garbage relocation {:?} against {:?}", reloc, sym),
                 };
 
+                // indirect functions defined here are called through an .iplt entry, which jumps to
+                // whatever their resolver returned. the entry is also their address, so pointers to
+                // them compare equal everywhere
+                if sym.stype == types::SymbolType::GNU_IFUNC {
+                    if let symbol::SymbolSectionIndex::Section(_) = sym.shndx {
+                        let iplt_sym = slots.iplt(&mut collected.symtab, reloc.sym);
+                        reloc.sym = iplt_sym as u32;
+                        sym = collected.symtab[iplt_sym].clone();
+                    }
+                }
+
+                // references to exported symbols are resolved at runtime, so they can be interposed.
+                // protected ones, and all of them with -Bsymbolic, bind locally
+                let import  = imports.get(&(reloc.sym as usize)).cloned();
+                let dynamic = import.or_else(|| match exports.get(&(reloc.sym as usize)) {
+                    Some(i) if !args.symbolic && sym.vis == types::SymbolVis::DEFAULT => Some(*i),
+                    _ => None,
+                });
+
                 match reloc.rtype {
                     relocation::RelocationType::R_X86_64_64 => {
-                        reloc.rtype   = relocation::RelocationType::R_X86_64_RELATIVE;
-                        dynrel.push(reloc);
-                        dynrel_addr.push(shndx);
+                        if let Some(dynsym_index) = dynamic {
+                            reloc.sym = dynsym_index as u32;
+                            slots.dynrel_sym.push(reloc);
+                            slots.dynrel_sym_addr.push(shndx);
+                        } else if is_static || sym.shndx == symbol::SymbolSectionIndex::Absolute {
+                            // absolute values do not move with the load address
+                            slots.hrel.push((shndx, reloc));
+                        } else {
+                            reloc.rtype   = relocation::RelocationType::R_X86_64_RELATIVE;
+                            slots.dynrel.push(reloc);
+                            slots.dynrel_addr.push(shndx);
+                        }
                     },
                     relocation::RelocationType::R_X86_64_PC32 |
                     relocation::RelocationType::R_X86_64_PLT32 => {
+                        if let Some(dynsym_index) = dynamic {
+                            let function = matches!(collected.shared.get(&sym.name).map_or(&sym.stype, |s| &s.stype),
+                                                    types::SymbolType::FUNC | types::SymbolType::GNU_IFUNC);
+                            if !function && reloc.rtype == relocation::RelocationType::R_X86_64_PC32 {
+                                if import.is_some() {
+                                    error!("{} against '{}' from a shared object needs a copy relocation, \
+                                           which is not supported. maybe missing -fPIC ?",
+                                           reloc.rtype.typename(&collected.elf.header).unwrap_or("?"),
+                                           String::from_utf8_lossy(&sym.name));
+                                } else {
+                                    error!("{} against exported '{}' cannot be used in a shared object, \
+                                           since another definition may interpose it. \
+                                           maybe missing -fPIC, or link with -Bsymbolic ?",
+                                           reloc.rtype.typename(&collected.elf.header).unwrap_or("?"),
+                                           String::from_utf8_lossy(&sym.name));
+                                }
+                                process::abort();
+                            }
+                            reloc.sym = slots.plt(&mut collected.symtab, reloc.sym, dynsym_index) as u32;
+                        }
                         reloc.rtype = relocation::RelocationType::R_X86_64_PC32;
-                        hrel.push((shndx,reloc));
+                        slots.hrel.push((shndx,reloc));
                     },
                     relocation::RelocationType::R_X86_64_GOTPCREL |
                         relocation::RelocationType::R_X86_64_GOTPCRELX |
                         relocation::RelocationType::R_X86_64_REX_GOTPCRELX => {
-                        let got_sym = match sym2got.entry(reloc.sym) {
-                            hash_map::Entry::Occupied(e) => *e.get(),
-                            hash_map::Entry::Vacant(e) => {
-                                let got_slot = got.len();
-                                got.append(&mut vec![0;8]);
-                                let got_sym = collected.symtab.len();
-                                collected.symtab.push(symbol::Symbol{
-                                    shndx:  symbol::SymbolSectionIndex::Section(shndx_got as u16),
-                                    value:  got_slot as u64,
-                                    size:   8,
-                                    name:   [&sym.name[..], b"__GOT"].concat(),
-                                    stype:  types::SymbolType::OBJECT,
-                                    bind:   types::SymbolBind::GLOBAL,
-                                    vis:    types::SymbolVis::DEFAULT,
-                                    _name:  0,
-                                });
-
-                                if let symbol::SymbolSectionIndex::Undefined = sym.shndx {
-                                    warn!("{:?} to undefined symbol {} will be relocated to zeroed out GOT",
-                                          reloc.rtype, String::from_utf8_lossy(&sym.name));
-                                } else {
-                                    dynrel.push(relocation::Relocation{
-                                        addr:   got_slot as u64,
-                                        sym:    reloc.sym,
-                                        rtype:  relocation::RelocationType::R_X86_64_RELATIVE,
-                                        addend: 0,
-                                    });
-                                    dynrel_addr.push(shndx_got);
-                                }
-
-                                e.insert(got_sym);
-                                got_sym
-                            },
-                        };
+                        // the X variants mark instructions that can do without the GOT, when the symbol
+                        // ends up in this module and can't be interposed. the slot is only made for
+                        // references that can't
+                        let relaxable = args.relax && reloc.rtype != relocation::RelocationType::R_X86_64_GOTPCREL &&
+                            dynamic.is_none() && sym.stype != types::SymbolType::GNU_IFUNC &&
+                            matches!(sym.shndx, symbol::SymbolSectionIndex::Section(_));
+                        if relaxable {
+                            let relaxed = match collected.elf.sections[shndx].content.as_raw_mut() {
+                                Some(code) => relax_gotpcrel(code, reloc.addr as usize),
+                                None => None,
+                            };
+                            if let Some(addr) = relaxed {
+                                reloc.addr  = addr;
+                                reloc.rtype = relocation::RelocationType::R_X86_64_PC32;
+                                slots.hrel.push((shndx, reloc));
+                                continue;
+                            }
+                        }
 
-                        reloc.sym = got_sym as u32;
+                        reloc.sym = slots.got(&mut collected.symtab, &collected.elf.header, &reloc,
+                                              dynamic, is_static) as u32;
                         reloc.rtype = relocation::RelocationType::R_X86_64_PC32;
-                        hrel.push((shndx, reloc));
+                        slots.hrel.push((shndx, reloc));
+                    },
+                    relocation::RelocationType::R_X86_64_32 | relocation::RelocationType::R_X86_64_32S
+                        if is_static => {
+                        slots.hrel.push((shndx, reloc));
                     },
                     relocation::RelocationType::R_X86_64_32 | relocation::RelocationType::R_X86_64_32S => {
                         panic!("unsupported relocation. maybe missing -fPIC ? {:?} -> {:?}", reloc, sym);
                     },
 
-                    relocation::RelocationType::R_X86_64_GOTTPOFF |
+                    relocation::RelocationType::R_X86_64_TLSGD |
                     relocation::RelocationType::R_X86_64_TLSLD |
-                    relocation::RelocationType::R_X86_64_TLSGD => {
+                    relocation::RelocationType::R_X86_64_GOTTPOFF |
+                    relocation::RelocationType::R_X86_64_GOTPC32_TLSDESC |
+                    relocation::RelocationType::R_X86_64_TLSDESC_CALL => {
                         if sym.stype != types::SymbolType::TLS {
-                            //says drepper's paper at least. but then again i have no idea wtf the
-                            //TLS symbol is for anyway. this check could be removed i guess.
                             panic!("relocation {:?} cannot be used against non-tls symbol {:?}", reloc, sym);
                         }
-                        let tls_sym = match sym2tls.entry(reloc.sym) {
-                            hash_map::Entry::Occupied(e) => *e.get(),
-                            hash_map::Entry::Vacant(e) => {
-                                let got_slot = got.len();
-
-                                let gotsize = match reloc.rtype {
-                                    relocation::RelocationType::R_X86_64_TLSGD => {
-                                        elf_write_u64!(&collected.elf.header, got, 1)?;
-                                        elf_write_u64!(&collected.elf.header, got, sym.value)?;
-                                        16
-                                    },
-                                    relocation::RelocationType::R_X86_64_TLSLD => {
-                                        elf_write_u64!(&collected.elf.header, got, 1)?;
-                                        //The offset is already added by the compiler,
-                                        //so the offset in the tls entry must be 0.
-                                        //TODO but what's the point of all of this if it's always 0?
-                                        //i must still be missing something.
-                                        elf_write_u64!(&collected.elf.header, got, 0)?;
-                                        16
-                                    },
-                                    relocation::RelocationType::R_X86_64_GOTTPOFF => {
-                                        //TODO: this is entirely guesswork since it's not documented clearly
-                                        //tdata comes exactly before %fs on x86_64, like so:
-                                        //
-                                        //     -200 [ module 2 .tdata ]
-                                        //     -100 [ module 1 .tdata ]
-                                        //     0    $fs
-                                        //
-                                        // so a symbol value 10 in module 1 is 10 - 100 = -90.
-                                        // (this example ignores alignment)
-                                        //
-                                        let shndx_tdata = match sym.shndx {
-                                            symbol::SymbolSectionIndex::Section(shndx) => shndx,
-                                            _ => panic!("relocation against garbage symbol {:?} -> {:?}",
-                                                        reloc, sym),
-                                        };
-
-                                        let mut tlx = collected.elf.sections[shndx_tdata as usize].header.size;
-                                        let align = collected.elf.sections[shndx_tdata as usize].header.addralign;
-                                        if tlx % align > 0 {
-                                            tlx += align - (tlx % align);
-                                        }
-                                        tlx -= sym.value;
-                                        let tlx = -(tlx as i64);
-
-                                        elf_write_u64!(&collected.elf.header, got, tlx as u64)?;
-                                        8
-                                    }
-                                    _ => unreachable!(),
-                                };
-
-
-                                let tls_sym = collected.symtab.len();
-                                collected.symtab.push(symbol::Symbol{
-                                    shndx:  symbol::SymbolSectionIndex::Section(shndx_got as u16),
-                                    value:  got_slot as u64,
-                                    size:   gotsize,
-                                    name:   [&sym.name[..], b"__TLS_GOT"].concat(),
-                                    stype:  types::SymbolType::OBJECT,
-                                    bind:   types::SymbolBind::GLOBAL,
-                                    vis:    types::SymbolVis::DEFAULT,
-                                    _name:  0,
-                                });
-
-                                e.insert(tls_sym);
-                                tls_sym
-                            },
-                        };
 
+                        // an executable's own TLS block is at a fixed offset from the thread pointer,
+                        // so the __tls_get_addr calls and descriptors are rewritten to use that (LE).
+                        // symbols from shared objects are still in the initial TLS blocks, which the
+                        // dynamic linker puts at offsets it fills into the GOT (IE)
+                        let mut slot = reloc.rtype.clone();
+                        if exec {
+                            let relaxed = match collected.elf.sections[shndx].content.as_raw_mut() {
+                                Some(code) => relax_tls(code, &reloc.rtype, reloc.addr as usize, import.is_none()),
+                                None => None,
+                            };
+                            match relaxed {
+                                Some((TlsRelax::Le(at), end)) => {
+                                    relaxed_end = end;
+                                    // the addend was relative to the end of the instruction
+                                    slots.hrel.push((shndx, relocation::Relocation{
+                                        addr:   at,
+                                        sym:    reloc.sym,
+                                        rtype:  relocation::RelocationType::R_X86_64_TPOFF32,
+                                        addend: reloc.addend + 4,
+                                    }));
+                                    continue;
+                                },
+                                Some((TlsRelax::Ie(at), end)) => {
+                                    relaxed_end = end;
+                                    reloc.addr = at;
+                                    slot = relocation::RelocationType::R_X86_64_GOTTPOFF;
+                                },
+                                Some((TlsRelax::Done, end)) => {
+                                    relaxed_end = end;
+                                    continue;
+                                },
+                                None => {
+                                    error!("{} against '{}' at {}+0x{:x} is not in one of the code sequences \
+                                           the ABI specifies for it, so it cannot be relaxed",
+                                           reloc.rtype.typename(&collected.elf.header).unwrap_or("?"),
+                                           String::from_utf8_lossy(&sym.name),
+                                           String::from_utf8_lossy(&collected.elf.sections[shndx].name),
+                                           reloc.addr);
+                                    process::abort();
+                                },
+                            }
+                        } else if reloc.rtype == relocation::RelocationType::R_X86_64_TLSDESC_CALL {
+                            // only there to mark the call for relaxation
+                            continue;
+                        }
 
-                        reloc.sym = tls_sym as u32;
+                        reloc.sym = slots.tls(&mut collected.symtab, reloc.sym, slot, dynamic) as u32;
                         reloc.rtype = relocation::RelocationType::R_X86_64_PC32;
-                        hrel.push((shndx, reloc));
+                        slots.hrel.push((shndx, reloc));
                     },
-                    relocation::RelocationType::R_X86_64_DTPOFF32 => {
-                        reloc.rtype = relocation::RelocationType::R_X86_64_32;
-                        hrel.push((shndx, reloc));
+                    relocation::RelocationType::R_X86_64_DTPOFF32 |
+                    relocation::RelocationType::R_X86_64_DTPOFF64 => {
+                        // the local dynamic code of executables was relaxed to work off the thread pointer.
+                        // debug info still wants the offset into the block
+                        if exec && collected.elf.sections[shndx].header.flags.contains(types::SectionFlags::ALLOC) {
+                            reloc.rtype = if reloc.rtype == relocation::RelocationType::R_X86_64_DTPOFF32 {
+                                relocation::RelocationType::R_X86_64_TPOFF32
+                            } else {
+                                relocation::RelocationType::R_X86_64_TPOFF64
+                            };
+                        }
+                        slots.hrel.push((shndx, reloc));
+                    },
+                    relocation::RelocationType::R_X86_64_TPOFF32 |
+                    relocation::RelocationType::R_X86_64_TPOFF64 if exec => {
+                        slots.hrel.push((shndx, reloc));
                     },
                     _ => {
-                        panic!("relocating {:?} of '{}' in '{}' not implemented",
-                               reloc.rtype,
+                        panic!("relocating {} of '{}' in '{}' not implemented",
+                               reloc.rtype.typename(&collected.elf.header).unwrap_or("?"),
                                String::from_utf8_lossy(&sym.name),
                                String::from_utf8_lossy(&collected.elf.sections[shndx].name),
                               );
@@ -308,62 +1099,150 @@ impl DynamicRelocator {
             }
         }
 
+        let DynamicSlots{got, iplt, mut dynrel, mut dynrel_addr, mut dynrel_sym, mut dynrel_sym_addr,
+                         mut sym_addend, pltrel, irel, hrel, ..} = slots;
+
         collected.elf.sections[shndx_got].content = section::SectionContent::Raw(got);
+        collected.elf.sections[shndx_iplt].content = section::SectionContent::Raw(iplt);
+
+        // the dynamic linker calls the resolvers after everything else is relocated, because they
+        // might need some of it. static executables have libc do it, with what is between
+        // __rela_iplt_start and __rela_iplt_end
+        let mut shndx_relaiplt = None;
+        if is_static {
+            if !irel.is_empty() {
+                shndx_relaiplt = Some(collected.elf.sections.len());
+                collected.elf.sections.push(section::Section::new(b".rela.iplt".to_vec(),
+                types::SectionType::RELA, types::SectionFlags::ALLOC,
+                section::SectionContent::Relocations(irel), 0, 0));
+                collected.elf.sections.last_mut().unwrap().header.addralign = 8;
+            }
+        } else {
+            for rel in irel {
+                sym_addend.insert(dynrel_sym.len(), rel.sym as usize);
+                dynrel_sym.push(relocation::Relocation{sym: 0, ..rel});
+                dynrel_sym_addr.push(shndx_got);
+            }
+        }
 
-        let shndx_dynstr = collected.elf.sections.len();
-        collected.elf.sections.push(section::Section::new(b".dynstr".to_vec(),
-        types::SectionType::STRTAB, types::SectionFlags::ALLOC,
-        section::SectionContent::Strtab(elfkit::strtab::Strtab::default()), 0, 0));
-
-        let mut shndx_dynsym = collected.elf.sections.len();
-        collected.elf.sections.push(section::Section::new(b".dynsym".to_vec(),
-        types::SectionType::DYNSYM, types::SectionFlags::ALLOC,
-        section::SectionContent::Symbols(vec![symbol::Symbol::default()]), shndx_dynstr as u32, 0));
-
-        let mut shndx_hash = collected.elf.sections.len();
-        collected.elf.sections.push(section::Section::new(b".hash".to_vec(),
-        types::SectionType::HASH, types::SectionFlags::ALLOC,
-        section::SectionContent::None, shndx_dynsym  as u32, 0));
-
-        let mut shndx_reladyn = collected.elf.sections.len();
-        collected.elf.sections.push(section::Section::new(b".rela.dyn".to_vec(),
-        types::SectionType::RELA, types::SectionFlags::ALLOC,
-        section::SectionContent::Relocations(dynrel),
-        shndx_dynsym as u32, 0));
-        collected.elf.sections[shndx_reladyn].header.addralign = 8;
-
-        let mut shndx_dynamic = collected.elf.sections.len();
-        let dynamic = DynamicRelocator::dynamic(&collected.elf)?;
-        collected.elf.sections.push(section::Section::new(b".dynamic".to_vec(), types::SectionType::DYNAMIC,
-        types::SectionFlags::ALLOC | types::SectionFlags::WRITE, // TODO why writeable?
-        //section::SectionContent::Dynamic(vec![dynamic::Dynamic::default()]),
-        section::SectionContent::Dynamic(dynamic),
-        shndx_dynstr as u32,0));
-        collected.elf.sections[shndx_dynamic].header.addralign = 8;
+        // the actual code is written once the addresses are known.
+        // .plt starts with the entry that calls into the dynamic linker, .got.plt with 3 reserved slots
+        let num_plt = pltrel.len();
+        if num_plt > 0 {
+            collected.elf.sections[shndx_plt].content =
+                section::SectionContent::Raw(vec![0; (num_plt + 1) * 16]);
+            collected.elf.sections[shndx_gotplt].content =
+                section::SectionContent::Raw(vec![0; (num_plt + 3) * 8]);
+        }
 
+        // static executables have no dynamic linker that would read any of this
+        let mut shndx_dynsym    = None;
+        let mut shndx_hashes    = Vec::new();
+        let mut shndx_reladyn   = None;
+        let mut shndx_relaplt   = None;
+        let mut shndx_dynamic   = None;
+        let mut num_relative    = 0;
+        if !is_static {
+            let shndx_dynstr = collected.elf.sections.len();
+            collected.elf.sections.push(section::Section::new(b".dynstr".to_vec(),
+            types::SectionType::STRTAB, types::SectionFlags::ALLOC,
+            section::SectionContent::Strtab(elfkit::strtab::Strtab::default()), 0, 0));
+
+            let mut hashes = Vec::new();
+            if args.hash_style != HashStyle::Gnu {
+                hashes.push((b".hash".to_vec(), types::SectionType::HASH,
+                             section::SectionContent::Hash(hash::SysvHash::new(&dynsym))));
+            }
+            if args.hash_style != HashStyle::Sysv {
+                hashes.push((b".gnu.hash".to_vec(), types::SectionType::GNU_HASH,
+                             section::SectionContent::GnuHash(hash::GnuHash::new(&dynsym, &collected.elf.header)?)));
+            }
 
-        let remap = collected.reorder()?;
+            let shndx = collected.elf.sections.len();
+            shndx_dynsym = Some(shndx);
+            collected.elf.sections.push(section::Section::new(b".dynsym".to_vec(),
+            types::SectionType::DYNSYM, types::SectionFlags::ALLOC,
+            section::SectionContent::Symbols(dynsym), shndx_dynstr as u32, 0));
+
+            for (name, shtype, content) in hashes {
+                shndx_hashes.push(collected.elf.sections.len());
+                collected.elf.sections.push(section::Section::new(name, shtype, types::SectionFlags::ALLOC,
+                content, shndx as u32, 0));
+                collected.elf.sections.last_mut().unwrap().header.addralign = 8;
+            }
 
-        collected.elf.layout().unwrap();
+            // relative relocations come first, so they can be counted in DT_RELACOUNT
+            num_relative = dynrel.len();
+            dynrel.append(&mut dynrel_sym);
+            dynrel_addr.append(&mut dynrel_sym_addr);
+
+            shndx_reladyn = Some(collected.elf.sections.len());
+            collected.elf.sections.push(section::Section::new(b".rela.dyn".to_vec(),
+            types::SectionType::RELA, types::SectionFlags::ALLOC,
+            section::SectionContent::Relocations(dynrel),
+            shndx as u32, 0));
+            collected.elf.sections.last_mut().unwrap().header.addralign = 8;
+
+            if num_plt > 0 {
+                shndx_relaplt = Some(collected.elf.sections.len());
+                collected.elf.sections.push(section::Section::new(b".rela.plt".to_vec(),
+                types::SectionType::RELA, types::SectionFlags::ALLOC | types::SectionFlags::INFO_LINK,
+                section::SectionContent::Relocations(pltrel),
+                shndx as u32, shndx_gotplt as u32));
+                collected.elf.sections.last_mut().unwrap().header.addralign = 8;
+            }
 
-        if let Some(v) = remap.get(&shndx_hash) {
-            shndx_hash = *v;
+            shndx_dynamic = Some(collected.elf.sections.len());
+            let dynamic = DynamicRelocator::dynamic(&collected.elf, &collected.needed, args)?;
+            collected.elf.sections.push(section::Section::new(b".dynamic".to_vec(), types::SectionType::DYNAMIC,
+            types::SectionFlags::ALLOC | types::SectionFlags::WRITE, // TODO why writeable?
+            //section::SectionContent::Dynamic(vec![dynamic::Dynamic::default()]),
+            section::SectionContent::Dynamic(dynamic),
+            shndx_dynstr as u32,0));
+            collected.elf.sections.last_mut().unwrap().header.addralign = 8;
         }
-        if let Some(v) = remap.get(&shndx_dynsym) {
-            shndx_dynsym = *v;
+
+
+        // the script decides where its output sections go, the rest is placed after them
+        let mut script_symbols = HashMap::new();
+        if let Some(ref script) = args.script {
+            script_symbols = script.place(&mut collected.elf)?;
+            if args.image_base.is_none() {
+                if let Some(lowest) = collected.elf.section_start.values().min() {
+                    collected.elf.image_base = lowest - lowest % 0x1000;
+                }
+            }
         }
-        if let Some(v) = remap.get(&shndx_reladyn) {
-            shndx_reladyn = *v;
+
+        let remap = collected.reorder()?;
+
+        collected.elf.layout().unwrap();
+
+        for shndx in &mut shndx_hashes {
+            if let Some(v) = remap.get(shndx) {
+                *shndx = *v;
+            }
         }
-        if let Some(v) = remap.get(&shndx_dynamic) {
-            shndx_dynamic = *v;
+        let shndx_dynsym  = shndx_dynsym.map(|i| remap.get(&i).cloned().unwrap_or(i));
+        let shndx_reladyn = shndx_reladyn.map(|i| remap.get(&i).cloned().unwrap_or(i));
+        let shndx_relaplt = shndx_relaplt.map(|i| remap.get(&i).cloned().unwrap_or(i));
+        let shndx_relaiplt = shndx_relaiplt.map(|i| remap.get(&i).cloned().unwrap_or(i));
+        let shndx_got     = remap.get(&shndx_got).cloned().unwrap_or(shndx_got);
+        let shndx_dynamic = shndx_dynamic.map(|i| remap.get(&i).cloned().unwrap_or(i));
+        let shndx_plt     = remap.get(&shndx_plt).cloned().unwrap_or(shndx_plt);
+        let shndx_gotplt  = remap.get(&shndx_gotplt).cloned().unwrap_or(shndx_gotplt);
+
+        for shndx in shndx_hashes.iter().chain(shndx_dynsym.iter()).chain(shndx_reladyn.iter()) {
+            collected.elf.sections[*shndx].addrlock = true;
         }
+        if let Some(shndx_dynamic) = shndx_dynamic {
+            collected.elf.sections[shndx_dynamic].content = section::SectionContent::Dynamic(
+                DynamicRelocator::dynamic(&collected.elf, &collected.needed, args)?);
 
-        collected.elf.sections[shndx_hash   ].addrlock = true;
-        collected.elf.sections[shndx_dynsym ].addrlock = true;
-        collected.elf.sections[shndx_reladyn].addrlock = true;
-        collected.elf.sections[shndx_dynamic].content = section::SectionContent::Dynamic(
-            DynamicRelocator::dynamic(&collected.elf)?);
+            if let Some(shndx_relaplt) = shndx_relaplt {
+                DynamicRelocator::plt(&mut collected.elf, shndx_plt, shndx_gotplt, shndx_relaplt, shndx_dynamic)?;
+            }
+        }
 
 
 
@@ -381,24 +1260,42 @@ impl DynamicRelocator {
             }
         }
 
+        // TLS symbols are offsets into the TLS template, which the thread pointer points right after.
+        let (tls_start, tls_end) = collected.elf.segments.iter()
+            .find(|seg| seg.phtype == types::SegmentType::TLS)
+            .map(|seg| {
+                let mut size = seg.memsz;
+                let oa = size % seg.align.max(1);
+                if oa != 0 {
+                    size += seg.align - oa;
+                }
+                (seg.vaddr, size)
+            }).unwrap_or((0, 0));
+
         for sym in collected.symtab.iter_mut() {
             if let symbol::SymbolSectionIndex::Section(so) = sym.shndx {
                 let addr = collected.elf.sections[so as usize].header.addr;
 
-                if sym.stype != types::SymbolType::TLS {
+                if collected.elf.sections[so as usize].header.flags.contains(types::SectionFlags::TLS) {
+                    sym.value += addr - tls_start;
+                } else {
                     sym.value += addr;
                 }
-                if sym.name == b"_start" && sym.bind == types::SymbolBind::GLOBAL {
-                    collected.elf.header.entry = sym.value;
+            }
+            if sym.shndx == symbol::SymbolSectionIndex::Absolute {
+                if let Some(v) = script_symbols.get(&sym.name) {
+                    sym.value = *v;
                 }
             }
             match sym.name.as_slice() {
                 b"_DYNAMIC" => {
-                    sym.stype   = types::SymbolType::OBJECT;
-                    sym.bind    = types::SymbolBind::LOCAL;
-                    sym.vis     = types::SymbolVis::DEFAULT;
-                    sym.value   = collected.elf.sections[shndx_dynamic].header.addr;
-                    sym.shndx   = symbol::SymbolSectionIndex::Section(shndx_dynamic as u16);
+                    if let Some(shndx_dynamic) = shndx_dynamic {
+                        sym.stype   = types::SymbolType::OBJECT;
+                        sym.bind    = types::SymbolBind::LOCAL;
+                        sym.vis     = types::SymbolVis::DEFAULT;
+                        sym.value   = collected.elf.sections[shndx_dynamic].header.addr;
+                        sym.shndx   = symbol::SymbolSectionIndex::Section(shndx_dynamic as u32);
+                    }
                 },
                 b"__init_array_start" => {
                     if let Some(shndx_init_array) = shndx_init_array {
@@ -406,7 +1303,7 @@ impl DynamicRelocator {
                         sym.bind    = types::SymbolBind::LOCAL;
                         sym.vis     = types::SymbolVis::DEFAULT;
                         sym.value   = collected.elf.sections[shndx_init_array].header.addr;
-                        sym.shndx   = symbol::SymbolSectionIndex::Section(shndx_init_array as u16);
+                        sym.shndx   = symbol::SymbolSectionIndex::Section(shndx_init_array as u32);
                     }
                 },
                 b"__init_array_end" => {
@@ -416,7 +1313,7 @@ impl DynamicRelocator {
                         sym.vis     = types::SymbolVis::DEFAULT;
                         sym.value   = collected.elf.sections[shndx_init_array].header.addr +
                             collected.elf.sections[shndx_init_array].header.size;
-                        sym.shndx   = symbol::SymbolSectionIndex::Section(shndx_init_array as u16);
+                        sym.shndx   = symbol::SymbolSectionIndex::Section(shndx_init_array as u32);
                     }
                 },
                 b"__fini_array_start" => {
@@ -425,7 +1322,7 @@ impl DynamicRelocator {
                         sym.bind    = types::SymbolBind::LOCAL;
                         sym.vis     = types::SymbolVis::DEFAULT;
                         sym.value   = collected.elf.sections[shndx_fini_array].header.addr;
-                        sym.shndx   = symbol::SymbolSectionIndex::Section(shndx_fini_array as u16);
+                        sym.shndx   = symbol::SymbolSectionIndex::Section(shndx_fini_array as u32);
                     }
                 },
                 b"__fini_array_end" => {
@@ -435,29 +1332,89 @@ impl DynamicRelocator {
                         sym.vis     = types::SymbolVis::DEFAULT;
                         sym.value   = collected.elf.sections[shndx_fini_array].header.addr +
                             collected.elf.sections[shndx_fini_array].header.size;
-                        sym.shndx   = symbol::SymbolSectionIndex::Section(shndx_fini_array as u16);
+                        sym.shndx   = symbol::SymbolSectionIndex::Section(shndx_fini_array as u32);
+                    }
+                },
+                b"__rela_iplt_start" => {
+                    if let Some(shndx_relaiplt) = shndx_relaiplt {
+                        sym.stype   = types::SymbolType::NOTYPE;
+                        sym.bind    = types::SymbolBind::LOCAL;
+                        sym.vis     = types::SymbolVis::DEFAULT;
+                        sym.value   = collected.elf.sections[shndx_relaiplt].header.addr;
+                        sym.shndx   = symbol::SymbolSectionIndex::Section(shndx_relaiplt as u32);
+                    }
+                },
+                b"__rela_iplt_end" => {
+                    if let Some(shndx_relaiplt) = shndx_relaiplt {
+                        sym.stype   = types::SymbolType::NOTYPE;
+                        sym.bind    = types::SymbolBind::LOCAL;
+                        sym.vis     = types::SymbolVis::DEFAULT;
+                        sym.value   = collected.elf.sections[shndx_relaiplt].header.addr +
+                            collected.elf.sections[shndx_relaiplt].header.size;
+                        sym.shndx   = symbol::SymbolSectionIndex::Section(shndx_relaiplt as u32);
                     }
                 },
                 _ => {},
             }
         }
 
+        // a weak definition of the entry point will do, like it does for gnuld
+        let entry = collected.symtab.iter().find(|sym| {
+            sym.name == args.entry &&
+                (sym.bind == types::SymbolBind::GLOBAL || sym.bind == types::SymbolBind::WEAK) &&
+                matches!(sym.shndx, symbol::SymbolSectionIndex::Section(_) | symbol::SymbolSectionIndex::Absolute)
+        });
+        match entry {
+            Some(sym) => collected.elf.header.entry = sym.value,
+            None if exec => warn!("cannot find entry symbol {}, the entry point is left at 0",
+                                  String::from_utf8_lossy(&args.entry)),
+            None => {},
+        }
 
-        let mut reladyn = std::mem::replace(collected.elf.sections[shndx_reladyn].content
-                                        .as_relocations_mut().unwrap(), Vec::new());
-        for (i, rel) in reladyn.iter_mut().enumerate() {
-            let mut shndx = dynrel_addr[i];
-            if let Some(v) = remap.get(&shndx) {
-                shndx = *v;
+
+        if let Some(shndx_dynsym) = shndx_dynsym {
+            let mut dynsym = std::mem::take(collected.elf.sections[shndx_dynsym].content
+                                               .as_symbols_mut().unwrap());
+            for (i, dynsym_index) in &exports {
+                dynsym[*dynsym_index].value = collected.symtab[*i].value;
+                dynsym[*dynsym_index].shndx = collected.symtab[*i].shndx.clone();
+            }
+            collected.elf.sections[shndx_dynsym].content = section::SectionContent::Symbols(dynsym);
+        }
+        if let Some(shndx_reladyn) = shndx_reladyn {
+            let mut reladyn = std::mem::take(collected.elf.sections[shndx_reladyn].content
+                                            .as_relocations_mut().unwrap());
+            for (i, rel) in reladyn.iter_mut().enumerate() {
+                let mut shndx = dynrel_addr[i];
+                if let Some(v) = remap.get(&shndx) {
+                    shndx = *v;
+                }
+                if i >= num_relative {
+                    if let Some(sym) = sym_addend.get(&(i - num_relative)) {
+                        rel.addend += collected.symtab[*sym].value as i64;
+                    }
+                    rel.addr    += collected.elf.sections[shndx].header.addr;
+                    continue;
+                }
+                rel.addend  += collected.symtab[rel.sym as usize].value as i64;
+                rel.sym     =  0;
+                rel.addr    += collected.elf.sections[shndx].header.addr;
+                if rel.addend == 0 {
+                    error!("BUG emitting R_X86_64_RELATIVE with addend == 0 wont work.");
+                }
             }
-            rel.addend  += collected.symtab[rel.sym as usize].value as i64;
-            rel.sym     =  0;
-            rel.addr    += collected.elf.sections[shndx].header.addr;
-            if rel.addend == 0 {
-                error!("BUG emitting R_X86_64_RELATIVE with addend == 0 wont work.");
+            collected.elf.sections[shndx_reladyn].content = section::SectionContent::Relocations(reladyn);
+        }
+        if let Some(shndx_relaiplt) = shndx_relaiplt {
+            let mut relaiplt = std::mem::take(collected.elf.sections[shndx_relaiplt].content
+                                                 .as_relocations_mut().unwrap());
+            for rel in relaiplt.iter_mut() {
+                rel.addend  = collected.symtab[rel.sym as usize].value as i64;
+                rel.sym     = 0;
+                rel.addr   += collected.elf.sections[shndx_got].header.addr;
             }
+            collected.elf.sections[shndx_relaiplt].content = section::SectionContent::Relocations(relaiplt);
         }
-        collected.elf.sections[shndx_reladyn].content = section::SectionContent::Relocations(reladyn);
 
 
 
@@ -470,13 +1427,23 @@ impl DynamicRelocator {
             match reloc.rtype {
                 relocation::RelocationType::R_X86_64_PC32 => {
                     let vaddr = collected.elf.sections[shndx].header.addr + reloc.addr;
-                    let rv = ((sym.value as i64) + (reloc.addend as i64) - (vaddr as i64)) as i32;
+                    let value = (sym.value as i64) + reloc.addend - (vaddr as i64);
 
                     if sym.value == 0 {
-                        warn!("{:?} to undefined symbol  {}",  reloc.rtype,
+                        warn!("{} to undefined symbol  {}",
+                              reloc.rtype.typename(&collected.elf.header).unwrap_or("?"),
                               String::from_utf8_lossy(&sym.name));
                     }
 
+                    if value < i32::MIN as i64 || value > i32::MAX as i64 {
+                        error!("{} against '{}' in {} overflows: 0x{:x} does not fit. maybe the image base is too high?",
+                               reloc.rtype.typename(&collected.elf.header).unwrap_or("?"),
+                               String::from_utf8_lossy(&sym.name),
+                               String::from_utf8_lossy(&collected.elf.sections[shndx].name),
+                               value);
+                        process::abort();
+                    }
+
                     let w = match collected.elf.sections[shndx].content.as_raw_mut() {
                         Some(v) => v.as_mut_slice(),
                         None => {
@@ -491,9 +1458,27 @@ impl DynamicRelocator {
                     }
 
                     let mut w = &mut w[reloc.addr as usize ..];
-                    elf_write_u32!(&collected.elf.header, w, rv as u32)?;
+                    elf_write_u32!(&collected.elf.header, w, value as u32)?;
                 },
-                relocation::RelocationType::R_X86_64_32 => {
+                relocation::RelocationType::R_X86_64_64 |
+                relocation::RelocationType::R_X86_64_32 |
+                relocation::RelocationType::R_X86_64_32S => {
+                    let value = sym.value as i64 + reloc.addend;
+                    let overflow = match reloc.rtype {
+                        relocation::RelocationType::R_X86_64_32  => value < 0 || value > u32::MAX as i64,
+                        relocation::RelocationType::R_X86_64_32S => value < i32::MIN as i64 ||
+                                                                    value > i32::MAX as i64,
+                        _ => false,
+                    };
+                    if overflow {
+                        error!("{} against '{}' in {} overflows: 0x{:x} does not fit. maybe the image base is too high?",
+                               reloc.rtype.typename(&collected.elf.header).unwrap_or("?"),
+                               String::from_utf8_lossy(&sym.name),
+                               String::from_utf8_lossy(&collected.elf.sections[shndx].name),
+                               value);
+                        process::abort();
+                    }
+
                     let w = match collected.elf.sections[shndx].content.as_raw_mut() {
                         Some(v) => v.as_mut_slice(),
                         None => {
@@ -501,9 +1486,42 @@ impl DynamicRelocator {
                                    reloc, shndx);
                         }
                     };
-                    let value = sym.value as i64 + reloc.addend;
                     let mut w = &mut w[reloc.addr as usize ..];
-                    elf_write_u32!(&collected.elf.header, w, value as u32)?;
+                    if reloc.rtype == relocation::RelocationType::R_X86_64_64 {
+                        elf_write_u64!(&collected.elf.header, w, value as u64)?;
+                    } else {
+                        elf_write_u32!(&collected.elf.header, w, value as u32)?;
+                    }
+                },
+                relocation::RelocationType::R_X86_64_DTPOFF32 |
+                relocation::RelocationType::R_X86_64_DTPOFF64 |
+                relocation::RelocationType::R_X86_64_TPOFF32 |
+                relocation::RelocationType::R_X86_64_TPOFF64 => {
+                    if sym.shndx == symbol::SymbolSectionIndex::Undefined {
+                        warn!("{} to undefined symbol  {}",
+                              reloc.rtype.typename(&collected.elf.header).unwrap_or("?"),
+                              String::from_utf8_lossy(&sym.name));
+                    }
+                    let mut value = sym.value as i64 + reloc.addend;
+                    if reloc.rtype == relocation::RelocationType::R_X86_64_TPOFF32 ||
+                        reloc.rtype == relocation::RelocationType::R_X86_64_TPOFF64 {
+                        value -= tls_end as i64;
+                    }
+
+                    let w = match collected.elf.sections[shndx].content.as_raw_mut() {
+                        Some(v) => v.as_mut_slice(),
+                        None => {
+                            panic!("relocation {:?} against non-raw section {} makes no sense",
+                                   reloc, shndx);
+                        }
+                    };
+                    let mut w = &mut w[reloc.addr as usize ..];
+                    if reloc.rtype == relocation::RelocationType::R_X86_64_DTPOFF64 ||
+                        reloc.rtype == relocation::RelocationType::R_X86_64_TPOFF64 {
+                        elf_write_u64!(&collected.elf.header, w, value as u64)?;
+                    } else {
+                        elf_write_u32!(&collected.elf.header, w, value as u32)?;
+                    }
                 },
                 _ => unreachable!(),
             }
@@ -513,14 +1531,90 @@ impl DynamicRelocator {
 
     }
 
-    pub fn dynamic(elf: &Elf) -> Result<Vec<dynamic::Dynamic>, Error> {
+    /// write out the plt code and the initial .got.plt, which points back into the plt for lazy binding
+    fn plt(elf: &mut Elf, shndx_plt: usize, shndx_gotplt: usize, shndx_relaplt: usize,
+           shndx_dynamic: usize) -> Result<(), Error> {
+        let plt     = elf.sections[shndx_plt].header.addr;
+        let gotplt  = elf.sections[shndx_gotplt].header.addr;
+        let mut relaplt = std::mem::take(elf.sections[shndx_relaplt].content
+                                            .as_relocations_mut().unwrap());
+
+        let mut code = Vec::new();
+        // pushq GOT+8(%rip); jmp *GOT+16(%rip); nopl 0(%rax)
+        code.extend_from_slice(&[0xff, 0x35]);
+        elf_write_u32!(&elf.header, code, (gotplt + 8).wrapping_sub(plt + 6) as u32)?;
+        code.extend_from_slice(&[0xff, 0x25]);
+        elf_write_u32!(&elf.header, code, (gotplt + 16).wrapping_sub(plt + 12) as u32)?;
+        code.extend_from_slice(&[0x0f, 0x1f, 0x40, 0x00]);
+
+        let mut got = Vec::new();
+        elf_write_u64!(&elf.header, got, elf.sections[shndx_dynamic].header.addr)?;
+        elf_write_u64!(&elf.header, got, 0)?;
+        elf_write_u64!(&elf.header, got, 0)?;
+
+        for (n, rel) in relaplt.iter_mut().enumerate() {
+            let entry = plt + (n as u64 + 1) * 16;
+            rel.addr += gotplt;
+
+            // jmp *slot(%rip); pushq $n; jmp .plt
+            code.extend_from_slice(&[0xff, 0x25]);
+            elf_write_u32!(&elf.header, code, rel.addr.wrapping_sub(entry + 6) as u32)?;
+            code.push(0x68);
+            elf_write_u32!(&elf.header, code, n as u32)?;
+            code.push(0xe9);
+            elf_write_u32!(&elf.header, code, plt.wrapping_sub(entry + 16) as u32)?;
+
+            // until the first call, the slot points at the push above
+            elf_write_u64!(&elf.header, got, entry + 6)?;
+        }
+
+        elf.sections[shndx_plt].content     = section::SectionContent::Raw(code);
+        elf.sections[shndx_gotplt].content  = section::SectionContent::Raw(got);
+        elf.sections[shndx_relaplt].content = section::SectionContent::Relocations(relaplt);
+        elf.sections[shndx_plt].addrlock     = true;
+        elf.sections[shndx_gotplt].addrlock  = true;
+        elf.sections[shndx_relaplt].addrlock = true;
+        Ok(())
+    }
+
+    pub fn dynamic(elf: &Elf, needed: &[Vec<u8>], args: &LdOptions) -> Result<Vec<dynamic::Dynamic>, Error> {
         let mut padding = Vec::new();
-        let mut r = vec![
-            dynamic::Dynamic{
+        let mut r : Vec<dynamic::Dynamic> = needed.iter().map(|soname| dynamic::Dynamic{
+            dhtype:  types::DynamicType::NEEDED,
+            content: dynamic::DynamicContent::String((soname.clone(), None)),
+        }).collect();
+
+        if let Some(ref soname) = args.soname {
+            r.push(dynamic::Dynamic{
+                dhtype:  types::DynamicType::SONAME,
+                content: dynamic::DynamicContent::String((soname.clone().into_bytes(), None)),
+            });
+        }
+
+        let mut flags_1 = types::DynamicFlags1::empty();
+        if !args.shared {
+            flags_1.insert(types::DynamicFlags1::PIE);
+        }
+        let mut flags = types::DynamicFlags::empty();
+        if args.bind_now {
+            flags_1.insert(types::DynamicFlags1::NOW);
+            flags.insert(types::DynamicFlags::BIND_NOW);
+        }
+        if args.shared && args.symbolic {
+            flags.insert(types::DynamicFlags::SYMBOLIC);
+        }
+        if !flags.is_empty() {
+            r.push(dynamic::Dynamic{
+                dhtype: types::DynamicType::FLAGS,
+                content: dynamic::DynamicContent::Flags(flags),
+            });
+        }
+        if !flags_1.is_empty() {
+            r.push(dynamic::Dynamic{
                 dhtype: types::DynamicType::FLAGS_1,
-                content: dynamic::DynamicContent::Flags1(types::DynamicFlags1::PIE),
-            },
-        ];
+                content: dynamic::DynamicContent::Flags1(flags_1),
+            });
+        }
 
         for sec in &elf.sections {
             match sec.name.as_slice() {
@@ -530,6 +1624,12 @@ impl DynamicRelocator {
                         content: dynamic::DynamicContent::Address(sec.header.addr),
                     });
                 }
+                b".gnu.hash" => {
+                    r.push(dynamic::Dynamic {
+                        dhtype: types::DynamicType::GNU_HASH,
+                        content: dynamic::DynamicContent::Address(sec.header.addr),
+                    });
+                }
                 b".dynstr" => {
                     r.push(dynamic::Dynamic {
                         dhtype: types::DynamicType::STRTAB,
@@ -538,7 +1638,7 @@ impl DynamicRelocator {
 
                     r.push(dynamic::Dynamic {
                         dhtype: types::DynamicType::STRSZ,
-                        content: dynamic::DynamicContent::Address(sec.header.size),
+                        content: dynamic::DynamicContent::Size(sec.header.size),
                     });
                 }
                 b".dynsym" => {
@@ -548,7 +1648,28 @@ impl DynamicRelocator {
                     });
                     r.push(dynamic::Dynamic {
                         dhtype: types::DynamicType::SYMENT,
-                        content: dynamic::DynamicContent::Address(sec.header.entsize),
+                        content: dynamic::DynamicContent::Size(sec.header.entsize),
+                    });
+                }
+                // by content, so .dynamic has the same size before and after layout
+                b".got.plt" if sec.size(&elf.header) > 0 => {
+                    r.push(dynamic::Dynamic {
+                        dhtype: types::DynamicType::PLTGOT,
+                        content: dynamic::DynamicContent::Address(sec.header.addr),
+                    });
+                }
+                b".rela.plt" => {
+                    r.push(dynamic::Dynamic {
+                        dhtype: types::DynamicType::PLTRELSZ,
+                        content: dynamic::DynamicContent::Size(sec.header.size),
+                    });
+                    r.push(dynamic::Dynamic {
+                        dhtype: types::DynamicType::PLTREL,
+                        content: dynamic::DynamicContent::PltRel(types::DynamicType::RELA),
+                    });
+                    r.push(dynamic::Dynamic {
+                        dhtype: types::DynamicType::JMPREL,
+                        content: dynamic::DynamicContent::Address(sec.header.addr),
                     });
                 }
                 b".rela.dyn" => {
@@ -558,11 +1679,11 @@ impl DynamicRelocator {
                     });
                     r.push(dynamic::Dynamic {
                         dhtype: types::DynamicType::RELASZ,
-                        content: dynamic::DynamicContent::Address(sec.header.size),
+                        content: dynamic::DynamicContent::Size(sec.header.size),
                     });
                     r.push(dynamic::Dynamic {
                         dhtype: types::DynamicType::RELAENT,
-                        content: dynamic::DynamicContent::Address(sec.header.entsize),
+                        content: dynamic::DynamicContent::Size(sec.header.entsize),
                     });
 
                     let first_non_rela = match sec.content.as_relocations() {
@@ -579,7 +1700,7 @@ impl DynamicRelocator {
                     if first_non_rela > 0 {
                         r.push(dynamic::Dynamic {
                             dhtype: types::DynamicType::RELACOUNT,
-                            content: dynamic::DynamicContent::Address(first_non_rela),
+                            content: dynamic::DynamicContent::Count(first_non_rela),
                         });
                     } else {
                         padding.push(dynamic::Dynamic::default());
@@ -608,10 +1729,25 @@ impl DynamicRelocator {
 }
 
 
+/// where an input section went, for the map file
+pub struct MapEntry {
+    /// the object and section it came from, like `libc.a::printf.o(.text.printf)`
+    pub object: String,
+    pub shndx:  usize,
+    pub offset: u64,
+    pub size:   u64,
+}
+
 pub struct Collected {
     pub elf:        Elf,
     pub symtab:     Vec<symbol::Symbol>,
     pub relocs:     HashMap<usize, Vec<relocation::Relocation>>,
+    /// sonames of the shared objects linked against
+    pub needed:     Vec<Vec<u8>>,
+    /// symbols exported by the shared objects linked against
+    pub shared:     HashMap<Vec<u8>, symbol::Symbol>,
+    /// every merged input section, in merge order
+    pub map:        Vec<MapEntry>,
 }
 
 impl Collected {
@@ -630,13 +1766,28 @@ impl Collected {
         section::SectionContent::Symbols(self.symtab),
         sh_index_strtab as u32, first_global_symtab as u32));
 
-        for (shndx, relocs) in self.relocs {
+        self.elf.sections[sh_index_symtab].header.addralign = 8;
+
+        let mut relocs : Vec<(usize, Vec<relocation::Relocation>)> = self.relocs.into_iter()
+            .filter(|(_, relocs)| !relocs.is_empty()).collect();
+        relocs.sort_unstable_by_key(|&(shndx, _)| shndx);
+
+        //with this many sections some symbol indices no longer fit into the symbol.
+        //the content is generated in sync_all
+        if self.elf.sections.len() + relocs.len() + 2 >= types::SHN_LORESERVE as usize {
+            self.elf.sections.push(section::Section::new(b".symtab_shndx".to_vec(),
+            types::SectionType::SYMTAB_SHNDX, types::SectionFlags::empty(),
+            section::SectionContent::Raw(Vec::new()), sh_index_symtab as u32, 0));
+        }
+
+        for (shndx, relocs) in relocs {
             let mut name = b".rela".to_vec();
             name.append(&mut self.elf.sections[shndx].name.clone());
 
             self.elf.sections.push(section::Section::new(name, types::SectionType::RELA,
-                                                              types::SectionFlags::empty(),
+                                                              types::SectionFlags::INFO_LINK,
                                                               section::SectionContent::Relocations(relocs), sh_index_symtab as u32, shndx as u32));
+            self.elf.sections.last_mut().unwrap().header.addralign = 8;
         }
 
         self.elf.sections.push(section::Section::new(b".shstrtab".to_vec(), types::SectionType::STRTAB,
@@ -651,7 +1802,7 @@ impl Collected {
         for sym in &mut self.symtab {
             if let symbol::SymbolSectionIndex::Section(shndx) = sym.shndx {
                 if let Some(nu) = reorder.get(&(shndx as usize)) {
-                    sym.shndx = symbol::SymbolSectionIndex::Section(*nu as u16);
+                    sym.shndx = symbol::SymbolSectionIndex::Section(*nu as u32);
                 }
             }
         }
@@ -663,6 +1814,12 @@ impl Collected {
             self.relocs.insert(i, reloc);
         }
 
+        for entry in &mut self.map {
+            if let Some(nu) = reorder.get(&entry.shndx) {
+                entry.shndx = *nu;
+            }
+        }
+
         Ok(reorder)
     }
 
@@ -673,10 +1830,51 @@ pub trait Collector {
 }
 
 
+/// the output section an input section goes into, without a linker script
+fn output_name(name: &[u8]) -> Vec<u8> {
+    let mut name = name.to_vec();
+    if (name.len() > 3 && &name[0..4] == b".bss") || name == b"COMMON" {
+        name = b".bss".to_vec();
+    }
+    if name.len() > 6 && &name[0..7] == b".rodata" {
+        name = b".rodata".to_vec();
+    }
+    if name.starts_with(b".data.rel.ro") {
+        // kept apart, so it can be read only after relocation
+        name = b".data.rel.ro".to_vec();
+    } else if name.len() > 4 && &name[0..5] == b".data" {
+        name = b".data".to_vec();
+    }
+    if name.len() > 4 && &name[0..5] == b".text" {
+        name = b".text".to_vec();
+    }
+    if name.len() > 5 && &name[0..6] == b".tdata" {
+        name = b".tdata".to_vec();
+    }
+    if name.starts_with(b".tbss") {
+        name = b".tbss".to_vec();
+    }
+    name
+}
+
+/// the SHF_MERGE sections of a link, with their duplicate entries removed
+#[derive(Default)]
+struct MergedSections {
+    groups: Vec<merge::Merged>,
+    /// alignment of each group, the largest of its inputs
+    align:  Vec<u64>,
+    /// group and input index of each merged input section
+    inputs: HashMap<symbolic_linker::LinkGlobalId, (usize, usize)>,
+    /// output section and offset of the groups that were placed already
+    placed: HashMap<usize, (usize, usize)>,
+}
+
 /// a dummy implementation of Collector which works for testing
 pub struct SimpleCollector {
     pub collected:  Collected,
     sections: IndexMap<Vec<u8>, section::Section>,
+    script:   Option<Script>,
+    defsym:   Vec<linker_script::Assignment>,
 }
 
 impl Collector for SimpleCollector {
@@ -707,25 +1905,93 @@ impl SimpleCollector {
             elf: elf,
             relocs:     HashMap::new(),
             symtab:     Vec::new(),
+            needed:     Vec::new(),
+            shared:     HashMap::new(),
+            map:        Vec::new(),
         };
 
 
         Self{
             collected:  collected,
             sections:   sections,
+            script:     None,
+            defsym:     Vec::new(),
         }
     }
 
+    /// merge into the output sections of a linker script, in its order
+    pub fn with_script(mut self, script: Option<Script>) -> Self {
+        self.script = script;
+        self
+    }
+
+    /// symbols from --defsym, which win over definitions in the inputs
+    pub fn with_defsym(mut self, defsym: Vec<linker_script::Assignment>) -> Self {
+        self.defsym = defsym;
+        self
+    }
+
     fn collect(mut self, mut linker: SymbolicLinker) -> Self {
 
         let mut input_map = HashMap::new();
+        self.collected.needed = linker.needed;
+        self.collected.shared = linker.shared;
+
+        let mut script_symbols = HashMap::new();
+        let mut discarded = HashSet::new();
+
+        // relocations against the section symbol of a merged section have the offset in the addend,
+        // which is moved along with the entry. named symbols are moved below
+        let mut merged = self.merge_entries(&linker.objects);
+        for object in linker.objects.values_mut() {
+            for reloc in &mut object.relocs {
+                let link = &linker.symtab[reloc.sym as usize];
+                if link.sym.stype != types::SymbolType::SECTION {
+                    continue;
+                }
+                if let Some(&(g, i)) = merged.inputs.get(&link.obj) {
+                    reloc.addend = merged.groups[g].offset(i, link.sym.value as i64 + reloc.addend);
+                }
+            }
+        }
+
+        if let Some(script) = self.script.take() {
+            self.collect_script(&script, &mut linker.objects, &mut merged,
+                                &mut input_map, &mut script_symbols, &mut discarded);
+        }
+
+        // in link order, so the output is the same every time
+        let mut objects : Vec<symbolic_linker::Object> = linker.objects.drain().map(|(_, o)| o).collect();
+        objects.sort_by_key(|o| o.lid);
 
-        for (_, object) in linker.objects {
-            let (nu_shndx, nu_off) = self.merge(object.section, object.relocs, object.name);
+        for object in objects {
+            let (nu_shndx, nu_off) = match merged.inputs.get(&object.lid) {
+                Some(&(g, _)) => {
+                    let name = output_name(&object.section.name);
+                    self.merge_group(name, object.section, g, &mut merged)
+                },
+                None => self.merge(object.section, object.relocs, object.name),
+            };
             input_map.insert(object.lid, (nu_shndx, nu_off));
         }
 
         for loc in &mut linker.symtab {
+            if discarded.contains(&loc.obj) {
+                if let symbol::SymbolSectionIndex::Section(_) = loc.sym.shndx {
+                    warn!("symbol {} is in a section discarded by the linker script",
+                          String::from_utf8_lossy(&loc.sym.name));
+                    loc.sym.shndx = symbol::SymbolSectionIndex::Undefined;
+                    loc.sym.value = 0;
+                }
+            }
+            if loc.sym.shndx == symbol::SymbolSectionIndex::Absolute {
+                if let Some(&(shndx, value)) = script_symbols.get(&loc.sym.name) {
+                    loc.sym.shndx = symbol::SymbolSectionIndex::Section(shndx as u32);
+                    loc.sym.value = value;
+                    self.collected.symtab.push(loc.sym.clone());
+                    continue;
+                }
+            }
             match loc.sym.shndx {
                 symbol::SymbolSectionIndex::Section(_) => {
                     match input_map.get(&loc.obj) {
@@ -734,7 +2000,12 @@ impl SimpleCollector {
                         },
                         Some(&(nu_shndx, nu_off)) =>  {
                             if let symbol::SymbolSectionIndex::Section(_) = loc.sym.shndx {
-                                loc.sym.shndx = symbol::SymbolSectionIndex::Section(nu_shndx as u16);
+                                loc.sym.shndx = symbol::SymbolSectionIndex::Section(nu_shndx as u32);
+                                loc.sym.value = match merged.inputs.get(&loc.obj) {
+                                    Some(_) if loc.sym.stype == types::SymbolType::SECTION => 0,
+                                    Some(&(g, i)) => merged.groups[g].offset(i, loc.sym.value as i64) as u64,
+                                    None => loc.sym.value,
+                                };
                                 loc.sym.value += nu_off as u64;
                             }
                             self.collected.symtab.push(loc.sym.clone());
@@ -751,36 +2022,285 @@ impl SimpleCollector {
             }
         }
 
+        let defsym = std::mem::take(&mut self.defsym);
+        for a in &defsym {
+            self.define(a);
+        }
+
         self
     }
 
-    fn merge(&mut self, mut sec: section::Section, rela: Vec<relocation::Relocation>, objname: String) -> (usize, usize) {
+    /// evaluate a --defsym. a symbol defined relative to another one ends up in the same section,
+    /// so it moves with the load address
+    fn define(&mut self, a: &linker_script::Assignment) {
+        let mut values = HashMap::new();
+        let mut shndx  = symbol::SymbolSectionIndex::Absolute;
+        for name in a.expr.symbols() {
+            let target = self.collected.symtab.iter().find(|s| {
+                s.name == name && s.bind != types::SymbolBind::LOCAL &&
+                    s.shndx != symbol::SymbolSectionIndex::Undefined
+            });
+            match target {
+                Some(sym) => {
+                    if values.is_empty() {
+                        shndx = sym.shndx.clone();
+                    }
+                    values.insert(name.to_vec(), sym.value);
+                },
+                None => {
+                    error!("--defsym {}: undefined symbol {}", String::from_utf8_lossy(&a.symbol),
+                           String::from_utf8_lossy(name));
+                    process::abort();
+                },
+            }
+        }
+
+        let env = linker_script::Env{
+            dot:        0,
+            symbols:    &values,
+            sections:   &HashMap::new(),
+            memory:     &[],
+        };
+        let value = match a.expr.eval(&env) {
+            Ok(v) => v,
+            Err(e) => {
+                error!("--defsym {}: {:?}", String::from_utf8_lossy(&a.symbol), e);
+                process::abort();
+            },
+        };
+        for sym in &mut self.collected.symtab {
+            if sym.name == a.symbol && sym.bind != types::SymbolBind::LOCAL {
+                sym.shndx = shndx.clone();
+                sym.value = value;
+            }
+        }
+    }
+
+    /// merge the objects the script places in the order it places them.
+    /// a group of merged sections goes where the first of them is placed.
+    /// symbols assigned relative to an output section are returned with their section and offset
+    fn collect_script(&mut self, script: &Script,
+                      objects: &mut HashMap<symbolic_linker::LinkGlobalId, symbolic_linker::Object>,
+                      merged: &mut MergedSections,
+                      input_map: &mut HashMap<symbolic_linker::LinkGlobalId, (usize, usize)>,
+                      script_symbols: &mut HashMap<Vec<u8>, (usize, u64)>,
+                      discarded: &mut HashSet<symbolic_linker::LinkGlobalId>) {
+
+        let mut lids : Vec<symbolic_linker::LinkGlobalId> = objects.keys().cloned().collect();
+        lids.sort();
+        let mut placed = HashMap::new();
+        for lid in lids {
+            let obj = &objects[&lid];
+            if obj.section.header.shtype == types::SectionType::NULL {
+                continue;
+            }
+            if let Some(at) = script.input_section(object_file(obj), &obj.section.name) {
+                placed.entry(at).or_insert_with(Vec::new).push(lid);
+            }
+        }
+
+        let constants = script.constants();
+        for (i, cmd) in script.commands.iter().enumerate() {
+            let out = match *cmd {
+                linker_script::Command::Output(ref out) => out,
+                _ => continue,
+            };
+            for (j, cmd) in out.commands.iter().enumerate() {
+                match *cmd {
+                    linker_script::OutputCommand::Input(ref input) => {
+                        let mut lids = placed.remove(&(i, j)).unwrap_or_default();
+                        if input.sort {
+                            lids.sort_by(|a, b| objects[a].section.name.cmp(&objects[b].section.name));
+                        }
+                        for lid in lids {
+                            let mut object = objects.remove(&lid).unwrap();
+                            if out.is_discard() {
+                                discarded.insert(lid);
+                                continue;
+                            }
+                            if out.noload && object.section.header.shtype != types::SectionType::NOBITS {
+                                object.section.content = section::SectionContent::None;
+                                object.section.header.shtype = types::SectionType::NOBITS;
+                                object.relocs.clear();
+                            }
+                            let r = match merged.inputs.get(&lid) {
+                                Some(&(g, _)) => self.merge_group(out.name.clone(), object.section, g, merged),
+                                None => self.merge_into(out.name.clone(), object.section, object.relocs, object.name),
+                            };
+                            input_map.insert(lid, r);
+                        }
+                    },
+                    linker_script::OutputCommand::Assign(ref a) => {
+                        if out.is_discard() || (a.symbol != b"." && !a.expr.uses_dot()) {
+                            continue;
+                        }
+                        let shndx  = self.output_section(&out.name);
+                        let offset = match self.sections[shndx].content {
+                            section::SectionContent::Raw(ref r) => r.len() as u64,
+                            _ => self.sections[shndx].header.size,
+                        };
+                        let v = match a.expr.eval(&linker_script::Env{
+                            dot:        offset,
+                            symbols:    &constants,
+                            sections:   &HashMap::new(),
+                            memory:     &script.memory,
+                        }) {
+                            Ok(v) => v,
+                            Err(e) => {
+                                error!("in output section {} of the linker script: {:?}",
+                                       String::from_utf8_lossy(&out.name), e);
+                                process::abort();
+                            },
+                        };
 
-        let mut name = sec.name.clone();
-        if name.len() > 3 && &name[0..4] == b".bss" {
-            name = b".bss".to_vec();
+                        if a.symbol != b"." {
+                            script_symbols.insert(a.symbol.clone(), (shndx, v));
+                            continue;
+                        }
+                        if v < offset {
+                            error!("linker script moves . backwards in {}", String::from_utf8_lossy(&out.name));
+                            process::abort();
+                        }
+                        // aligning . aligns what comes next, so the section needs to be aligned as well
+                        if let linker_script::Expr::Align(None, ref align) = a.expr {
+                            let align = align.eval_at(0).unwrap_or(1);
+                            if align > self.sections[shndx].header.addralign {
+                                self.sections[shndx].header.addralign = align;
+                            }
+                        }
+                        match self.sections[shndx].content {
+                            section::SectionContent::Raw(ref mut r) => {
+                                r.resize(v as usize, 0);
+                            },
+                            _ => {
+                                self.sections[shndx].header.size = v;
+                            },
+                        }
+                    },
+                }
+            }
         }
-        if name.len() > 6 && &name[0..7] == b".rodata" {
-            name = b".rodata".to_vec();
+    }
+
+    /// merge the entries of SHF_MERGE sections that go into the same output section and have the
+    /// same entry size.
+    /// relocatable outputs may be linked with more, so they're left alone
+    fn merge_entries(&self, objects: &HashMap<symbolic_linker::LinkGlobalId, symbolic_linker::Object>)
+        -> MergedSections {
+        let mut merged = MergedSections::default();
+        if self.collected.elf.header.etype == types::ElfType::REL {
+            return merged;
         }
-        if name.len() > 4 && &name[0..5] == b".data" {
-            name = b".data".to_vec();
+
+        // in link order, so the output is the same every time
+        let mut objects : Vec<&symbolic_linker::Object> = objects.values().collect();
+        objects.sort_by_key(|o| o.lid);
+
+        let mut groups : IndexMap<(Vec<u8>, u64, bool), Vec<&symbolic_linker::Object>> = IndexMap::new();
+        for object in objects {
+            let sec = &object.section;
+            if !sec.header.flags.contains(types::SectionFlags::MERGE) ||
+                sec.header.flags.contains(types::SectionFlags::COMPRESSED) ||
+                sec.header.entsize == 0 || !object.relocs.is_empty() {
+                continue;
+            }
+            // the output section the script puts it in. sections the script doesn't mention
+            // go where they would without it
+            let name = match self.script.as_ref().and_then(|script| {
+                script.input_section(object_file(object), &sec.name).map(|(i, _)| &script.commands[i])
+            }) {
+                Some(linker_script::Command::Output(out)) if out.is_discard() || out.noload => continue,
+                Some(linker_script::Command::Output(out)) => out.name.clone(),
+                _ => output_name(&sec.name),
+            };
+            if let section::SectionContent::Raw(_) = sec.content {
+                let strings = sec.header.flags.contains(types::SectionFlags::STRINGS);
+                groups.entry((name, sec.header.entsize, strings)).or_default()
+                    .push(object);
+            }
         }
-        if name.len() > 4 && &name[0..5] == b".text" {
-            name = b".text".to_vec();
+
+        for ((_, entsize, strings), objects) in groups {
+            let g = merged.groups.len();
+            let inputs : Vec<&[u8]> = objects.iter().map(|o| &o.section.content.as_raw().unwrap()[..]).collect();
+            merged.groups.push(merge::merge(&inputs, entsize as usize, strings));
+            merged.align.push(objects.iter().map(|o| o.section.header.addralign).max().unwrap_or(1));
+            for (i, object) in objects.iter().enumerate() {
+                merged.inputs.insert(object.lid, (g, i));
+            }
         }
-        if name.len() > 5 && &name[0..6] == b".tdata" {
-            name = b".tdata".to_vec();
+        merged
+    }
+
+    /// place the merged entries of group g into an output section, unless an earlier input of the
+    /// group did already. every input of the group starts at the same place
+    fn merge_group(&mut self, name: Vec<u8>, mut sec: section::Section, g: usize,
+                   merged: &mut MergedSections) -> (usize, usize) {
+        match merged.placed.entry(g) {
+            hash_map::Entry::Occupied(e) => *e.get(),
+            hash_map::Entry::Vacant(e) => {
+                let objname = format!("(merged {} entsize {})", String::from_utf8_lossy(&name),
+                                      sec.header.entsize);
+                sec.content = section::SectionContent::Raw(merged.groups[g].content.clone());
+                sec.header.addralign = merged.align[g];
+                sec.header.flags.remove(types::SectionFlags::MERGE | types::SectionFlags::STRINGS);
+                sec.header.entsize = 0;
+                *e.insert(self.merge_into(name, sec, Vec::new(), objname))
+            },
         }
-        if name.len() > 6 && &name[0..7] == b".debug_" {
-            name = b".dbgFIXME".to_vec();
+    }
+
+    /// the index of an output section, which is created empty if nothing was merged into it yet
+    fn output_section(&mut self, name: &[u8]) -> usize {
+        if let Some((i, _, _)) = self.sections.get_full(name) {
+            return i;
         }
+        let mut sec = section::Section::new(name.to_vec(), types::SectionType::NOBITS,
+        types::SectionFlags::ALLOC | types::SectionFlags::WRITE, section::SectionContent::None, 0, 0);
+        sec.header.addralign = 1;
+        self.sections.insert(name.to_vec(), sec);
+        self.sections.len() - 1
+    }
+
+    fn merge(&mut self, sec: section::Section, rela: Vec<relocation::Relocation>, objname: String) -> (usize, usize) {
+        let name = output_name(&sec.name);
+        self.merge_into(name, sec, rela, objname)
+    }
+
+    fn merge_into(&mut self, name: Vec<u8>, mut sec: section::Section, rela: Vec<relocation::Relocation>,
+                  objname: String) -> (usize, usize) {
 
         sec.header.flags.remove(types::SectionFlags::GROUP);
+        let size = match sec.content {
+            section::SectionContent::None => sec.header.size,
+            _ => sec.size(&self.collected.elf.header) as u64,
+        };
 
         let (nu_shndx, nu_off) = match self.sections.entry(name.clone()) {
             indexmap::map::Entry::Occupied(mut e) => {
                 let i  = e.index();
+
+                // an output section created empty by the script takes on what goes in first.
+                // data and bss in the same output section turn the bss into zeros
+                let empty = e.get().header.size == 0 && e.get().size(&self.collected.elf.header) == 0;
+                match (&e.get().content, &sec.content) {
+                    (&section::SectionContent::None, &section::SectionContent::Raw(_)) => {
+                        let size = e.get().header.size as usize;
+                        e.get_mut().content = section::SectionContent::Raw(vec![0; size]);
+                        e.get_mut().header.shtype = sec.header.shtype.clone();
+                    },
+                    (&section::SectionContent::Raw(_), &section::SectionContent::None) => {
+                        sec.content = section::SectionContent::Raw(vec![0; sec.header.size as usize]);
+                    },
+                    _ => {},
+                }
+                if empty {
+                    e.get_mut().header.flags = sec.header.flags;
+                } else {
+                    e.get_mut().header.flags.insert(sec.header.flags &
+                        (types::SectionFlags::WRITE | types::SectionFlags::EXECINSTR));
+                }
+
                 let ov = match sec.content {
                     section::SectionContent::Raw(mut r) => {
                         let align = std::cmp::max(e.get().header.addralign, sec.header.addralign);
@@ -796,10 +2316,21 @@ impl SimpleCollector {
                         ov
                     },
                     section::SectionContent::None => {
-                        let ov = e.get().header.size;
-                        e.get_mut().header.size += sec.header.size as u64;
+                        let align = std::cmp::max(e.get().header.addralign, sec.header.addralign);
+                        e.get_mut().header.addralign = align;
+
+                        let mut ov = e.get().header.size;
+                        if align > 0 && ov % align != 0 {
+                            ov += align - (ov % align);
+                        }
+                        e.get_mut().header.size = ov + sec.header.size;
                         ov as usize
                     },
+                    section::SectionContent::Notes(mut n) => {
+                        let ov = e.get().size(&self.collected.elf.header);
+                        e.get_mut().content.as_notes_mut().unwrap().append(&mut n);
+                        ov
+                    },
                     _ => unreachable!(),
                 };
                 (i, ov)
@@ -813,10 +2344,14 @@ impl SimpleCollector {
             },
         };
 
+        // absolute 32bit addresses only work if the final address is known at link time
+        let absolute = self.collected.elf.header.etype == types::ElfType::REL ||
+            self.collected.elf.header.etype == types::ElfType::EXEC;
         let relav = self.collected.relocs.entry(nu_shndx).or_insert_with(||Vec::new());
         for mut rel in rela {
             match rel.rtype {
-                relocation::RelocationType::R_X86_64_32 | relocation::RelocationType::R_X86_64_32S => {
+                relocation::RelocationType::R_X86_64_32 | relocation::RelocationType::R_X86_64_32S
+                    if !absolute => {
                     error!("unsupported relocation. maybe missing -fPIC ? {:?} in {}",
                            rel, objname);
                     process::abort();
@@ -827,7 +2362,12 @@ impl SimpleCollector {
             relav.push(rel);
         }
 
-
+        self.collected.map.push(MapEntry{
+            object: objname,
+            shndx:  nu_shndx,
+            offset: nu_off as u64,
+            size,
+        });
 
         (nu_shndx, nu_off)
     }
@@ -840,35 +2380,249 @@ impl SimpleCollector {
 
 use std::path::Path;
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HashStyle {
+    Sysv,
+    Gnu,
+    Both,
+}
+
 pub struct LdOptions {
     pub dynamic_linker: String,
-    pub object_paths:   Vec<String>,
+    /// files to link, in command line order
+    pub inputs:         Vec<symbolic_linker::LinkInput>,
     pub output_path:    String,
     pub etype:          types::ElfType,
+    pub hash_style:     HashStyle,
+    pub compress_debug: Option<types::CompressionType>,
+    pub bind_now:       bool,
+    /// -z relro
+    pub relro:          bool,
+    pub execstack:      bool,
+    pub separate_code:  bool,
+    /// rewrite loads from the GOT to direct references where possible. --no-relax turns it off
+    pub relax:          bool,
+    /// warn about common symbols that get merged or overridden
+    pub warn_common:    bool,
+    /// --icf=safe|all folds identical code
+    pub icf:            symbolic_linker::Icf,
+    /// produce a shared object instead of an executable
+    pub shared:         bool,
+    /// -Bsymbolic binds references to exported symbols locally, so they can't be interposed
+    pub symbolic:       bool,
+    pub soname:         Option<String>,
+    /// address of the first byte of a static executable. 0x400000 unless a linker script says otherwise
+    pub image_base:     Option<u64>,
+    /// -Ttext, -Tdata and -Tbss, by output section name
+    pub section_start:  HashMap<Vec<u8>, u64>,
+    /// the symbol execution starts at
+    pub entry:          Vec<u8>,
+    pub script:         Option<Script>,
+    /// where to write the map file, if anywhere
+    pub map_path:       Option<String>,
+    pub build_id:       Option<BuildId>,
+    /// symbols to link in even though nothing refers to them
+    pub undefined:      Vec<Vec<u8>>,
+    pub wrap:           Vec<Vec<u8>>,
+    pub defsym:         Vec<linker_script::Assignment>,
+}
+
+/// how the NT_GNU_BUILD_ID note is computed
+#[derive(Debug, Clone, PartialEq)]
+pub enum BuildId {
+    Sha1,
+    Sha256,
+    Md5,
+    /// random, so not reproducible
+    Uuid,
+    Hex(Vec<u8>),
+}
+
+impl BuildId {
+    pub fn parse(style: &str) -> Option<Option<BuildId>> {
+        let id = match style {
+            "none"      => return Some(None),
+            "sha1"      => BuildId::Sha1,
+            "sha256"    => BuildId::Sha256,
+            "md5"       => BuildId::Md5,
+            "uuid"      => BuildId::Uuid,
+            _ if style.starts_with("0x") || style.starts_with("0X") => {
+                let hex = &style[2..];
+                if hex.is_empty() || !hex.len().is_multiple_of(2) {
+                    return None;
+                }
+                let mut bytes = Vec::new();
+                for i in (0..hex.len()).step_by(2) {
+                    bytes.push(u8::from_str_radix(hex.get(i..i + 2)?, 16).ok()?);
+                }
+                BuildId::Hex(bytes)
+            },
+            _ => return None,
+        };
+        Some(Some(id))
+    }
+
+    pub fn size(&self) -> usize {
+        match *self {
+            BuildId::Sha1           => 20,
+            BuildId::Sha256         => 32,
+            BuildId::Md5            => 16,
+            BuildId::Uuid           => 16,
+            BuildId::Hex(ref bytes) => bytes.len(),
+        }
+    }
+
+    pub fn compute(&self, content: &[u8]) -> Vec<u8> {
+        use sha2::Digest;
+        match *self {
+            BuildId::Sha1 => {
+                let mut h = sha1::Sha1::default();
+                h.input(content);
+                h.result().to_vec()
+            },
+            BuildId::Sha256 => {
+                let mut h = sha2::Sha256::default();
+                h.input(content);
+                h.result().to_vec()
+            },
+            BuildId::Md5 => md5::compute(content).0.to_vec(),
+            BuildId::Uuid => {
+                let mut id = vec![0; 16];
+                File::open("/dev/urandom").and_then(|mut f| f.read_exact(&mut id))
+                    .expect("cannot read /dev/urandom");
+                // version 4, variant 1
+                id[6] = (id[6] & 0x0f) | 0x40;
+                id[8] = (id[8] & 0x3f) | 0x80;
+                id
+            },
+            BuildId::Hex(ref bytes) => bytes.clone(),
+        }
+    }
+}
+
+impl LdOptions {
+    pub fn object_paths(&self) -> Vec<String> {
+        let mut paths = Vec::new();
+        for input in &self.inputs {
+            match *input {
+                symbolic_linker::LinkInput::File(ref file) => paths.push(file.path.clone()),
+                symbolic_linker::LinkInput::Group(ref files) => {
+                    paths.extend(files.iter().map(|file| file.path.clone()));
+                },
+            }
+        }
+        paths
+    }
+
+    /// symbols that are kept along with what defines them, besides the entry point and exports
+    pub fn roots(&self) -> Vec<Vec<u8>> {
+        let mut roots = self.undefined.clone();
+        for a in &self.defsym {
+            roots.extend(a.expr.symbols().into_iter().map(|name| name.to_vec()));
+        }
+        roots
+    }
 }
 
 impl Default for LdOptions {
     fn default() -> Self {
         Self {
             dynamic_linker: String::default(),
-            object_paths:   Vec::new(),
+            inputs:         Vec::new(),
             output_path:    String::from("a.out"),
             etype:          types::ElfType::DYN,
+            hash_style:     HashStyle::Sysv,
+            compress_debug: None,
+            bind_now:       false,
+            relro:          false,
+            execstack:      false,
+            separate_code:  false,
+            relax:          true,
+            warn_common:    false,
+            icf:            symbolic_linker::Icf::None,
+            shared:         false,
+            symbolic:       false,
+            soname:         None,
+            image_base:     None,
+            section_start:  HashMap::new(),
+            entry:          b"_start".to_vec(),
+            script:         None,
+            map_path:       None,
+            build_id:       None,
+            undefined:      Vec::new(),
+            wrap:           Vec::new(),
+            defsym:         Vec::new(),
         }
     }
 }
 
 
 
-fn search_lib(search_paths: &Vec<String>, needle: &String) -> String{
-    let so = String::from("lib") + needle + ".a";
+fn search_lib(search_paths: &Vec<String>, needle: &str, is_static: bool) -> String{
+    let mut names = vec![String::from("lib") + needle + ".so", String::from("lib") + needle + ".a"];
+    if is_static {
+        names.remove(0);
+    }
     for p in search_paths {
-        let pc = Path::new(p).join(&so);
-        if pc.exists() {
-            return pc.into_os_string().into_string().unwrap();
+        for so in &names {
+            let pc = Path::new(p).join(so);
+            if pc.exists() {
+                return pc.into_os_string().into_string().unwrap();
+            }
         }
     }
-    panic!("ld.elfkit: cannot find: {} in {:?}", so, search_paths);
+    panic!("ld.elfkit: cannot find: {} in {:?}", names[names.len() - 1], search_paths);
+}
+
+fn parse_address(val: &str) -> u64 {
+    let r = if val.starts_with("0x") || val.starts_with("0X") {
+        u64::from_str_radix(&val[2..], 16)
+    } else {
+        val.parse()
+    };
+    match r {
+        Ok(v) => v,
+        Err(_) => panic!("ld.elfkit: invalid address: {}", val),
+    }
+}
+
+fn load_script(options: &mut LdOptions, path: &str) {
+    let mut src = Vec::new();
+    if let Err(e) = File::open(path).and_then(|mut f| f.read_to_end(&mut src)) {
+        panic!("ld.elfkit: cannot read linker script {}: {}", path, e);
+    }
+    let script = match Script::parse(&src) {
+        Ok(v) => v,
+        Err(e) => panic!("ld.elfkit: in linker script {}: {:?}", path, e),
+    };
+    if let Some(ref entry) = script.entry {
+        options.entry = entry.clone();
+    }
+    options.script = Some(script);
+}
+
+/// --defsym sym=expr is the same as the assignment in a linker script
+fn parse_defsym(val: &str) -> linker_script::Assignment {
+    let script = match Script::parse(format!("{};", val).as_bytes()) {
+        Ok(v) => v,
+        Err(e) => panic!("ld.elfkit: invalid --defsym {}: {:?}", val, e),
+    };
+    match script.assignments().first() {
+        Some(a) if a.symbol != b"." && !a.expr.uses_dot() => (*a).clone(),
+        _ => panic!("ld.elfkit: invalid --defsym {}", val),
+    }
+}
+
+/// value of an option that is either glued to it with = or the next argument
+fn optarg(arg: &String, argname: &str, argc: &mut usize) -> Option<String> {
+    if arg == argname {
+        *argc += 1;
+        Some(env::args().nth(*argc).unwrap())
+    } else if arg.starts_with(argname) && arg[argname.len()..].starts_with('=') {
+        Some(String::from(&arg[argname.len() + 1..]))
+    } else {
+        None
+    }
 }
 
 fn ldarg(arg: &String, argname: &str, argc: &mut usize) -> Option<String> {
@@ -885,9 +2639,41 @@ fn ldarg(arg: &String, argname: &str, argc: &mut usize) -> Option<String> {
 }
 
 
+/// options that apply to the inputs following them on the command line
+#[derive(Clone, Default)]
+struct InputState {
+    whole_archive:  bool,
+    as_needed:      bool,
+    /// only look for archives when searching for -l
+    bstatic:        bool,
+}
+
+impl InputState {
+    fn input(&self, path: String) -> symbolic_linker::Input {
+        symbolic_linker::Input{
+            path,
+            whole_archive:  self.whole_archive,
+            as_needed:      self.as_needed,
+        }
+    }
+}
+
+fn push_input(options: &mut LdOptions, group: &mut Option<Vec<symbolic_linker::Input>>,
+              input: symbolic_linker::Input) {
+    match *group {
+        Some(ref mut files) => files.push(input),
+        None => options.inputs.push(symbolic_linker::LinkInput::File(input)),
+    }
+}
+
 pub fn parse_ld_options() -> LdOptions{
     let mut options         = LdOptions::default();
     let mut search_paths    = Vec::new();
+    let mut state           = InputState::default();
+    let mut pushed_states   = Vec::new();
+    let mut group           = None;
+    // -e wins over ENTRY in a linker script, no matter which comes first
+    let mut entry           = None;
 
     debug!("arguments to ld.elfkit: {:?}", env::args());
 
@@ -901,10 +2687,9 @@ pub fn parse_ld_options() -> LdOptions{
         if let Some(val) = ldarg(&arg, "-L", &mut argc) {
             search_paths.push(val);
         } else if let Some(val) = ldarg(&arg, "-l", &mut argc) {
-            let path = search_lib(&search_paths, &val);
-            if !options.object_paths.contains(&path) {
-                options.object_paths.push(path);
-            }
+            // the same library may come up more than once, to resolve what came in between
+            let path = search_lib(&search_paths, &val, state.bstatic);
+            push_input(&mut options, &mut group, state.input(path));
         } else if let Some(val) = ldarg(&arg, "-m", &mut argc) {
             if val != "elf_x86_64" {
                 panic!("machine not supported: {}", val);
@@ -912,13 +2697,152 @@ pub fn parse_ld_options() -> LdOptions{
         } else if let Some(val) = ldarg(&arg, "-o", &mut argc) {
             options.output_path = val;
         } else if arg == "-pie" {
+        } else if arg == "-static" {
+            options.etype = types::ElfType::EXEC;
+            state.bstatic = true;
+        } else if arg == "-Bstatic" || arg == "-dn" || arg == "-non_shared" {
+            state.bstatic = true;
+        } else if arg == "-Bdynamic" || arg == "-dy" || arg == "-call_shared" {
+            state.bstatic = false;
+        } else if arg == "--whole-archive" {
+            state.whole_archive = true;
+        } else if arg == "--no-whole-archive" {
+            state.whole_archive = false;
+        } else if arg == "--as-needed" {
+            state.as_needed = true;
+        } else if arg == "--no-as-needed" {
+            state.as_needed = false;
+        } else if arg == "--push-state" {
+            pushed_states.push(state.clone());
+        } else if arg == "--pop-state" {
+            state = match pushed_states.pop() {
+                Some(v) => v,
+                None => panic!("ld.elfkit: --pop-state without --push-state"),
+            };
+        } else if arg == "--start-group" || arg == "-(" {
+            if group.is_some() {
+                panic!("ld.elfkit: groups cannot be nested");
+            }
+            group = Some(Vec::new());
+        } else if arg == "--end-group" || arg == "-)" {
+            match group.take() {
+                Some(files) => options.inputs.push(symbolic_linker::LinkInput::Group(files)),
+                None => panic!("ld.elfkit: --end-group without --start-group"),
+            }
+        } else if arg == "--image-base" || arg.starts_with("--image-base=") {
+            let val = if arg == "--image-base" {
+                argc += 1;
+                env::args().nth(argc).unwrap()
+            } else {
+                String::from(&arg["--image-base=".len()..])
+            };
+            let image_base = parse_address(&val);
+            if !image_base.is_multiple_of(0x1000) {
+                panic!("ld.elfkit: image base 0x{:x} is not page aligned", image_base);
+            }
+            options.image_base = Some(image_base);
+        } else if let Some(&(opt, name)) = [("-Ttext", &b".text"[..]), ("-Tdata", &b".data"[..]),
+                                              ("-Tbss", &b".bss"[..])].iter()
+            .find(|&&(opt, _)| arg == opt || arg.starts_with(&format!("{}=", opt))) {
+            // these have to be handled before -T, which would take them for a script
+            let val = if arg == opt {
+                argc += 1;
+                env::args().nth(argc).unwrap()
+            } else {
+                String::from(&arg[opt.len() + 1..])
+            };
+            options.section_start.insert(name.to_vec(), parse_address(&val));
+        } else if arg == "--script" || arg.starts_with("--script=") {
+            let val = if arg == "--script" {
+                argc += 1;
+                env::args().nth(argc).unwrap()
+            } else {
+                String::from(&arg["--script=".len()..])
+            };
+            load_script(&mut options, &val);
+        } else if let Some(val) = ldarg(&arg, "-T", &mut argc) {
+            load_script(&mut options, &val);
+        } else if arg == "-Map" || arg == "--Map" {
+            argc += 1;
+            options.map_path = Some(env::args().nth(argc).unwrap());
+        } else if arg.starts_with("-Map=") || arg.starts_with("--Map=") {
+            options.map_path = Some(String::from(&arg[arg.find('=').unwrap() + 1..]));
+        } else if arg == "--build-id" || arg == "-build-id" {
+            options.build_id = Some(BuildId::Sha1);
+        } else if arg.starts_with("--build-id=") || arg.starts_with("-build-id=") {
+            let val = &arg[arg.find('=').unwrap() + 1..];
+            options.build_id = match BuildId::parse(val) {
+                Some(id) => id,
+                None => {
+                    error!("invalid --build-id style '{}'", val);
+                    process::abort();
+                },
+            };
+        } else if let Some(val) = optarg(&arg, "--entry", &mut argc) {
+            entry = Some(val.into_bytes());
+        } else if arg.starts_with("-e") && !arg.starts_with("-export") {
+            entry = ldarg(&arg, "-e", &mut argc).map(String::into_bytes);
+        } else if let Some(val) = optarg(&arg, "--undefined", &mut argc) {
+            options.undefined.push(val.into_bytes());
+        } else if let Some(val) = ldarg(&arg, "-u", &mut argc) {
+            options.undefined.push(val.into_bytes());
+        } else if let Some(val) = optarg(&arg, "--wrap", &mut argc).or_else(|| optarg(&arg, "-wrap", &mut argc)) {
+            options.wrap.push(val.into_bytes());
+        } else if let Some(val) = optarg(&arg, "--defsym", &mut argc).or_else(|| optarg(&arg, "-defsym", &mut argc)) {
+            options.defsym.push(parse_defsym(&val));
+        } else if arg == "--relax" {
+            options.relax = true;
+        } else if arg == "--no-relax" {
+            options.relax = false;
+        } else if arg == "--warn-common" {
+            options.warn_common = true;
+        } else if arg == "-shared" || arg == "-Bshareable" {
+            options.shared = true;
+        } else if arg == "-Bsymbolic" {
+            options.symbolic = true;
+        } else if arg == "-soname" || arg == "-h" {
+            argc += 1;
+            options.soname = Some(env::args().nth(argc).unwrap());
+        } else if let Some(val) = arg.strip_prefix("-soname=") {
+            options.soname = Some(String::from(val));
         } else if arg == "-r" {
             options.etype = types::ElfType::REL;
+        } else if let Some(val) = arg.strip_prefix("--icf=") {
+            options.icf = match val {
+                "none" => symbolic_linker::Icf::None,
+                "safe" => symbolic_linker::Icf::Safe,
+                "all"  => symbolic_linker::Icf::All,
+                any    => panic!("unknown icf mode: {}", any),
+            };
+        } else if let Some(val) = arg.strip_prefix("--hash-style=") {
+            options.hash_style = match val {
+                "sysv" => HashStyle::Sysv,
+                "gnu"  => HashStyle::Gnu,
+                "both" => HashStyle::Both,
+                any    => panic!("unknown hash style: {}", any),
+            };
+        } else if let Some(val) = arg.strip_prefix("--compress-debug-sections=") {
+            options.compress_debug = match val {
+                "none" => None,
+                "zlib" => Some(types::CompressionType::ZLIB),
+                "zstd" => Some(types::CompressionType::ZSTD),
+                any    => panic!("unknown debug section compression: {}", any),
+            };
         } else if arg == "-dynamic-linker" {
             argc += 1;
             options.dynamic_linker = env::args().nth(argc).unwrap()
         } else if let Some(val) = ldarg(&arg, "-z", &mut argc) {
-            warn!("argument ignored: -z {}" ,val);
+            match val.as_str() {
+                "now"             => options.bind_now = true,
+                "lazy"            => options.bind_now = false,
+                "relro"           => options.relro = true,
+                "norelro"         => options.relro = false,
+                "execstack"       => options.execstack = true,
+                "noexecstack"     => options.execstack = false,
+                "separate-code"   => options.separate_code = true,
+                "noseparate-code" => options.separate_code = false,
+                _ => warn!("argument ignored: -z {}" ,val),
+            }
 
         } else if arg == "-plugin" {
             argc += 1;
@@ -926,12 +2850,19 @@ pub fn parse_ld_options() -> LdOptions{
         } else if arg.starts_with("-") {
             warn!("argument ignored: {}",arg);
         } else {
-            options.object_paths.push(arg);
+            push_input(&mut options, &mut group, state.input(arg));
         }
         argc +=1;
     }
 
-    info!("linking {:?}", options.object_paths);
+    if group.is_some() {
+        panic!("ld.elfkit: --start-group without --end-group");
+    }
+    if let Some(entry) = entry {
+        options.entry = entry;
+    }
+
+    info!("linking {:?}", options.object_paths());
 
     options
 }
@@ -2,8 +2,8 @@ extern crate glob;
 extern crate clap;
 extern crate elfkit;
 
-use std::fs::{File};
-use elfkit::Elf;
+use std::fs::{self, File};
+use elfkit::ElfRef;
 use std::path::Path;
 use std::collections::HashSet;
 use std::io::{self};
@@ -37,25 +37,25 @@ fn join_paths(a: &str, b: &str) -> String {
 
 impl Ldd {
     fn recurse(&mut self, path: &str) {
-        let mut f = File::open(path).unwrap();
-        let mut elf = Elf::from_reader(&mut f).unwrap();
+        let data = fs::read(path).unwrap();
+        let elf = ElfRef::from_bytes(&data).unwrap();
 
         let mut deps = Vec::new();
-        for shndx in 0..elf.sections.len() {
-            if elf.sections[shndx].header.shtype == elfkit::types::SectionType::DYNAMIC {
-                elf.load(shndx, &mut f).unwrap();
-                let dynamic = elf.sections[shndx].content.as_dynamic().unwrap();
-
-                for dyn in dynamic.iter() {
-                    if dyn.dhtype == elfkit::types::DynamicType::RPATH{
-                        if let elfkit::dynamic::DynamicContent::String(ref name) = dyn.content {
+        for sec in elf.sections() {
+            let sec = sec.unwrap();
+            if sec.header.shtype == elfkit::types::SectionType::DYNAMIC {
+                // tags we don't know about can't be NEEDED, RPATH or RUNPATH
+                for dyn in elf.dynamic(&sec).unwrap().filter_map(|d| d.ok()) {
+                    if dyn.dhtype == elfkit::types::DynamicType::RPATH ||
+                        dyn.dhtype == elfkit::types::DynamicType::RUNPATH {
+                        if let Some(name) = dyn.string {
                             self.lpaths.push(join_paths(
-                                    &self.sysroot, &String::from_utf8_lossy(&name.0).into_owned()))
+                                    &self.sysroot, &String::from_utf8_lossy(name)))
                         }
                     }
                     if dyn.dhtype == elfkit::types::DynamicType::NEEDED {
-                        if let elfkit::dynamic::DynamicContent::String(ref name) = dyn.content {
-                            deps.push(String::from_utf8_lossy(&name.0).into_owned());
+                        if let Some(name) = dyn.string {
+                            deps.push(String::from_utf8_lossy(name).into_owned());
                         }
                     }
                 }
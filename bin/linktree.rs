@@ -29,7 +29,7 @@ fn main() {
     let mut linker = SymbolicLinker::default();
     linker.link(loader).unwrap();
     println!("lookup complete: {} objects are required", linker.objects.len());
-    linker.gc();
+    linker.gc(&[rootsym.to_vec()]);
     println!("after gc : {}", linker.objects.len());
 
 
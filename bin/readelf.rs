@@ -1,11 +1,15 @@
 extern crate colored;
 extern crate elfkit;
+extern crate num_traits;
 
 use std::env;
-use std::fs::File;
-use elfkit::{types, DynamicContent, Elf, SectionContent};
+use std::fs;
+use elfkit::{types, ElfRef, NoteContent};
+use elfkit::elfref::{SectionRef, SymbolRef};
 use elfkit::relocation::RelocationType;
+use elfkit::version;
 use elfkit::symbol::SymbolSectionIndex;
+use num_traits::FromPrimitive;
 use colored::*;
 
 fn hextab<S>(align: usize, s: S) -> String
@@ -17,11 +21,23 @@ where
     format!("\x1b[90m{}\x1b[0;m{}", pad, s)
 }
 
+fn print_bucket_histogram(lengths: Vec<usize>) {
+    let mut histogram = vec![0; lengths.iter().cloned().max().unwrap_or(0) + 1];
+    for l in &lengths {
+        histogram[*l] += 1;
+    }
+    println!("  Length  Number");
+    for (l, n) in histogram.iter().enumerate() {
+        println!("  {:>6}  {:>6}", l, n);
+    }
+}
+
 fn main() {
     let filename = env::args().nth(1).unwrap();
-    let mut file = File::open(filename).unwrap();
-    let mut elf = Elf::from_reader(&mut file).unwrap();
-    elf.load_all(&mut file).unwrap();
+    let data = fs::read(filename).unwrap();
+    let elf = ElfRef::from_bytes(&data).unwrap();
+    let sections : Vec<SectionRef> = elf.sections().map(|s| s.unwrap()).collect();
+    let segments : Vec<_> = elf.segments().map(|s| s.unwrap()).collect();
 
     println!("{}", "ELF Header:".bold());
     println!(
@@ -106,7 +122,7 @@ fn main() {
          Flg Lnk Inf Al"
     );
 
-    for (i, section) in elf.sections.iter().enumerate() {
+    for (i, section) in sections.iter().enumerate() {
         println!(
             "  [{:>2}] {:<16.16} {} {} {} {} {} {:<3} {:<3.3} {:<3} {:<2.2}",
             i,
@@ -150,8 +166,8 @@ fn main() {
         "g".bold()
     );
 
-    if elf.segments.len() > 0 {
-        println!("");
+    if !segments.is_empty() {
+        println!();
         println!(
             "{} at offset 0x{:x}:",
             "Program Headers (Segments)".bold(),
@@ -160,7 +176,7 @@ fn main() {
         println!("  Type           Offset             VirtAddr           PhysAddr");
         println!("                 FileSiz            MemSiz             Flags  Align");
 
-        for ph in &elf.segments {
+        for ph in &segments {
             println!(
                 "  {:<14.14} 0x{} 0x{} 0x{}",
                 format!("{:?}", ph.phtype),
@@ -196,11 +212,11 @@ fn main() {
         fls.push((
             String::from("segment headers"),
             elf.header.phoff,
-            (elf.header.phentsize * elf.header.phnum) as u64,
+            elf.header.phentsize as u64 * elf.header.phnum as u64,
         ));
     }
 
-    for section in elf.sections.iter() {
+    for section in sections.iter() {
         if section.header.size < 1 {
             continue;
         }
@@ -217,18 +233,18 @@ fn main() {
         "{}",
         "                     addr       size     segment".bold()
     );
-    if elf.segments.len() > 0 {
+    if !segments.is_empty() {
         for n in 0..12 {
             let n = n;
             print!("                                         ");
-            for segment in elf.segments.iter() {
+            for segment in segments.iter() {
                 let name = format!("{:?}", segment.phtype);
                 print!(" {} |", if name.len() > n { &name[n..n + 1] } else { " " });
             }
             println!("");
         }
         print!("                                         ");
-        for _ in elf.segments.iter() {
+        for _ in segments.iter() {
             print!("---|")
         }
         println!("");
@@ -247,7 +263,7 @@ fn main() {
     }
 
     if let Some(&(_, off, size)) = fls.last() {
-        let filelen = file.metadata().unwrap().len();
+        let filelen = data.len() as u64;
         if off + size < filelen {
             fls.push((String::default(), off + size, filelen - (off + size)));
         }
@@ -262,7 +278,7 @@ fn main() {
             size
         );
 
-        for segment in elf.segments.iter() {
+        for segment in segments.iter() {
             if off >= segment.vaddr && (off + size) <= (segment.vaddr + segment.memsz) {
                 if segment.flags.contains(types::SegmentFlags::WRITABLE) {
                     print!("{:^3}|", format!("{}", segment.flags).red())
@@ -280,40 +296,66 @@ fn main() {
 
 
 
-    for section in &elf.sections {
-        match section.content {
-            SectionContent::Relocations(ref relocs) => {
+    let verdefs : Vec<_> = sections.iter()
+        .filter(|s| s.header.shtype == types::SectionType::GNU_VERDEF)
+        .flat_map(|s| elf.verdefs(s).unwrap()).collect();
+    let verneeds : Vec<_> = sections.iter()
+        .filter(|s| s.header.shtype == types::SectionType::GNU_VERNEED)
+        .flat_map(|s| elf.verneeds(s).unwrap()).collect();
+    let version_name = |index: u16| {
+        version::version_name(index, &verdefs, &verneeds)
+            .map(|n| String::from_utf8_lossy(n).into_owned())
+            .unwrap_or_else(|| match index & !version::VERSYM_HIDDEN {
+                version::VER_NDX_LOCAL => String::from("*local*"),
+                version::VER_NDX_GLOBAL => String::from("*global*"),
+                i => format!("{}", i),
+            })
+    };
+
+    // the symbols of a symbol table section, or nothing if it isn't one
+    let symbols_of = |link: u32| -> Vec<SymbolRef> {
+        match sections.get(link as usize) {
+            Some(sec) if sec.header.shtype == types::SectionType::SYMTAB ||
+                sec.header.shtype == types::SectionType::DYNSYM =>
+                elf.symbols(sec).unwrap().map(|s| s.unwrap()).collect(),
+            _ => Vec::new(),
+        }
+    };
+
+    for section in sections.iter() {
+        match section.header.shtype {
+            types::SectionType::REL | types::SectionType::RELA => {
                 println!("");
                 println!(
                     "{} relocation section at offset 0x{:x}:",
-                    String::from_utf8_lossy(&section.name).bold(),
+                    String::from_utf8_lossy(section.name).bold(),
                     section.header.offset
                 );
                 println!("  Offset           Type            Symbol               Addend");
 
-                for reloc in relocs {
+                let symbols = symbols_of(section.header.link);
+                for reloc in elf.relocations(section).unwrap() {
+                    let reloc = reloc.unwrap();
                     print!(
                         "  {} {:<15.15} ",
                         hextab(16, reloc.addr),
-                        &format!("{:?}", reloc.rtype)[2..]
+                        match reloc.rtype.typename(&elf.header) {
+                            Some(s) => s.to_string(),
+                            None => format!("{}", reloc.rtype.to_u32()),
+                        }
                     );
 
-                    elf.sections
-                        .get(section.header.link as usize)
-                        .and_then(|sec| sec.content.as_symbols())
-                        .and_then(|symbols| symbols.get(reloc.sym as usize))
-                        .and_then(|symbol| if symbol.name.len() > 0 {
-                            print!("{: <20.20} ", String::from_utf8_lossy(&symbol.name));
-                            Some(())
-                        } else {
-                            None
-                        })
-                        .unwrap_or_else(|| {
+                    match symbols.get(reloc.sym as usize) {
+                        Some(symbol) if !symbol.name.is_empty() => {
+                            print!("{: <20.20} ", String::from_utf8_lossy(symbol.name));
+                        }
+                        _ => {
                             print!("{: <20.20} ", reloc.sym);
-                        });
+                        }
+                    }
 
                     match reloc.rtype {
-                        RelocationType::R_X86_64_RELATIVE => {
+                        RelocationType::R_X86_64_RELATIVE if elf.header.machine == types::Machine::X86_64 => {
                             println!("0x{:<16.16x}", reloc.addend);
                         }
                         _ => {
@@ -322,18 +364,24 @@ fn main() {
                     }
                 }
             }
-            SectionContent::Symbols(ref symbols) => {
+            types::SectionType::SYMTAB | types::SectionType::DYNSYM => {
                 println!("");
                 println!(
                     "{} symbols section at offset 0x{:x}:",
-                    String::from_utf8_lossy(&section.name).bold(),
+                    String::from_utf8_lossy(section.name).bold(),
                     section.header.offset
                 );
                 println!("  Num: Value             Size Type    Bind   Vis      Ndx Name");
 
-                for (i, symbol) in symbols.iter().enumerate() {
+                let versyms = sections.iter()
+                    .find(|s| s.header.link as usize == section.index &&
+                          s.header.shtype == types::SectionType::GNU_VERSYM)
+                    .map(|s| elf.versyms(s).unwrap());
+
+                for (i, symbol) in elf.symbols(section).unwrap().enumerate() {
+                    let symbol = symbol.unwrap();
                     println!(
-                        "  {:>3}: {} {:>5.5} {:<7.7} {:<6.6} {:<8.8} {:<3.3} {} ",
+                        "  {:>3}: {} {:>5.5} {:<7.7} {:<6.6} {:<8.8} {:<3} {}{} ",
                         i,
                         hextab(16, symbol.value),
                         symbol.size,
@@ -346,44 +394,205 @@ fn main() {
                             SymbolSectionIndex::Common => String::from("COM"),
                             SymbolSectionIndex::Section(i) => format!("{}", i),
                         },
-                        String::from_utf8_lossy(&symbol.name)
+                        String::from_utf8_lossy(symbol.name),
+                        match versyms.as_ref().and_then(|v| v.get(i)) {
+                            Some(v) if v.index > version::VER_NDX_GLOBAL => format!("{}{}",
+                                if v.hidden || symbol.shndx == SymbolSectionIndex::Undefined { "@" } else { "@@" },
+                                version_name(v.index)),
+                            _ => String::default(),
+                        }
                     );
                 }
             }
-            SectionContent::Dynamic(ref dynamics) => {
+            types::SectionType::DYNAMIC => {
                 println!("");
                 println!(
                     "{} dynamic linker section at offset 0x{:x}:",
-                    String::from_utf8_lossy(&section.name).bold(),
+                    String::from_utf8_lossy(section.name).bold(),
                     section.header.offset
                 );
                 println!("  Tag          Value");
 
-                for dyn in dynamics {
+                for dyn in elf.dynamic(section).unwrap() {
+                    let dyn = dyn.unwrap();
                     println!(
                         "  {:<12} {}",
                         format!("{:?}", dyn.dhtype),
-                        match dyn.content {
-                            DynamicContent::None => String::default(),
-                            DynamicContent::String(ref s) => String::from_utf8_lossy(&s.0).into_owned(),
-                            DynamicContent::Address(u) => hextab(16, u),
-                            DynamicContent::Flags1(v) => format!("{:?}", v),
+                        match dyn.dhtype {
+                            _ if dyn.string.is_some() => String::from_utf8_lossy(dyn.string.unwrap()).into_owned(),
+                            types::DynamicType::NULL => String::default(),
+                            types::DynamicType::FLAGS =>
+                                format!("{:?}", types::DynamicFlags::from_bits_truncate(dyn.val)),
+                            types::DynamicType::FLAGS_1 =>
+                                format!("{:?}", types::DynamicFlags1::from_bits_truncate(dyn.val)),
+                            types::DynamicType::PLTREL => match types::DynamicType::from_u64(dyn.val) {
+                                Some(v) => format!("{:?}", v),
+                                None => format!("{}", dyn.val),
+                            },
+                            ref t if t.is_size() => format!("{} (bytes)", dyn.val),
+                            ref t if t.is_count() => format!("{}", dyn.val),
+                            _ => hextab(16, dyn.val),
                         }
                     );
                 }
             }
-            SectionContent::Raw(ref s) => match section.name.as_slice() {
-                b".interp" => {
-                    println!("");
+            types::SectionType::NOTE => {
+                println!();
+                println!(
+                    "{} notes section at offset 0x{:x}:",
+                    String::from_utf8_lossy(section.name).bold(),
+                    section.header.offset
+                );
+                println!("  Owner                Type       Description");
+
+                for note in elf.notes(section).unwrap() {
                     println!(
-                        "{} program interpreter section at offset 0x{:x}:",
-                        String::from_utf8_lossy(&section.name).bold(),
-                        section.header.offset
+                        "  {:<20.20} {} {}",
+                        String::from_utf8_lossy(&note.name),
+                        hextab(10, note.ntype()),
+                        match note.content {
+                            NoteContent::GnuAbiTag{os, major, minor, subminor} => format!(
+                                "ABI tag: OS {} {}.{}.{}", os, major, minor, subminor),
+                            NoteContent::GnuBuildId(ref id) => format!("Build ID: {}",
+                                id.iter().map(|b| format!("{:02x}", b)).collect::<String>()),
+                            NoteContent::GnuGoldVersion(ref v) => format!("Version: {}",
+                                String::from_utf8_lossy(v)),
+                            NoteContent::GnuProperties(ref props) => format!("Properties: {:?}", props),
+                            NoteContent::Raw{ref desc, ..} => format!("{} bytes", desc.len()),
+                        }
                     );
-                    println!("  {}", String::from_utf8_lossy(s));
                 }
-                _ => {}
-            },
+            }
+            types::SectionType::HASH => {
+                let hash = elf.hash(section).unwrap();
+                println!();
+                println!(
+                    "{} sysv hash section at offset 0x{:x}: {} buckets, {} chains",
+                    String::from_utf8_lossy(section.name).bold(),
+                    section.header.offset,
+                    hash.buckets.len(),
+                    hash.chains.len(),
+                );
+                print_bucket_histogram(hash.buckets.iter().map(|b| {
+                    let mut l = 0;
+                    let mut i = *b as usize;
+                    while i != 0 && i < hash.chains.len() && l < hash.chains.len() {
+                        l += 1;
+                        i = hash.chains[i] as usize;
+                    }
+                    l
+                }).collect());
+            }
+            types::SectionType::GNU_HASH => {
+                let hash = elf.gnu_hash(section).unwrap();
+                println!();
+                println!(
+                    "{} gnu hash section at offset 0x{:x}: {} buckets, symoffset {}, {} bloom words, shift {}",
+                    String::from_utf8_lossy(section.name).bold(),
+                    section.header.offset,
+                    hash.buckets.len(),
+                    hash.symoffset,
+                    hash.bloom.len(),
+                    hash.bloom_shift,
+                );
+                print_bucket_histogram(hash.buckets.iter().map(|b| {
+                    if *b < hash.symoffset {
+                        return 0;
+                    }
+                    hash.chains[(*b - hash.symoffset) as usize ..].iter()
+                        .position(|c| c & 1 != 0).map(|p| p + 1).unwrap_or(0)
+                }).collect());
+            }
+            types::SectionType::GNU_VERSYM => {
+                println!();
+                println!(
+                    "{} symbol versions section at offset 0x{:x}:",
+                    String::from_utf8_lossy(section.name).bold(),
+                    section.header.offset
+                );
+                for (i, chunk) in elf.versyms(section).unwrap().chunks(4).enumerate() {
+                    print!("  {:03x}:", i * 4);
+                    for v in chunk {
+                        print!(" {:>4x}{} {:<16.16}", v.index, if v.hidden { "h" } else { " " },
+                               format!("({})", version_name(v.index)));
+                    }
+                    println!();
+                }
+            }
+            types::SectionType::GNU_VERDEF => {
+                println!();
+                println!(
+                    "{} version definition section at offset 0x{:x}:",
+                    String::from_utf8_lossy(section.name).bold(),
+                    section.header.offset
+                );
+                for vd in elf.verdefs(section).unwrap() {
+                    println!(
+                        "  Rev: {}  Flags: {:?}  Index: {}  Name: {}",
+                        vd.version,
+                        vd.flags,
+                        vd.index,
+                        vd.names.first().map(|n| String::from_utf8_lossy(&n.name).into_owned())
+                            .unwrap_or_default(),
+                    );
+                    for (i, parent) in vd.names.iter().skip(1).enumerate() {
+                        println!("    Parent {}: {}", i + 1, String::from_utf8_lossy(&parent.name));
+                    }
+                }
+            }
+            types::SectionType::GNU_VERNEED => {
+                println!();
+                println!(
+                    "{} version needs section at offset 0x{:x}:",
+                    String::from_utf8_lossy(section.name).bold(),
+                    section.header.offset
+                );
+                for vn in elf.verneeds(section).unwrap() {
+                    println!("  Version: {}  File: {}", vn.version, String::from_utf8_lossy(&vn.file));
+                    for aux in &vn.aux {
+                        println!(
+                            "    Name: {}  Flags: {:?}  Version: {}",
+                            String::from_utf8_lossy(&aux.name),
+                            aux.flags,
+                            aux.other,
+                        );
+                    }
+                }
+            }
+            types::SectionType::GROUP => {
+                let group = elf.group(section).unwrap();
+                let signature = symbols_of(section.header.link)
+                    .get(section.header.info as usize)
+                    .map(|symbol| String::from_utf8_lossy(symbol.name).into_owned())
+                    .unwrap_or_default();
+                println!();
+                println!(
+                    "{} {}group section at offset 0x{:x} [{}] contains {} sections:",
+                    String::from_utf8_lossy(section.name).bold(),
+                    if group.is_comdat() { "COMDAT " } else { "" },
+                    section.header.offset,
+                    signature,
+                    group.members.len(),
+                );
+                for m in &group.members {
+                    println!(
+                        "  [{:>5}] {}",
+                        m,
+                        sections.get(*m as usize)
+                            .map(|sec| String::from_utf8_lossy(sec.name).into_owned())
+                            .unwrap_or_default(),
+                    );
+                }
+            }
+            _ if section.name == b".interp" => {
+                println!();
+                println!(
+                    "{} program interpreter section at offset 0x{:x}:",
+                    String::from_utf8_lossy(section.name).bold(),
+                    section.header.offset
+                );
+                println!("  {}", String::from_utf8_lossy(section.data));
+            }
             _ => {}
         }
     }
@@ -0,0 +1,107 @@
+use std::io::{self, Read, Write};
+use {Error, Header};
+use types;
+use utils::ElfEndianReadExt;
+use num_traits::FromPrimitive;
+use flate2;
+use ruzstd;
+
+/// the Elf_Chdr in front of the data of SHF_COMPRESSED sections
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chdr {
+    pub chtype:     types::CompressionType,
+    /// size of the uncompressed data
+    pub size:       u64,
+    /// alignment of the uncompressed data
+    pub addralign:  u64,
+}
+
+impl Chdr {
+    pub fn entsize(eh: &Header) -> usize {
+        match eh.ident_class {
+            types::Class::Class64 => 24,
+            types::Class::Class32 => 12,
+        }
+    }
+
+    pub fn from_reader<R>(mut io: R, eh: &Header) -> Result<Chdr, Error> where R: Read {
+        let reb = io.elf_read_u32(eh)?;
+        let chtype = match types::CompressionType::from_u32(reb) {
+            Some(v) => v,
+            None => return Err(Error::InvalidCompressionType(reb)),
+        };
+        if eh.ident_class == types::Class::Class64 {
+            // ch_reserved
+            io.elf_read_u32(eh)?;
+        }
+        Ok(Chdr {
+            chtype,
+            size:       io.elf_read_uclass(eh)?,
+            addralign:  io.elf_read_uclass(eh)?,
+        })
+    }
+
+    pub fn to_writer<W>(&self, mut io: W, eh: &Header) -> Result<usize, Error> where W: Write {
+        elf_write_u32!(eh, io, self.chtype as u32)?;
+        if eh.ident_class == types::Class::Class64 {
+            elf_write_u32!(eh, io, 0)?;
+        }
+        elf_write_uclass!(eh, io, self.size)?;
+        elf_write_uclass!(eh, io, self.addralign)?;
+        Ok(Chdr::entsize(eh))
+    }
+}
+
+/// split the content of a SHF_COMPRESSED section into its header and the uncompressed data
+pub fn decompress(data: &[u8], eh: &Header) -> Result<(Chdr, Vec<u8>), Error> {
+    let mut io = data;
+    let chdr = Chdr::from_reader(&mut io, eh)?;
+
+    let mut r = Vec::with_capacity(chdr.size as usize);
+    match chdr.chtype {
+        types::CompressionType::ZLIB => {
+            flate2::read::ZlibDecoder::new(io).read_to_end(&mut r)?;
+        },
+        types::CompressionType::ZSTD => {
+            // the data may be split into several frames
+            while !io.is_empty() {
+                let mut frame = ruzstd::decoding::StreamingDecoder::new(&mut io)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}", e)))?;
+                frame.read_to_end(&mut r)?;
+            }
+        },
+    }
+
+    if r.len() as u64 != chdr.size {
+        return Err(Error::CompressedSizeMismatch{
+            expected:   chdr.size,
+            got:        r.len() as u64,
+        });
+    }
+    Ok((chdr, r))
+}
+
+/// compress data into the content of a SHF_COMPRESSED section, including its header.
+/// addralign is the alignment of the uncompressed data
+pub fn compress(data: &[u8], chtype: types::CompressionType, addralign: u64, eh: &Header)
+    -> Result<Vec<u8>, Error> {
+    let chdr = Chdr {
+        chtype,
+        size:       data.len() as u64,
+        addralign,
+    };
+    let mut r = Vec::new();
+    chdr.to_writer(&mut r, eh)?;
+
+    match chtype {
+        types::CompressionType::ZLIB => {
+            let mut enc = flate2::write::ZlibEncoder::new(r, flate2::Compression::default());
+            enc.write_all(data)?;
+            r = enc.finish()?;
+        },
+        types::CompressionType::ZSTD => {
+            ruzstd::encoding::compress(data, &mut r, ruzstd::encoding::CompressionLevel::Fastest);
+        },
+    }
+    Ok(r)
+}
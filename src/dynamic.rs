@@ -3,11 +3,19 @@ use {Error, Header, SectionContent};
 use types;
 use num_traits::{FromPrimitive, ToPrimitive};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum DynamicContent {
     None,
+    /// the string and its offset into the linked strtab, which is set by sync
     String((Vec<u8>,Option<u64>)),
     Address(u64),
+    /// a size in bytes, for example of a table or of one of its entries
+    Size(u64),
+    /// a number of entries
+    Count(u64),
+    /// the relocation type used by the plt, DT_RELA or DT_REL
+    PltRel(types::DynamicType),
+    Flags(types::DynamicFlags),
     Flags1(types::DynamicFlags1),
 }
 
@@ -17,7 +25,7 @@ impl Default for DynamicContent{
     }
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct Dynamic {
     pub dhtype: types::DynamicType,
     pub content: DynamicContent,
@@ -44,7 +52,7 @@ impl Dynamic {
             Some(&SectionContent::Strtab(ref s)) => Some(s),
             any => return Err(Error::LinkedSectionIsNotStrtab{
                 during: "reading dynamic",
-                link: any.map(|v|v.clone()),
+                link: any.cloned(),
             }),
         };
 
@@ -62,18 +70,9 @@ impl Dynamic {
                     });
                     break;
                 },
-                Some(types::DynamicType::RPATH) => {
+                Some(x) if x.is_string() => {
                     r.push(Dynamic {
-                        dhtype: types::DynamicType::RPATH,
-                        content: DynamicContent::String(match strtab {
-                            None => (Vec::default(),None),
-                            Some(s) => (s.get(val as usize), Some(val)),
-                        }),
-                    });
-                },
-                Some(types::DynamicType::NEEDED) => {
-                    r.push(Dynamic {
-                        dhtype: types::DynamicType::NEEDED,
+                        dhtype: x,
                         content: DynamicContent::String(match strtab {
                             None => (Vec::default(),None),
                             Some(s) => (s.get(val as usize), Some(val)),
@@ -91,6 +90,40 @@ impl Dynamic {
                         ),
                     });
                 },
+                Some(types::DynamicType::FLAGS) => {
+                    r.push(Dynamic {
+                        dhtype: types::DynamicType::FLAGS,
+                        content: DynamicContent::Flags(
+                            match types::DynamicFlags::from_bits(val) {
+                                Some(v) => v,
+                                None => return Err(Error::InvalidDynamicFlags(val)),
+                            },
+                        ),
+                    });
+                },
+                Some(types::DynamicType::PLTREL) => {
+                    r.push(Dynamic {
+                        dhtype: types::DynamicType::PLTREL,
+                        content: DynamicContent::PltRel(
+                            match types::DynamicType::from_u64(val) {
+                                Some(v) => v,
+                                None => return Err(Error::InvalidDynamicType(val)),
+                            },
+                        ),
+                    });
+                },
+                Some(x) if x.is_size() => {
+                    r.push(Dynamic {
+                        dhtype: x,
+                        content: DynamicContent::Size(val),
+                    });
+                },
+                Some(x) if x.is_count() => {
+                    r.push(Dynamic {
+                        dhtype: x,
+                        content: DynamicContent::Count(val),
+                    });
+                },
                 Some(x) => {
                     r.push(Dynamic {
                         dhtype: x,
@@ -120,9 +153,15 @@ impl Dynamic {
                 Some(val) => elf_write_uclass!(eh, io, val)?,
                 None      => return Err(Error::WritingNotSynced),
             },
-            DynamicContent::Address(ref v) => {
+            DynamicContent::Address(ref v) | DynamicContent::Size(ref v) | DynamicContent::Count(ref v) => {
                 elf_write_uclass!(eh, io, *v)?;
             }
+            DynamicContent::PltRel(ref v) => {
+                elf_write_uclass!(eh, io, v.to_u64().unwrap())?;
+            }
+            DynamicContent::Flags(ref v) => {
+                elf_write_uclass!(eh, io, v.bits())?;
+            }
             DynamicContent::Flags1(ref v) => {
                 elf_write_uclass!(eh, io, v.bits())?;
             }
@@ -138,11 +177,15 @@ impl Dynamic {
                 }
                 any => return Err(Error::LinkedSectionIsNotStrtab{
                     during: "syncing dynamic",
-                    link: any.map(|v|v.clone()),
+                    link: any.cloned(),
                 }),
             },
             DynamicContent::None => {}
             DynamicContent::Address(_) => {}
+            DynamicContent::Size(_) => {}
+            DynamicContent::Count(_) => {}
+            DynamicContent::PltRel(_) => {}
+            DynamicContent::Flags(_) => {}
             DynamicContent::Flags1(_) => {}
         }
         Ok(())
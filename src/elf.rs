@@ -5,6 +5,7 @@ use error::Error;
 use section::*;
 use segment::*;
 use symbol;
+use note;
 use section;
 use segment;
 
@@ -19,6 +20,21 @@ pub struct Elf {
     pub header: Header,
     pub segments: Vec<SegmentHeader>,
     pub sections: Vec<Section>,
+    /// decompress SHF_COMPRESSED sections when loading them
+    pub decompress: bool,
+    /// virtual address layout places the image at. zero for position independent images.
+    /// must be page aligned
+    pub image_base: u64,
+    /// fixed virtual addresses of sections by name.
+    /// layout starts a new segment at each of them and continues from there
+    pub section_start: HashMap<Vec<u8>, u64>,
+    /// names of the writable sections that are only written while relocating.
+    /// reorder puts them first and layout covers them with PT_GNU_RELRO
+    pub relro: Vec<Vec<u8>>,
+    /// make the stack executable with PT_GNU_STACK
+    pub execstack: bool,
+    /// keep code on pages of its own, so nothing else is mapped executable
+    pub separate_code: bool,
 }
 
 impl Elf {
@@ -27,6 +43,12 @@ impl Elf {
             header:     header,
             segments:   Vec::new(),
             sections:   Vec::new(),
+            decompress: false,
+            image_base: 0,
+            section_start: HashMap::new(),
+            relro:      Vec::new(),
+            execstack:  false,
+            separate_code: false,
         }
     }
 
@@ -34,7 +56,14 @@ impl Elf {
     where
         R: Read + Seek,
     {
-        let header = Header::from_reader(io)?;
+        let mut header = Header::from_reader(io)?;
+
+        // extended numbering. the real values are in section 0
+        if header.is_extended() {
+            io.seek(SeekFrom::Start(header.shoff))?;
+            let sh0 = SectionHeader::from_reader(io, &header)?;
+            header.apply_section0(&sh0);
+        }
 
         // parse segments
         let mut segments = Vec::with_capacity(header.phnum as usize);
@@ -91,6 +120,12 @@ impl Elf {
             header:     header,
             segments:   segments,
             sections:   sections,
+            decompress: false,
+            image_base: 0,
+            section_start: HashMap::new(),
+            relro:      Vec::new(),
+            execstack:  false,
+            separate_code: false,
         })
     }
 
@@ -99,6 +134,7 @@ impl Elf {
         R: Read + Seek,
     {
         let mut sec = std::mem::replace(&mut self.sections[i], Section::default());
+        let was_unloaded = matches!(sec.content, SectionContent::Unloaded);
         {
             let link = sec.header.link.clone();
             let linked = {
@@ -109,13 +145,72 @@ impl Elf {
                     Some(&self.sections[link as usize])
                 }
             };
-            sec.from_reader(io, linked, &self.header)?;
+            if self.decompress {
+                sec.from_reader_decompressed(&mut *io, linked, &self.header)?;
+            } else {
+                sec.from_reader(&mut *io, linked, &self.header)?;
+            }
         }
         self.sections[i] = sec;
 
+        if was_unloaded && self.sections[i].content.as_symbols().is_some() {
+            self.load_symtab_shndx(i, io)?;
+        }
+
         Ok(())
     }
 
+    /// replace SHN_XINDEX placeholders in the symbols of section i
+    /// with the real index from the SYMTAB_SHNDX section linked to it
+    fn load_symtab_shndx<R> (&mut self, i: usize, io: &mut R) -> Result<(), Error>
+        where
+        R: Read + Seek,
+    {
+        let x = match self.sections.iter().position(|s| {
+            s.header.shtype == types::SectionType::SYMTAB_SHNDX && s.header.link == i as u32
+        }) {
+            Some(x) => x,
+            None => return Ok(()),
+        };
+        self.load(x, io)?;
+
+        let mut table = Vec::new();
+        if let Some(raw) = self.sections[x].content.as_raw() {
+            let mut raw = raw.as_slice();
+            while let Ok(v) = elf_read_u32!(self.header, raw) {
+                table.push(v);
+            }
+        }
+
+        let symbols = self.sections[i].content.as_symbols_mut().unwrap();
+        for (si, sym) in symbols.iter_mut().enumerate() {
+            if sym.shndx == symbol::SymbolSectionIndex::Section(types::SHN_XINDEX as u32) {
+                match table.get(si) {
+                    Some(v) => sym.shndx = symbol::SymbolSectionIndex::Section(*v),
+                    None => return Err(Error::InvalidSymbolShndx(
+                            String::from_utf8_lossy(&sym.name).into_owned(), types::SHN_XINDEX)),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// read the notes in PT_NOTE segment i.
+    /// useful for files without section headers, otherwise the same notes are in the NOTE sections
+    pub fn segment_notes<R> (&self, i: usize, io: &mut R) -> Result<Vec<note::Note>, Error>
+        where
+        R: Read + Seek,
+    {
+        let seg = &self.segments[i];
+        if seg.phtype != types::SegmentType::NOTE {
+            return Err(Error::UnexpectedSegmentType(seg.phtype.clone()));
+        }
+        io.seek(SeekFrom::Start(seg.offset))?;
+        let mut bb = vec![0; seg.filesz as usize];
+        io.read_exact(&mut bb)?;
+        note::Note::from_reader(bb.as_slice(), &self.header, note::align(seg.align))
+    }
+
     pub fn load_all<R> (&mut self, io: &mut R) -> Result<(), Error>
         where
         R: Read + Seek,
@@ -129,9 +224,11 @@ impl Elf {
     /// write out everything to linked sections, such as string tables
     /// after calling this function, size() is reliable for all sections
     pub fn sync_all(&mut self) -> Result<(), Error> {
+        self.sync_symtab_shndx()?;
+
         match self.sections.iter().position(|s| s.name == b".shstrtab") {
             Some(i) => {
-                self.header.shstrndx = i as u16;
+                self.header.shstrndx = i as u32;
                 let mut shstrtab = std::mem::replace(
                     &mut self.sections[self.header.shstrndx as usize].content,
                     SectionContent::default(),
@@ -175,6 +272,41 @@ impl Elf {
         Ok(())
     }
 
+    /// regenerate SYMTAB_SHNDX sections from the symbols they are linked to.
+    /// a symbol table with section indices beyond SHN_LORESERVE must have one.
+    fn sync_symtab_shndx(&mut self) -> Result<(), Error> {
+        for x in 0..self.sections.len() {
+            if self.sections[x].header.shtype != types::SectionType::SYMTAB_SHNDX {
+                continue;
+            }
+            let link = self.sections[x].header.link as usize;
+            let mut table = Vec::new();
+            if let Some(symbols) = self.sections.get(link).and_then(|s| s.content.as_symbols()) {
+                let io = &mut table;
+                for sym in symbols {
+                    elf_write_u32!(self.header, io, sym.xindex())?;
+                }
+            } else {
+                continue;
+            }
+            self.sections[x].content = SectionContent::Raw(table);
+            self.sections[x].header.entsize = 4;
+        }
+
+        for i in 0..self.sections.len() {
+            let extended = match self.sections[i].content.as_symbols() {
+                Some(symbols) => symbols.iter().any(|s| s.xindex() > 0),
+                None => false,
+            };
+            if extended && !self.sections.iter().any(|s| {
+                s.header.shtype == types::SectionType::SYMTAB_SHNDX && s.header.link == i as u32
+            }) {
+                return Err(Error::SymbolSectionIndexExtendedCannotBeWritten);
+            }
+        }
+        Ok(())
+    }
+
     pub fn to_writer<R>(&mut self, mut io: R) -> Result<(), Error>
     where
         R: Write + Seek,
@@ -192,10 +324,17 @@ impl Elf {
                 seg.to_writer(&self.header, &mut io)?;
             }
             let at = io.seek(SeekFrom::Current(0))? as usize;
-            self.header.phnum = self.segments.len() as u16;
+            self.header.phnum = self.segments.len() as u32;
             self.header.phentsize = ((at - off) / self.segments.len()) as u16;
         }
 
+        if self.header.shstrndx > 0 {
+            self.header.shnum = self.sections.len() as u32;
+        }
+        if let Some(sec0) = self.sections.first_mut() {
+            self.header.extend_section0(&mut sec0.header);
+        }
+
         let headers: Vec<SectionHeader> = self.sections.iter().map(|s| s.header.clone()).collect();
         let mut sections = std::mem::replace(&mut self.sections, Vec::new());
 
@@ -223,7 +362,6 @@ impl Elf {
             for sec in &headers {
                 sec.to_writer(&self.header, &mut io)?;
             }
-            self.header.shnum = headers.len() as u16;
             self.header.shentsize = SectionHeader::entsize(&self.header) as u16;
         }
 
@@ -244,13 +382,13 @@ impl Elf {
     pub fn make_symtab_gnuld_compat(&mut self) -> Result<(), Error> {
         for i in 0..self.sections.len() {
             if self.sections[i].header.shtype == types::SectionType::SYMTAB {
-                self._make_symtab_gnuld_compat(i);
+                self._make_symtab_gnuld_compat(i)?;
             }
         }
         self.sync_all()
     }
 
-    fn _make_symtab_gnuld_compat(&mut self, shndx: usize) {
+    fn _make_symtab_gnuld_compat(&mut self, shndx: usize) -> Result<(), Error> {
 
         let mut original_size = self.sections[shndx].content.as_symbols().unwrap().len();
 
@@ -270,7 +408,7 @@ impl Elf {
         let mut symtab_gs = Vec::new();
         let mut symtab_ls = Vec::new();
         for (oi,sym) in symtab_remap {
-            if sym.bind == types::SymbolBind::GLOBAL {
+            if sym.bind != types::SymbolBind::LOCAL {
                 symtab_gs.push((oi, sym));
             } else {
                 symtab_ls.push((oi, sym));
@@ -287,7 +425,7 @@ impl Elf {
         let mut nu_sec_syms = vec![0];
         for i in 1..self.sections.len() {
             symtab_ls.insert(i, (original_size, symbol::Symbol{
-                shndx:  symbol::SymbolSectionIndex::Section(i as u16),
+                shndx:  symbol::SymbolSectionIndex::Section(i as u32),
                 value:  0,
                 size:   0,
                 name:   Vec::new(),
@@ -316,15 +454,22 @@ impl Elf {
         let symtab_remap : IndexMap<usize, symbol::Symbol>
             = IndexMap::from_iter(symtab_ls.into_iter().chain(symtab_gs.into_iter()));
 
+        //REL has the addend in the target section, so it has to be patched there
+        let mut implicit = Vec::new();
         for sec in &mut self.sections {
             match sec.header.shtype {
-                types::SectionType::RELA => {
+                types::SectionType::RELA | types::SectionType::REL => {
                     if sec.header.link != shndx as u32{
                         continue;
                     }
+                    let rel = sec.header.shtype == types::SectionType::REL;
                     for reloc in sec.content.as_relocations_mut().unwrap().iter_mut() {
                         if let Some(secsym) = symtab_sec.get(&(reloc.sym as usize)) {
                             if let symbol::SymbolSectionIndex::Section(so) = secsym.shndx {
+                                if rel && secsym.value != 0 {
+                                    implicit.push((sec.header.info as usize, reloc.clone(),
+                                                   secsym.value as i64));
+                                }
                                 reloc.addend += secsym.value as i64;
                                 reloc.sym     = nu_sec_syms[so as usize] as u32;
                             } else {
@@ -340,11 +485,47 @@ impl Elf {
             }
         }
 
+        for (target, mut reloc, delta) in implicit {
+            if let Some(raw) = self.sections[target].content.as_raw_mut() {
+                reloc.addend = reloc.implicit_addend(raw, &self.header)? + delta;
+                reloc.write_implicit_addend(raw, &self.header)?;
+            }
+        }
+
         self.sections[shndx].content = section::SectionContent::Symbols(
             symtab_remap.into_iter().map(|(_,v)|v).collect());
+        Ok(())
     }
 
 
+    /// relocatable objects have no segments and all addresses are 0.
+    /// sections are placed one after another in their current order
+    fn layout_relocatable(&mut self) -> Result<(), Error> {
+        self.sync_all()?;
+
+        self.segments.clear();
+        self.header.phnum     = 0;
+        self.header.phoff     = 0;
+        self.header.phentsize = 0;
+        self.header.ehsize    = self.header.size() as u16;
+
+        let mut off = self.header.size() as u64;
+        for sec in &mut self.sections[1..] {
+            if sec.header.addralign > 0 {
+                let oa = off % sec.header.addralign;
+                if oa != 0 {
+                    off += sec.header.addralign - oa;
+                }
+            }
+            sec.header.offset = off;
+            sec.header.addr   = 0;
+            if sec.header.shtype != types::SectionType::NOBITS {
+                off += sec.header.size;
+            }
+        }
+        Ok(())
+    }
+
     /// reorder to minimize segmentation
     /// will only reorder sections that come after the last locked section
     pub fn reorder(self: &mut Elf) -> Result<HashMap<usize,usize>, Error> {
@@ -359,7 +540,39 @@ impl Elf {
         }
 
 
+        // sections at fixed addresses go first, in address order. the rest is placed after them
+        let section_start = &self.section_start;
+        let relro = &self.relro;
         reorder.sort_by(|&(_,ref s1),&(_,ref s2)|{
+            match (section_start.get(&s1.name), section_start.get(&s2.name)) {
+                (Some(a1), Some(a2)) => return a1.cmp(a2),
+                (Some(_), None) => return std::cmp::Ordering::Less,
+                (None, Some(_)) => return std::cmp::Ordering::Greater,
+                (None, None) => {},
+            }
+
+            // the TLS template is .tdata followed by .tbss. it goes between the read only and the
+            // writable sections, so it can start the writable segment and be protected with relro
+            let s1_t = s1.header.flags.contains(types::SectionFlags::ALLOC | types::SectionFlags::TLS);
+            let s2_t = s2.header.flags.contains(types::SectionFlags::ALLOC | types::SectionFlags::TLS);
+            if s1_t && s2_t && s1.header.shtype != s2.header.shtype {
+                if s1.header.shtype == types::SectionType::NOBITS {
+                    return std::cmp::Ordering::Greater;
+                } else if s2.header.shtype == types::SectionType::NOBITS {
+                    return std::cmp::Ordering::Less;
+                }
+            }
+            if s1_t != s2_t {
+                let other = if s1_t { s2 } else { s1 };
+                let read_only = other.header.shtype != types::SectionType::NOBITS &&
+                    other.header.flags.contains(types::SectionFlags::ALLOC) &&
+                    !other.header.flags.contains(types::SectionFlags::WRITE);
+                return match (s1_t, read_only) {
+                    (true, true) | (false, false) => std::cmp::Ordering::Greater,
+                    _ => std::cmp::Ordering::Less,
+                };
+            }
+
             if s1.header.shtype != s2.header.shtype {
                 if s1.header.shtype == types::SectionType::NOBITS {
                     return std::cmp::Ordering::Greater;
@@ -386,6 +599,17 @@ impl Elf {
                 }
             }
 
+            // relro goes at the start of the writable segment
+            let s1_r = relro.contains(&s1.name);
+            let s2_r = relro.contains(&s2.name);
+            if s1_w && s1_r != s2_r {
+                if s1_r {
+                    return std::cmp::Ordering::Less;
+                } else {
+                    return std::cmp::Ordering::Greater;
+                }
+            }
+
             if s1.header.shtype != s2.header.shtype  {
                 return s1.header.shtype.to_u32().cmp(&s2.header.shtype.to_u32());
             }
@@ -416,12 +640,25 @@ impl Elf {
     }
 
     pub fn layout(self: &mut Elf) -> Result<(), Error> {
+        if self.header.etype == types::ElfType::REL {
+            return self.layout_relocatable();
+        }
         self.sync_all()?;
 
         let dbg_old_segments_count = self.segments.len();
 
         self.segments.clear();
 
+        // segments are aligned to the largest page size the image base allows
+        let page = if self.image_base & 0x1fffff == 0 { 0x200000 } else { 0x1000 };
+        // while protections apply to the pages the kernel actually maps
+        let small_page = 0x1000;
+
+        // the first section with a fixed address, and the first segment after it.
+        // everything from there on keeps its address when the headers are inserted
+        let mut first_fixed = None;
+        let mut fixed_segments = None;
+
         trace!("start of Elf::layout segmentation");
 
         let mut current_load_segment_flags = types::SegmentFlags::READABLE;
@@ -433,6 +670,12 @@ impl Elf {
         let mut poff = 0;
         let mut voff = poff;
 
+        // the thread pointer goes right after the TLS template, so its start is aligned for all of it
+        let mut tls : Option<segment::SegmentHeader> = None;
+        let tls_align = self.sections.iter()
+            .filter(|sec| sec.header.flags.contains(types::SectionFlags::ALLOC | types::SectionFlags::TLS))
+            .map(|sec| sec.header.addralign).max().unwrap_or(0);
+
         let mut dbg_old_addresses = vec![self.sections[0].header.addr];
 
         trace!("    name     \tsize\tpoff\tvoff\tpstart\tvstart\tflags");
@@ -445,13 +688,77 @@ impl Elf {
                      current_load_segment_vstart,
                      current_load_segment_flags);
 
-            if sec.header.addralign > 0 {
-                let oa = poff % sec.header.addralign;
+            let align = if tls.is_none() && sec.header.flags.contains(types::SectionFlags::ALLOC | types::SectionFlags::TLS) {
+                tls_align
+            } else {
+                sec.header.addralign
+            };
+            if align > 0 {
+                let oa = poff % align;
                 if oa != 0 {
-                    poff += sec.header.addralign - oa;
-                    voff += sec.header.addralign - oa;
+                    poff += align - oa;
+                    voff += align - oa;
+                }
+                trace!("   ^ realigned for {} to voff 0x{:x}", align, voff);
+            }
+
+            // a fixed address right where the section would go anyway doesn't need a new segment,
+            // unless it's the first one, which decides where the headers go, or the segment would
+            // have to be split anyway, which moves the section.
+            // splitting by protection would move it too, so the segment becomes writable instead,
+            // same as gnuld does for sections a script packs together
+            let mut packed = false;
+            let fixed = match self.section_start.get(&sec.name) {
+                Some(&start) => {
+                    packed = first_fixed.is_some() &&
+                        start >= self.image_base && start - self.image_base == voff &&
+                        (sec.header.shtype == types::SectionType::NOBITS || (voff - poff) % page == 0);
+                    if packed {
+                        if sec.header.flags.contains(types::SectionFlags::WRITE) {
+                            current_load_segment_flags.insert(types::SegmentFlags::WRITABLE);
+                        }
+                        None
+                    } else {
+                        Some(start)
+                    }
+                },
+                None => None,
+            };
+            if let Some(start) = fixed {
+                if start < self.image_base || start - self.image_base < voff {
+                    return Err(Error::SectionStartOverlaps{
+                        sec:    String::from_utf8_lossy(&sec.name).into_owned(),
+                        addr:   start,
+                        end:    self.image_base + voff,
+                    });
                 }
-                trace!("   ^ realigned for {} to voff 0x{:x}", sec.header.addralign, voff);
+                trace!("   ^ fixed address 0x{:x} starts a new segment", start);
+                if current_load_segment_voff > current_load_segment_vstart {
+                    self.segments.push(segment::SegmentHeader {
+                        phtype: types::SegmentType::LOAD,
+                        flags:  current_load_segment_flags,
+                        offset: current_load_segment_pstart,
+                        filesz: current_load_segment_poff - current_load_segment_pstart,
+                        vaddr:  current_load_segment_vstart,
+                        paddr:  current_load_segment_vstart,
+                        memsz:  current_load_segment_voff - current_load_segment_vstart,
+                        align:  page,
+                    });
+                }
+                if first_fixed.is_none() {
+                    first_fixed    = Some(shndx);
+                    fixed_segments = Some(self.segments.len());
+                }
+
+                voff = start - self.image_base;
+                // file offsets must stay congruent to addresses modulo the page size
+                poff += (voff - poff) % page;
+
+                current_load_segment_pstart = poff;
+                current_load_segment_vstart = voff;
+                current_load_segment_poff   = poff;
+                current_load_segment_voff   = voff;
+                current_load_segment_flags  = types::SegmentFlags::READABLE;
             }
 
             if sec.header.shtype != types::SectionType::NOBITS {
@@ -459,7 +766,7 @@ impl Elf {
                     panic!("elfkit: relayout: poff>voff 0x{:x}>0x{:x} in {}.", poff, voff,
                            String::from_utf8_lossy(&sec.name));
                 }
-                if (voff - poff) % 0x200000 != 0 {
+                if (voff - poff) % page != 0 {
                     trace!("   ^ causes segmentation by load alignment");
                     if sec.header.flags.contains(types::SectionFlags::EXECINSTR) {
                         current_load_segment_flags.insert(types::SegmentFlags::EXECUTABLE);
@@ -475,10 +782,10 @@ impl Elf {
                         vaddr:  current_load_segment_vstart,
                         paddr:  current_load_segment_vstart,
                         memsz:  current_load_segment_voff - current_load_segment_vstart,
-                        align:  0x200000,
+                        align:  page,
                     });
 
-                    voff += 0x200000 - ((voff - poff) % 0x200000);
+                    voff += page - ((voff - poff) % page);
 
                     current_load_segment_pstart = poff;
                     current_load_segment_vstart = voff;
@@ -488,14 +795,14 @@ impl Elf {
 
 
             if sec.header.flags.contains(types::SectionFlags::ALLOC) {
-                // can mix exec and read segments. at least gnuld does, so whatevs?
-                if sec.header.flags.contains(types::SectionFlags::EXECINSTR) {
-                    current_load_segment_flags.insert(types::SegmentFlags::EXECUTABLE);
-                } else {}
+                // can mix exec and read segments, unless asked not to. at least gnuld does, so whatevs?
+                let exec = sec.header.flags.contains(types::SectionFlags::EXECINSTR);
+                let exec_change = self.separate_code &&
+                    exec != current_load_segment_flags.contains(types::SegmentFlags::EXECUTABLE);
 
                 // cannot mix write and non write segments, danger zone
-                if sec.header.flags.contains(types::SectionFlags::WRITE) !=
-                current_load_segment_flags.contains(types::SegmentFlags::WRITABLE) {
+                if !packed && (exec_change || sec.header.flags.contains(types::SectionFlags::WRITE) !=
+                current_load_segment_flags.contains(types::SegmentFlags::WRITABLE)) {
                     if current_load_segment_voff >  current_load_segment_vstart || shndx == 1 {
                         //println!("   ^ causes segmentation by protection change");
                         self.segments.push(segment::SegmentHeader {
@@ -506,9 +813,17 @@ impl Elf {
                             vaddr:  current_load_segment_vstart,
                             paddr:  current_load_segment_vstart,
                             memsz:  current_load_segment_voff - current_load_segment_vstart,
-                            align:  0x200000,
+                            align:  page,
                         });
-                        voff += 0x200000 - ((voff - poff) % 0x200000);
+                        if self.separate_code {
+                            // the pages code is mapped from contain nothing else
+                            let oa = poff % small_page;
+                            if oa != 0 {
+                                poff += small_page - oa;
+                                voff += small_page - oa;
+                            }
+                        }
+                        voff += page - ((voff - poff) % page);
                         current_load_segment_pstart = poff;
                         current_load_segment_vstart = voff;
                         current_load_segment_flags = types::SegmentFlags::READABLE;
@@ -523,6 +838,13 @@ impl Elf {
                     } else {
                         current_load_segment_flags.remove(types::SegmentFlags::WRITABLE);
                     }
+                    if !exec {
+                        current_load_segment_flags.remove(types::SegmentFlags::EXECUTABLE);
+                    }
+                }
+
+                if exec {
+                    current_load_segment_flags.insert(types::SegmentFlags::EXECUTABLE);
                 }
             }
 
@@ -531,24 +853,35 @@ impl Elf {
             poff += sec.size(&self.header) as u64;
 
             sec.header.addr = voff;
-            voff += sec.header.size;
+            // .tbss is only a template for the threads' copies, so what follows may overlap it
+            if !(sec.header.shtype == types::SectionType::NOBITS &&
+                 sec.header.flags.contains(types::SectionFlags::TLS)) {
+                voff += sec.header.size;
+            }
             trace!("   = final addr 0x{:x}", sec.header.addr);
 
             if sec.header.flags.contains(types::SectionFlags::ALLOC) {
                 current_load_segment_poff = poff;
                 current_load_segment_voff = voff;
 
+                // one PT_TLS covers the whole template, the .tbss part of it only in memory
                 if sec.header.flags.contains(types::SectionFlags::TLS) {
-                    self.segments.push(segment::SegmentHeader {
+                    let seg = tls.get_or_insert(segment::SegmentHeader {
                         phtype: types::SegmentType::TLS,
-                        flags:  current_load_segment_flags,
+                        flags:  types::SegmentFlags::READABLE,
                         offset: sec.header.offset,
-                        filesz: sec.header.size,
+                        filesz: 0,
                         vaddr:  sec.header.addr,
                         paddr:  sec.header.addr,
-                        memsz:  sec.header.size,
-                        align:  sec.header.addralign,
+                        memsz:  0,
+                        align:  1,
                     });
+                    let end = sec.header.addr + sec.header.size - seg.vaddr;
+                    if sec.header.shtype != types::SectionType::NOBITS {
+                        seg.filesz = end;
+                    }
+                    seg.memsz = end;
+                    seg.align = seg.align.max(sec.header.addralign);
                 }
 
                 match sec.name.as_slice() {
@@ -564,6 +897,18 @@ impl Elf {
                             align: 0x8,
                         });
                     }
+                    _ if sec.header.shtype == types::SectionType::NOTE => {
+                        self.segments.push(segment::SegmentHeader {
+                            phtype: types::SegmentType::NOTE,
+                            flags: types::SegmentFlags::READABLE,
+                            offset: sec.header.offset,
+                            filesz: sec.header.size,
+                            vaddr: sec.header.addr,
+                            paddr: sec.header.addr,
+                            memsz: sec.header.size,
+                            align: note::align(sec.header.addralign),
+                        });
+                    }
                     b".interp" => {
                         self.segments.push(segment::SegmentHeader {
                             phtype: types::SegmentType::INTERP,
@@ -591,12 +936,40 @@ impl Elf {
                 vaddr:  current_load_segment_vstart,
                 paddr:  current_load_segment_vstart,
                 memsz:  current_load_segment_voff - current_load_segment_vstart,
-                align:  0x200000,
+                align:  page,
             });
         }
+        if let Some(tls) = tls {
+            self.segments.push(tls);
+        }
+
 
+        // the headers are only mapped if something is placed before the first fixed address
+        let map_headers = fixed_segments != Some(0);
 
-        self.header.phnum     = self.segments.len() as u16 + 1;
+        // relro is the run of relro sections a writable segment starts with
+        let mut relro = None;
+        let first_writable = self.sections.iter().position(|sec| {
+            sec.header.flags.contains(types::SectionFlags::ALLOC | types::SectionFlags::WRITE)
+        });
+        if let Some(first) = first_writable {
+            let offset = self.sections[first].header.offset;
+            let starts_segment = self.segments.iter().any(|seg| {
+                seg.phtype == types::SegmentType::LOAD && seg.offset == offset &&
+                    seg.flags.contains(types::SegmentFlags::WRITABLE)
+            });
+            let run = self.sections[first..].iter().take_while(|sec| {
+                sec.header.flags.contains(types::SectionFlags::ALLOC) && self.relro.contains(&sec.name)
+            }).count();
+            let size : u64 = self.sections[first..first + run].iter().map(|s| s.header.size).sum();
+            if starts_segment && size > 0 {
+                relro = Some((first, first + run));
+            }
+        }
+
+        // PT_GNU_STACK is always there, so the stack is only executable when asked for
+        self.header.phnum     = self.segments.len() as u32 + if map_headers { 1 } else { 0 } +
+            if relro.is_some() { 1 } else { 0 } + 1;
         self.header.phentsize = segment::SegmentHeader::entsize(&self.header) as u16;
         self.header.phoff     = self.header.size() as u64;
 
@@ -613,15 +986,48 @@ impl Elf {
                 }
             }
         }
-        for sec in &mut self.sections[1..] {
-            sec.header.offset += hoff;
-            sec.header.addr   += hoff;
+        if self.separate_code {
+            let oa = hoff % small_page;
+            if oa != 0 {
+                hoff += small_page - oa;
+            }
+        }
+        // sections at fixed addresses only move in the file, by whole pages
+        let first_fixed = first_fixed.unwrap_or(self.sections.len());
+        let fixed_segments = fixed_segments.unwrap_or(self.segments.len());
+        let mut fixed_hoff = hoff;
+        let oa = fixed_hoff % page;
+        if oa != 0 {
+            fixed_hoff += page - oa;
+        }
+        for (shndx, sec) in self.sections.iter_mut().enumerate().skip(1) {
+            if shndx < first_fixed {
+                sec.header.offset += hoff;
+                sec.header.addr   += hoff;
+            } else {
+                sec.header.offset += fixed_hoff;
+            }
+        }
+        if first_fixed < self.sections.len() {
+            let start = self.sections[first_fixed].header.addr;
+            for sec in &self.sections[1..first_fixed] {
+                if sec.header.flags.contains(types::SectionFlags::ALLOC) &&
+                    sec.header.addr + sec.header.size > start {
+                    return Err(Error::SectionStartOverlaps{
+                        sec:    String::from_utf8_lossy(&self.sections[first_fixed].name).into_owned(),
+                        addr:   start + self.image_base,
+                        end:    sec.header.addr + sec.header.size + self.image_base,
+                    });
+                }
+            }
         }
 
 
         let mut seen_first_load = false;
-        for seg in self.segments.iter_mut() {
-            if seg.phtype == types::SegmentType::LOAD && !seen_first_load {
+        for (i, seg) in self.segments.iter_mut().enumerate() {
+            if i >= fixed_segments {
+                seg.offset += fixed_hoff;
+            } else if seg.phtype == types::SegmentType::LOAD && !seen_first_load {
                 seen_first_load = true;
                 seg.memsz  += hoff;
                 seg.filesz += hoff;
@@ -632,17 +1038,110 @@ impl Elf {
             }
         }
 
-        self.segments.insert(0, segment::SegmentHeader {
-            phtype: types::SegmentType::PHDR,
-            flags: types::SegmentFlags::READABLE | types::SegmentFlags::EXECUTABLE,
-            offset: self.header.phoff,
-            filesz: self.header.phnum as u64 * self.header.phentsize as u64,
-            vaddr:  self.header.phoff,
-            paddr:  self.header.phoff,
-            memsz:  self.header.phnum as u64 * self.header.phentsize as u64,
-            align:  0x8,
+        if let Some((first, end)) = relro {
+            // the dynamic linker only protects whole pages. so the run is moved to end close to
+            // a page boundary, and what follows it to start right on it.
+            // not when sections have fixed addresses, because those can't move
+            let (offset, start) = (self.sections[first].header.offset, self.sections[first].header.addr);
+            let (run_offset, run_end) = {
+                let last = &self.sections[end - 1].header;
+                (last.offset + last.size, last.addr + last.size)
+            };
+            let align_of = |secs: &[Section]| secs.iter().map(|s| s.header.addralign).max().unwrap_or(1).max(1);
+
+            let mut pad = 0;
+            let mut gap = 0;
+            if first_fixed == self.sections.len() {
+                pad = (small_page - run_end % small_page) % small_page;
+                pad -= pad % align_of(&self.sections[first..end]);
+                // what follows already starts after the run's end, aligned to itself
+                let next = self.sections.get(end).map(|s| s.header.addr).unwrap_or(run_end) + pad;
+                let boundary = run_end + pad + (small_page - (run_end + pad) % small_page) % small_page;
+                gap = boundary.saturating_sub(next);
+                let align = align_of(&self.sections[end..]);
+                let oa = gap % align;
+                if oa != 0 {
+                    gap += align - oa;
+                }
+            }
+            for (shndx, sec) in self.sections.iter_mut().enumerate().skip(first) {
+                let by = if shndx < end { pad } else { pad + gap };
+                sec.header.offset += by;
+                sec.header.addr   += by;
+            }
+            for seg in &mut self.segments {
+                if seg.offset >= run_offset && seg.vaddr >= run_end {
+                    seg.offset += pad + gap;
+                    seg.vaddr  += pad + gap;
+                    seg.paddr  += pad + gap;
+                } else if seg.offset >= offset {
+                    // the writable segment itself grows by the gap
+                    if seg.offset + seg.filesz > run_offset {
+                        seg.filesz += gap;
+                    }
+                    if seg.vaddr + seg.memsz > run_end {
+                        seg.memsz += gap;
+                    }
+                    seg.offset += pad;
+                    seg.vaddr  += pad;
+                    seg.paddr  += pad;
+                }
+            }
+
+            let size = if gap > 0 || pad > 0 {
+                let end = run_end + pad;
+                end + (small_page - end % small_page) % small_page - start - pad
+            } else {
+                run_end - start
+            };
+            self.segments.push(segment::SegmentHeader {
+                phtype: types::SegmentType::GNU_RELRO,
+                flags:  types::SegmentFlags::READABLE,
+                offset: offset + pad,
+                filesz: size,
+                vaddr:  start + pad,
+                paddr:  start + pad,
+                memsz:  size,
+                align:  0x1,
+            });
+        }
+
+        for sec in &mut self.sections[1..] {
+            sec.header.addr += self.image_base;
+        }
+        for seg in &mut self.segments {
+            seg.vaddr += self.image_base;
+            seg.paddr += self.image_base;
+        }
+
+        let mut stack_flags = types::SegmentFlags::READABLE | types::SegmentFlags::WRITABLE;
+        if self.execstack {
+            stack_flags.insert(types::SegmentFlags::EXECUTABLE);
+        }
+        self.segments.push(segment::SegmentHeader {
+            phtype: types::SegmentType::GNU_STACK,
+            flags:  stack_flags,
+            offset: 0,
+            filesz: 0,
+            vaddr:  0,
+            paddr:  0,
+            memsz:  0,
+            align:  0x10,
         });
 
+        if map_headers {
+            self.segments.insert(0, segment::SegmentHeader {
+                phtype: types::SegmentType::PHDR,
+                flags: types::SegmentFlags::READABLE,
+                offset: self.header.phoff,
+                filesz: self.header.phnum as u64 * self.header.phentsize as u64,
+                vaddr:  self.header.phoff + self.image_base,
+                paddr:  self.header.phoff + self.image_base,
+                memsz:  self.header.phnum as u64 * self.header.phentsize as u64,
+                align:  0x8,
+            });
+        }
+
         trace!("done {} segments", self.segments.len());
 
         for i in 0..self.sections.len() {
@@ -699,6 +1198,15 @@ impl Elf {
                     sec.header.info -= 1;
                 }
             }
+
+            if let Some(group) = sec.content.as_group_mut() {
+                group.members.retain(|m| *m != at as u32);
+                for m in &mut group.members {
+                    if *m > at as u32 {
+                        *m -= 1;
+                    }
+                }
+            }
         }
 
         Ok(r)
@@ -716,6 +1224,14 @@ impl Elf {
                     sec.header.info += 1;
                 }
             }
+
+            if let Some(group) = sec.content.as_group_mut() {
+                for m in &mut group.members {
+                    if *m >= at as u32 {
+                        *m += 1;
+                    }
+                }
+            }
         }
 
         Ok(())
@@ -739,6 +1255,13 @@ impl Elf {
                     sec.header.info = 999999;
                 }
             }
+            if let Some(group) = sec.content.as_group_mut() {
+                for m in &mut group.members {
+                    if *m == from as u32 {
+                        *m = 999999;
+                    }
+                }
+            }
         }
         let sec = self.remove_section(from)?;
         self.insert_section(to, sec)?;
@@ -751,6 +1274,13 @@ impl Elf {
                     sec.header.info = to as u32;
                 }
             }
+            if let Some(group) = sec.content.as_group_mut() {
+                for m in &mut group.members {
+                    if *m == 999999 {
+                        *m = to as u32;
+                    }
+                }
+            }
         }
 
         Ok(())
@@ -0,0 +1,327 @@
+use header::Header;
+use types;
+use error::Error;
+use section::SectionHeader;
+use segment::SegmentHeader;
+use symbol::{self, SymbolSectionIndex};
+use relocation::Relocation;
+use dynamic::Dynamic;
+use note::{self, Note};
+use hash::{GnuHash, SysvHash};
+use group::Group;
+use version::{Verdef, Verneed, Versym};
+use utils::ElfEndianReadExt;
+use num_traits::FromPrimitive;
+
+/// A borrowed, read only view of an elf file.
+///
+/// Nothing is copied out of the underlying bytes until it is asked for.
+/// Sections, symbols, relocations and dynamic entries are decoded lazily by iterators,
+/// so this works on memory mapped files of any size.
+/// Use `Elf` instead when you need to modify or write the file.
+pub struct ElfRef<'a> {
+    pub header: Header,
+    data:       &'a [u8],
+    shstrtab:   &'a [u8],
+}
+
+/// A section header together with the bytes it refers to
+#[derive(Debug, Clone)]
+pub struct SectionRef<'a> {
+    pub index:  usize,
+    pub name:   &'a [u8],
+    pub header: SectionHeader,
+    /// the section content. empty for NOBITS
+    pub data:   &'a [u8],
+}
+
+/// Like `symbol::Symbol` but with the name borrowed from the string table
+#[derive(Debug, Clone)]
+pub struct SymbolRef<'a> {
+    pub name:   &'a [u8],
+    pub shndx:  SymbolSectionIndex,
+    pub value:  u64,
+    pub size:   u64,
+    pub stype:  types::SymbolType,
+    pub bind:   types::SymbolBind,
+    pub vis:    types::SymbolVis,
+}
+
+/// A dynamic entry. For tags that refer to a string, `string` is already looked up
+/// in the linked string table.
+#[derive(Debug, Clone)]
+pub struct DynamicRef<'a> {
+    pub dhtype: types::DynamicType,
+    pub val:    u64,
+    pub string: Option<&'a [u8]>,
+}
+
+fn slice(data: &[u8], offset: u64, size: u64) -> Result<&[u8], Error> {
+    let end = offset.checked_add(size).ok_or(Error::TruncatedInput)?;
+    if end > data.len() as u64 {
+        return Err(Error::TruncatedInput);
+    }
+    Ok(&data[offset as usize..end as usize])
+}
+
+/// the fixed size entries of a section. a section that isn't made of whole entries is truncated
+fn entries(data: &[u8], entsize: usize) -> Result<::std::slice::Chunks<'_, u8>, Error> {
+    if entsize == 0 || !data.len().is_multiple_of(entsize) {
+        return Err(Error::TruncatedInput);
+    }
+    Ok(data.chunks(entsize))
+}
+
+fn cstr(data: &[u8], offset: usize) -> &[u8] {
+    match data.get(offset..) {
+        None => &[],
+        Some(s) => s.split(|&c| c == 0).next().unwrap_or(&[]),
+    }
+}
+
+impl<'a> ElfRef<'a> {
+    pub fn from_bytes(data: &'a [u8]) -> Result<ElfRef<'a>, Error> {
+        let header = Header::from_reader(&mut &data[..])?;
+
+        let mut r = ElfRef {
+            header,
+            data,
+            shstrtab:   &[],
+        };
+
+        // extended numbering. the real values are in section 0
+        if r.header.is_extended() {
+            let mut io = slice(data, r.header.shoff, r.header.shentsize as u64)?;
+            let sh0 = SectionHeader::from_reader(&mut io, &r.header)?;
+            r.header.apply_section0(&sh0);
+        }
+
+        if r.header.shstrndx > 0 {
+            let sh = r.section_header(r.header.shstrndx as usize)?;
+            r.shstrtab = slice(data, sh.offset, sh.size)?;
+        }
+
+        Ok(r)
+    }
+
+    /// the complete underlying file
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    pub fn section_count(&self) -> usize {
+        self.header.shnum as usize
+    }
+
+    fn section_header(&self, i: usize) -> Result<SectionHeader, Error> {
+        if i >= self.section_count() {
+            return Err(Error::TruncatedInput);
+        }
+        let entsize = self.header.shentsize as u64;
+        let mut io = slice(self.data, self.header.shoff + i as u64 * entsize, entsize)?;
+        SectionHeader::from_reader(&mut io, &self.header)
+    }
+
+    pub fn section(&self, i: usize) -> Result<SectionRef<'a>, Error> {
+        let header = self.section_header(i)?;
+        let data = if header.shtype == types::SectionType::NOBITS {
+            &[]
+        } else {
+            slice(self.data, header.offset, header.size)?
+        };
+        Ok(SectionRef {
+            index:  i,
+            name:   cstr(self.shstrtab, header.name as usize),
+            header,
+            data,
+        })
+    }
+
+    pub fn sections<'b>(&'b self) -> impl Iterator<Item = Result<SectionRef<'a>, Error>> + 'b {
+        (0..self.section_count()).map(move |i| self.section(i))
+    }
+
+    pub fn section_by_name(&self, name: &[u8]) -> Option<SectionRef<'a>> {
+        self.sections()
+            .filter_map(|s| s.ok())
+            .find(|s| s.name == name)
+    }
+
+    pub fn segments<'b>(&'b self) -> impl Iterator<Item = Result<SegmentHeader, Error>> + 'b {
+        let entsize = self.header.phentsize as u64;
+        (0..self.header.phnum as u64).map(move |i| {
+            let mut io = slice(self.data, self.header.phoff + i * entsize, entsize)?;
+            SegmentHeader::from_reader(&mut io, &self.header)
+        })
+    }
+
+    /// the notes in a NOTE section
+    pub fn notes(&self, sec: &SectionRef<'a>) -> Result<Vec<Note>, Error> {
+        Note::from_reader(sec.data, &self.header, note::align(sec.header.addralign))
+    }
+
+    /// the notes in a PT_NOTE segment
+    pub fn segment_notes(&self, seg: &SegmentHeader) -> Result<Vec<Note>, Error> {
+        if seg.phtype != types::SegmentType::NOTE {
+            return Err(Error::UnexpectedSegmentType(seg.phtype.clone()));
+        }
+        let data = slice(self.data, seg.offset, seg.filesz)?;
+        Note::from_reader(data, &self.header, note::align(seg.align))
+    }
+
+    /// the table in a HASH section
+    pub fn hash(&self, sec: &SectionRef<'a>) -> Result<SysvHash, Error> {
+        SysvHash::from_reader(sec.data, &self.header)
+    }
+
+    /// the table in a GNU_HASH section
+    pub fn gnu_hash(&self, sec: &SectionRef<'a>) -> Result<GnuHash, Error> {
+        GnuHash::from_reader(sec.data, &self.header)
+    }
+
+    /// the flags and member section indices of a GROUP section
+    pub fn group(&self, sec: &SectionRef<'a>) -> Result<Group, Error> {
+        Group::from_reader(sec.data, &self.header)
+    }
+
+    /// the entries of a GNU_VERSYM section, one per symbol in the linked .dynsym
+    pub fn versyms(&self, sec: &SectionRef<'a>) -> Result<Vec<Versym>, Error> {
+        let mut data = sec.data;
+        let mut r = Vec::new();
+        while data.len() >= 2 {
+            r.push(Versym::from_u16(data.elf_read_u16(&self.header)?));
+        }
+        Ok(r)
+    }
+
+    /// the version definitions in a GNU_VERDEF section
+    pub fn verdefs(&self, sec: &SectionRef<'a>) -> Result<Vec<Verdef>, Error> {
+        let strtab = self.linked_strtab(sec)?;
+        Verdef::from_bytes(sec.data, &self.header, |i| cstr(strtab, i as usize).to_vec())
+    }
+
+    /// the version requirements in a GNU_VERNEED section
+    pub fn verneeds(&self, sec: &SectionRef<'a>) -> Result<Vec<Verneed>, Error> {
+        let strtab = self.linked_strtab(sec)?;
+        Verneed::from_bytes(sec.data, &self.header, |i| cstr(strtab, i as usize).to_vec())
+    }
+
+    /// the section linked from sec, if it is a string table
+    fn linked_strtab(&self, sec: &SectionRef<'a>) -> Result<&'a [u8], Error> {
+        if sec.header.link < 1 {
+            return Ok(&[]);
+        }
+        let linked = self.section(sec.header.link as usize)?;
+        if linked.header.shtype != types::SectionType::STRTAB {
+            return Err(Error::LinkedSectionIsNotStrtab{
+                during: "viewing linked strtab",
+                link:   None,
+            });
+        }
+        Ok(linked.data)
+    }
+
+    pub fn symbols(&self, sec: &SectionRef<'a>)
+        -> Result<impl Iterator<Item = Result<SymbolRef<'a>, Error>> + 'a, Error> {
+        let strtab = self.linked_strtab(sec)?;
+        let eh     = self.header.clone();
+        let xindex = self.sections()
+            .filter_map(|s| s.ok())
+            .find(|s| s.header.shtype == types::SectionType::SYMTAB_SHNDX &&
+                  s.header.link as usize == sec.index)
+            .map(|s| s.data)
+            .unwrap_or(&[]);
+        Ok(entries(sec.data, symbol::Symbol::entsize(&self.header))?.enumerate().map(move |(i, b)| {
+            let e = symbol::SymbolEntry::from_bytes(b, &eh)?;
+            let name = cstr(strtab, e._name as usize);
+            let (mut shndx, stype, bind, vis) = e.decode(name)?;
+            if e.shndx == types::SHN_XINDEX {
+                let mut io = match xindex.get(i * 4..) {
+                    Some(v) => v,
+                    None => return Err(Error::InvalidSymbolShndx(
+                            String::from_utf8_lossy(name).into_owned(), e.shndx)),
+                };
+                shndx = SymbolSectionIndex::Section(io.elf_read_u32(&eh)?);
+            }
+            Ok(SymbolRef {
+                name,
+                shndx,
+                value:  e.value,
+                size:   e.size,
+                stype,
+                bind,
+                vis,
+            })
+        }))
+    }
+
+    /// entries of a REL or RELA section.
+    /// for REL the addend is read from the section the relocations apply to.
+    /// dynamic REL sections don't apply to a single section (info is 0), their addends stay 0.
+    pub fn relocations(&self, sec: &SectionRef<'a>)
+        -> Result<impl Iterator<Item = Result<Relocation, Error>> + 'a, Error> {
+        let eh   = self.header.clone();
+        let rela = sec.header.shtype != types::SectionType::REL;
+        let target = if rela || sec.header.info == 0 {
+            None
+        } else {
+            Some(self.section(sec.header.info as usize)?.data)
+        };
+        let entsize = if rela { Relocation::entsize(&eh) } else { Relocation::entsize_rel(&eh) };
+        Ok(entries(sec.data, entsize)?.map(move |b| {
+            let mut r = Relocation::from_bytes(b, &eh, rela)?;
+            if let Some(target) = target {
+                r.addend = r.implicit_addend(target, &eh)?;
+            }
+            Ok(r)
+        }))
+    }
+
+    pub fn dynamic(&self, sec: &SectionRef<'a>)
+        -> Result<impl Iterator<Item = Result<DynamicRef<'a>, Error>> + 'a, Error> {
+        let strtab  = self.linked_strtab(sec)?;
+        let eh      = self.header.clone();
+        Ok(entries(sec.data, Dynamic::entsize(&self.header))?.map(move |mut b| {
+            let tag = b.elf_read_uclass(&eh)?;
+            let val = b.elf_read_uclass(&eh)?;
+            let dhtype = match types::DynamicType::from_u64(tag) {
+                Some(v) => v,
+                None    => return Err(Error::InvalidDynamicType(tag)),
+            };
+            let string = if dhtype.is_string() {
+                Some(cstr(strtab, val as usize))
+            } else {
+                None
+            };
+            Ok(DynamicRef {
+                dhtype,
+                val,
+                string,
+            })
+        }).scan(false, |done, r| {
+            // stop after DT_NULL, anything behind it is padding
+            if *done {
+                return None;
+            }
+            if let Ok(ref d) = r {
+                *done = d.dhtype == types::DynamicType::NULL;
+            }
+            Some(r)
+        }))
+    }
+}
+
+impl<'a> SymbolRef<'a> {
+    pub fn to_owned(&self) -> symbol::Symbol {
+        symbol::Symbol {
+            shndx:  self.shndx.clone(),
+            value:  self.value,
+            size:   self.size,
+            name:   self.name.to_vec(),
+            stype:  self.stype.clone(),
+            bind:   self.bind.clone(),
+            vis:    self.vis.clone(),
+            _name:  0,
+        }
+    }
+}
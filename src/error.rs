@@ -5,6 +5,8 @@ use section::SectionContent;
 pub enum Error {
     Io(::std::io::Error),
     InvalidMagic,
+    TruncatedInput,
+    UnexpectedSegmentType(types::SegmentType),
     InvalidIdentClass(u8),
     InvalidEndianness(u8),
     InvalidIdentVersion(u8),
@@ -27,6 +29,7 @@ pub enum Error {
         link:  Option<SectionContent>,
     },
     InvalidDynamicFlags1(u64),
+    InvalidDynamicFlags(u64),
     FirstSectionOffsetCanNotBeLargerThanAddress,
     MissingSymtabSection,
     LinkedSectionIsNotSymtab,
@@ -34,6 +37,13 @@ pub enum Error {
     InvalidSymbolShndx(String, u16),
     DynsymInStaticLibrary,
     SymbolSectionIndexExtendedCannotBeWritten,
+    UnsortedGnuHashSymbols,
+    InvalidCompressionType(u32),
+    CompressedSizeMismatch{
+        expected:   u64,
+        got:        u64,
+    },
+    CompressingAllocSection,
     WritingNotSynced,
     SyncingUnloadedSection,
     WritingUnloadedSection,
@@ -50,6 +60,25 @@ pub enum Error {
         sym:    String,
         obj:    String,
     },
+    LoadFailed{
+        name:   String,
+        cause:  Box<Error>,
+    },
+    LinkerScriptSyntax{
+        line:       usize,
+        msg:        String,
+    },
+    LinkerScriptUndefined(String),
+    RegionOverflow{
+        region:     String,
+        sec:        String,
+        by:         u64,
+    },
+    SectionStartOverlaps{
+        sec:        String,
+        addr:       u64,
+        end:        u64,
+    },
     MovingLockedSection{
         sec:        String,
         old_addr:   u64,
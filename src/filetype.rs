@@ -1,4 +1,4 @@
-use std::io::{Read, Result, Seek, SeekFrom};
+use std::io::{ErrorKind, Read, Result, Seek, SeekFrom};
 
 pub enum FileType {
     Archive,
@@ -13,7 +13,11 @@ where
 {
     io.seek(SeekFrom::Start(0))?;
     let mut magic = [0; 8];
-    io.read(&mut magic)?;
+    // anything shorter than the magic is neither of these
+    match io.read_exact(&mut magic) {
+        Err(ref e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(FileType::Unknown),
+        r => r?,
+    }
     io.seek(SeekFrom::Start(0))?;
 
     if magic[0..4] == [0x7F, 'E' as u8, 'L' as u8, 'F' as u8] {
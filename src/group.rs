@@ -0,0 +1,52 @@
+use std::io::{Read, Write};
+use {Error, Header};
+use types;
+use utils::ElfEndianReadExt;
+
+/// the content of a SHT_GROUP section.
+/// the signature is the symbol at sh_info in the linked symbol table
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Group {
+    pub flags:      types::GroupFlags,
+    /// indices of the sections in the group, including their relocation sections
+    pub members:    Vec<u32>,
+}
+
+impl Group {
+    pub fn entsize(_: &Header) -> usize {
+        4
+    }
+
+    pub fn size(&self) -> usize {
+        (1 + self.members.len()) * 4
+    }
+
+    pub fn is_comdat(&self) -> bool {
+        self.flags.contains(types::GroupFlags::COMDAT)
+    }
+
+    /// the number of members isn't stored anywhere, so this reads until the end of io
+    pub fn from_reader<R>(mut io: R, eh: &Header) -> Result<Group, Error> where R: Read {
+        let flags = io.elf_read_u32(eh)?;
+        let mut r = Group {
+            flags:   types::GroupFlags::from_bits_truncate(flags),
+            members: Vec::new(),
+        };
+
+        let mut rest = Vec::new();
+        io.read_to_end(&mut rest)?;
+        let mut rest = &rest[..rest.len() - rest.len() % 4];
+        while !rest.is_empty() {
+            r.members.push(rest.elf_read_u32(eh)?);
+        }
+        Ok(r)
+    }
+
+    pub fn to_writer<W>(&self, mut io: W, eh: &Header) -> Result<usize, Error> where W: Write {
+        elf_write_u32!(eh, io, self.flags.bits())?;
+        for m in &self.members {
+            elf_write_u32!(eh, io, *m)?;
+        }
+        Ok(self.size())
+    }
+}
@@ -0,0 +1,288 @@
+use std::io::{Read, Write};
+use {Error, Header};
+use symbol::{Symbol, SymbolSectionIndex};
+use types;
+use utils::ElfEndianReadExt;
+
+/// the hash function used by SHT_HASH / DT_HASH
+pub fn sysv_hash(name: &[u8]) -> u32 {
+    let mut h: u32 = 0;
+    for byte in name {
+        h = (h << 4).wrapping_add(*byte as u32);
+        let g = h & 0xf0000000;
+        if g != 0 {
+            h ^= g >> 24;
+        }
+        h &= !g;
+    }
+    h
+}
+
+/// the hash function used by SHT_GNU_HASH / DT_GNU_HASH
+pub fn gnu_hash(name: &[u8]) -> u32 {
+    let mut h: u32 = 5381;
+    for byte in name {
+        h = h.wrapping_mul(33).wrapping_add(*byte as u32);
+    }
+    h
+}
+
+/// bucket counts used by gnu ld for SHT_HASH
+const SYSV_BUCKETS: &[u32] = &[
+    1, 3, 17, 37, 67, 97, 131, 197, 263, 521, 1031, 2053, 4099, 8209,
+    16411, 32771, 65537, 131101, 262147,
+];
+
+/// a SHT_HASH table. chains has one entry per symbol in the linked symbol table
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SysvHash {
+    pub buckets:    Vec<u32>,
+    pub chains:     Vec<u32>,
+}
+
+impl SysvHash {
+    /// build a table over all symbols except the null symbol at index 0
+    pub fn new(symbols: &[Symbol]) -> SysvHash {
+        let mut nbuckets = 1;
+        for (i, b) in SYSV_BUCKETS.iter().enumerate() {
+            nbuckets = *b;
+            if i + 1 == SYSV_BUCKETS.len() || symbols.len() < SYSV_BUCKETS[i + 1] as usize {
+                break;
+            }
+        }
+
+        let mut r = SysvHash {
+            buckets: vec![0; nbuckets as usize],
+            chains:  vec![0; symbols.len()],
+        };
+        for (i, sym) in symbols.iter().enumerate().skip(1).rev() {
+            let b = (sysv_hash(&sym.name) % nbuckets) as usize;
+            r.chains[i]  = r.buckets[b];
+            r.buckets[b] = i as u32;
+        }
+        r
+    }
+
+    /// index of the symbol called name, if there is one
+    pub fn lookup(&self, name: &[u8], symbols: &[Symbol]) -> Option<usize> {
+        if self.buckets.is_empty() {
+            return None;
+        }
+        let mut i = self.buckets[(sysv_hash(name) % self.buckets.len() as u32) as usize] as usize;
+        while i != 0 && i < symbols.len() && i < self.chains.len() {
+            if symbols[i].name == name {
+                return Some(i);
+            }
+            i = self.chains[i] as usize;
+        }
+        None
+    }
+
+    pub fn size(&self) -> usize {
+        (2 + self.buckets.len() + self.chains.len()) * 4
+    }
+
+    pub fn from_reader<R>(mut io: R, eh: &Header) -> Result<SysvHash, Error> where R: Read {
+        let nbuckets = io.elf_read_u32(eh)?;
+        let nchains  = io.elf_read_u32(eh)?;
+        let mut r = SysvHash::default();
+        for _ in 0..nbuckets {
+            r.buckets.push(io.elf_read_u32(eh)?);
+        }
+        for _ in 0..nchains {
+            r.chains.push(io.elf_read_u32(eh)?);
+        }
+        Ok(r)
+    }
+
+    pub fn to_writer<W>(&self, mut io: W, eh: &Header) -> Result<usize, Error> where W: Write {
+        elf_write_u32!(eh, io, self.buckets.len() as u32)?;
+        elf_write_u32!(eh, io, self.chains.len() as u32)?;
+        for v in self.buckets.iter().chain(self.chains.iter()) {
+            elf_write_u32!(eh, io, *v)?;
+        }
+        Ok(self.size())
+    }
+}
+
+/// a SHT_GNU_HASH table. the symbols from symoffset on must be sorted by bucket,
+/// which is what gnu_hash_order does
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GnuHash {
+    pub symoffset:      u32,
+    pub bloom_shift:    u32,
+    /// words are 32 or 64 bit, depending on the elf class
+    pub bloom:          Vec<u64>,
+    pub buckets:        Vec<u32>,
+    /// one entry per symbol from symoffset on
+    pub chains:         Vec<u32>,
+}
+
+/// whether a dynamic symbol goes into the gnu hash table
+pub fn is_gnu_hashed(sym: &Symbol) -> bool {
+    sym.shndx != SymbolSectionIndex::Undefined && sym.bind != types::SymbolBind::LOCAL
+}
+
+fn bloom_bits(eh: &Header) -> u32 {
+    match eh.ident_class {
+        types::Class::Class64 => 64,
+        types::Class::Class32 => 32,
+    }
+}
+
+fn gnu_nbuckets(nhashed: usize) -> u32 {
+    ::std::cmp::max(nhashed / 4, 1) as u32
+}
+
+/// sort symbols so that the unhashed ones come first, in their original order,
+/// followed by the hashed ones grouped by gnu hash bucket.
+/// returns the new index of every symbol, indexed by its old index
+pub fn gnu_hash_order(symbols: &mut Vec<Symbol>) -> Vec<usize> {
+    let nbuckets = gnu_nbuckets(symbols.iter().filter(|s| is_gnu_hashed(s)).count());
+
+    let mut order : Vec<(usize, Symbol)> = symbols.drain(..).enumerate().collect();
+    order.sort_by_key(|(_, sym)| {
+        if is_gnu_hashed(sym) {
+            (true, gnu_hash(&sym.name) % nbuckets)
+        } else {
+            (false, 0)
+        }
+    });
+
+    let mut remap = vec![0; order.len()];
+    for (i, (old, sym)) in order.into_iter().enumerate() {
+        remap[old] = i;
+        symbols.push(sym);
+    }
+    remap
+}
+
+impl GnuHash {
+    /// build a table over symbols that have been sorted with gnu_hash_order
+    pub fn new(symbols: &[Symbol], eh: &Header) -> Result<GnuHash, Error> {
+        let symoffset = symbols.iter().position(is_gnu_hashed).unwrap_or(symbols.len());
+        let hashed    = &symbols[symoffset..];
+        let nbuckets  = gnu_nbuckets(hashed.len());
+        let c         = bloom_bits(eh);
+
+        // 12 bits per symbol, rounded up to a power of two words
+        let nbloom = ::std::cmp::max(hashed.len() * 12 / c as usize, 1).next_power_of_two();
+
+        let mut r = GnuHash {
+            symoffset:   symoffset as u32,
+            bloom_shift: 26,
+            bloom:       vec![0; nbloom],
+            buckets:     vec![0; nbuckets as usize],
+            chains:      Vec::with_capacity(hashed.len()),
+        };
+
+        let mut last_bucket = None;
+        for (i, sym) in hashed.iter().enumerate() {
+            if !is_gnu_hashed(sym) {
+                return Err(Error::UnsortedGnuHashSymbols);
+            }
+            let h = gnu_hash(&sym.name);
+            let b = h % nbuckets;
+
+            let word = ((h / c) as usize) % nbloom;
+            r.bloom[word] |= (1 << (h % c)) | (1 << ((h >> r.bloom_shift) % c));
+
+            if last_bucket != Some(b) {
+                if let Some(last) = last_bucket {
+                    if last > b {
+                        return Err(Error::UnsortedGnuHashSymbols);
+                    }
+                    *r.chains.last_mut().unwrap() |= 1;
+                }
+                r.buckets[b as usize] = symoffset as u32 + i as u32;
+                last_bucket = Some(b);
+            }
+            r.chains.push(h & !1);
+        }
+        if let Some(v) = r.chains.last_mut() {
+            *v |= 1;
+        }
+        Ok(r)
+    }
+
+    /// index of the symbol called name, if there is one
+    pub fn lookup(&self, name: &[u8], symbols: &[Symbol], eh: &Header) -> Option<usize> {
+        if self.buckets.is_empty() || self.bloom.is_empty() {
+            return None;
+        }
+        let h = gnu_hash(name);
+        let c = bloom_bits(eh);
+
+        let word = self.bloom[((h / c) as usize) % self.bloom.len()];
+        let mask = (1 << (h % c)) | (1 << (h.wrapping_shr(self.bloom_shift) % c));
+        if word & mask != mask {
+            return None;
+        }
+
+        let mut i = self.buckets[(h % self.buckets.len() as u32) as usize] as usize;
+        if i < self.symoffset as usize {
+            return None;
+        }
+        while i < symbols.len() {
+            let ch = match self.chains.get(i - self.symoffset as usize) {
+                Some(v) => *v,
+                None => return None,
+            };
+            if ch | 1 == h | 1 && symbols[i].name == name {
+                return Some(i);
+            }
+            if ch & 1 != 0 {
+                break;
+            }
+            i += 1;
+        }
+        None
+    }
+
+    pub fn size(&self, eh: &Header) -> usize {
+        16 + self.bloom.len() * (bloom_bits(eh) as usize / 8)
+            + (self.buckets.len() + self.chains.len()) * 4
+    }
+
+    /// the number of chain entries isn't stored anywhere, so this reads until the end of io
+    pub fn from_reader<R>(mut io: R, eh: &Header) -> Result<GnuHash, Error> where R: Read {
+        let nbuckets    = io.elf_read_u32(eh)?;
+        let symoffset   = io.elf_read_u32(eh)?;
+        let nbloom      = io.elf_read_u32(eh)?;
+        let bloom_shift = io.elf_read_u32(eh)?;
+
+        let mut r = GnuHash {
+            symoffset,
+            bloom_shift,
+            ..Default::default()
+        };
+        for _ in 0..nbloom {
+            r.bloom.push(io.elf_read_uclass(eh)?);
+        }
+        for _ in 0..nbuckets {
+            r.buckets.push(io.elf_read_u32(eh)?);
+        }
+
+        let mut rest = Vec::new();
+        io.read_to_end(&mut rest)?;
+        let mut rest = &rest[..rest.len() - rest.len() % 4];
+        while !rest.is_empty() {
+            r.chains.push(rest.elf_read_u32(eh)?);
+        }
+        Ok(r)
+    }
+
+    pub fn to_writer<W>(&self, mut io: W, eh: &Header) -> Result<usize, Error> where W: Write {
+        elf_write_u32!(eh, io, self.buckets.len() as u32)?;
+        elf_write_u32!(eh, io, self.symoffset)?;
+        elf_write_u32!(eh, io, self.bloom.len() as u32)?;
+        elf_write_u32!(eh, io, self.bloom_shift)?;
+        for v in &self.bloom {
+            elf_write_uclass!(eh, io, *v)?;
+        }
+        for v in self.buckets.iter().chain(self.chains.iter()) {
+            elf_write_u32!(eh, io, *v)?;
+        }
+        Ok(self.size(eh))
+    }
+}
@@ -3,6 +3,7 @@ use std::io::{Read, Write};
 use std::io::BufWriter;
 use error::Error;
 use types;
+use section::SectionHeader;
 
 #[derive(Debug,Clone)]
 pub struct Header {
@@ -22,10 +23,10 @@ pub struct Header {
     pub flags:      types::HeaderFlags,
     pub ehsize:     u16, //size of this header (who cares?)
     pub phentsize:  u16, //the size of a program header table entry
-    pub phnum:      u16, //the number of entries in the program header table
+    pub phnum:      u32, //the number of entries in the program header table
     pub shentsize:  u16, //the size of a section header table entry
-    pub shnum:      u16, //the number of entries in the section header table
-    pub shstrndx:   u16, //where to find section names
+    pub shnum:      u32, //the number of entries in the section header table
+    pub shstrndx:   u32, //where to find section names
 }
 
 impl Default for Header {
@@ -128,10 +129,10 @@ impl Header {
 
             r.ehsize    = read_u16(io)?;
             r.phentsize = read_u16(io)?;
-            r.phnum     = read_u16(io)?;
+            r.phnum     = read_u16(io)? as u32;
             r.shentsize = read_u16(io)?;
-            r.shnum     = read_u16(io)?;
-            r.shstrndx  = read_u16(io)?;
+            r.shnum     = read_u16(io)? as u32;
+            r.shstrndx  = read_u16(io)? as u32;
         });
 
         Ok(r)
@@ -158,14 +159,56 @@ impl Header {
         elf_write_u32!(self, w, self.flags.bits())?;
         elf_write_u16!(self, w, self.ehsize.to_u16().unwrap())?;
         elf_write_u16!(self, w, self.phentsize.to_u16().unwrap())?;
-        elf_write_u16!(self, w, self.phnum.to_u16().unwrap())?;
+        //values that don't fit are in section 0, see extend_section0
+        elf_write_u16!(self, w, if self.phnum >= types::PN_XNUM as u32 {
+            types::PN_XNUM
+        } else {
+            self.phnum as u16
+        })?;
         elf_write_u16!(self, w, self.shentsize.to_u16().unwrap())?;
-        elf_write_u16!(self, w, self.shnum.to_u16().unwrap())?;
-        elf_write_u16!(self, w, self.shstrndx.to_u16().unwrap())?;
+        elf_write_u16!(self, w, if self.shnum >= types::SHN_LORESERVE as u32 {
+            0
+        } else {
+            self.shnum as u16
+        })?;
+        elf_write_u16!(self, w, if self.shstrndx >= types::SHN_LORESERVE as u32 {
+            types::SHN_XINDEX
+        } else {
+            self.shstrndx as u16
+        })?;
 
         Ok(())
     }
 
+    /// true if the header read from disk refers to section 0 for the actual
+    /// number of sections, the shstrtab index or the number of segments
+    pub fn is_extended(&self) -> bool {
+        (self.shnum == 0 && self.shoff != 0) ||
+            self.shstrndx == types::SHN_XINDEX as u32 ||
+            self.phnum == types::PN_XNUM as u32
+    }
+
+    /// fill in the real values of an extended header from section 0
+    pub fn apply_section0(&mut self, sh0: &SectionHeader) {
+        if self.shnum == 0 && self.shoff != 0 {
+            self.shnum = sh0.size as u32;
+        }
+        if self.shstrndx == types::SHN_XINDEX as u32 {
+            self.shstrndx = sh0.link;
+        }
+        if self.phnum == types::PN_XNUM as u32 {
+            self.phnum = sh0.info;
+        }
+    }
+
+    /// store the values that don't fit into the header in section 0.
+    /// the reverse of apply_section0
+    pub fn extend_section0(&self, sh0: &mut SectionHeader) {
+        sh0.size = if self.shnum >= types::SHN_LORESERVE as u32 { self.shnum as u64 } else { 0 };
+        sh0.link = if self.shstrndx >= types::SHN_LORESERVE as u32 { self.shstrndx } else { 0 };
+        sh0.info = if self.phnum >= types::PN_XNUM as u32 { self.phnum } else { 0 };
+    }
+
     pub fn size(&self) -> usize {
         16 + 2 + 2 + 4 + match self.ident_class {
             types::Class::Class32 => 4 + 4 + 4,
@@ -1,17 +1,26 @@
 #[macro_use] extern crate bitflags;
 extern crate byteorder;
+extern crate flate2;
+extern crate ruzstd;
 #[macro_use] extern crate enum_primitive_derive;
 extern crate num_traits;
 extern crate indexmap;
 #[macro_use] extern crate log;
 
 #[macro_use] pub mod utils;
+pub mod compress;
 pub mod dynamic;
 pub mod elf;
+pub mod elfref;
 pub mod error;
 pub mod filetype;
+pub mod group;
+pub mod hash;
 pub mod header;
+pub mod linker_script;
 pub mod loader;
+pub mod merge;
+pub mod note;
 pub mod symbolic_linker;
 pub mod relocation;
 pub mod section;
@@ -19,11 +28,14 @@ pub mod segment;
 pub mod strtab;
 pub mod symbol;
 pub mod types;
+pub mod version;
 
 pub use dynamic::{Dynamic, DynamicContent};
 pub use elf::Elf;
+pub use elfref::ElfRef;
 pub use error::Error;
 pub use header::Header;
+pub use note::{Note, NoteContent};
 pub use symbolic_linker::{SymbolicLinker};
 pub use relocation::Relocation;
 pub use section::{Section, SectionContent, SectionHeader};
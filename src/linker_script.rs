@@ -0,0 +1,939 @@
+//! a subset of the GNU ld script language.
+//!
+//! supported are ENTRY, MEMORY, SECTIONS with output sections, input section wildcards,
+//! KEEP, SORT, /DISCARD/, (NOLOAD), >REGION, PROVIDE and assignments to symbols and `.`.
+//!
+//! the linker asks the script where each input section goes while merging,
+//! and the script decides the addresses of the output sections before layout.
+//! inside an output section `.` is relative to the start of the section, like in gnu ld.
+
+use std;
+use std::collections::HashMap;
+use {Elf, Error};
+use types;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    And,
+    Or,
+    Shl,
+    Shr,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Number(u64),
+    /// the location counter
+    Dot,
+    Symbol(Vec<u8>),
+    Neg(Box<Expr>),
+    Not(Box<Expr>),
+    Binary(Op, Box<Expr>, Box<Expr>),
+    /// ALIGN(align) aligns `.`, ALIGN(expr, align) aligns expr
+    Align(Option<Box<Expr>>, Box<Expr>),
+    Max(Box<Expr>, Box<Expr>),
+    Min(Box<Expr>, Box<Expr>),
+    Origin(Vec<u8>),
+    Length(Vec<u8>),
+    /// address of an output section
+    Addr(Vec<u8>),
+    SizeOf(Vec<u8>),
+}
+
+/// everything an expression can refer to
+pub struct Env<'a> {
+    pub dot:        u64,
+    pub symbols:    &'a HashMap<Vec<u8>, u64>,
+    /// address and size of the output sections placed so far
+    pub sections:   &'a HashMap<Vec<u8>, (u64, u64)>,
+    pub memory:     &'a [MemoryRegion],
+}
+
+fn align_up(v: u64, align: u64) -> u64 {
+    if align < 2 {
+        return v;
+    }
+    let oa = v % align;
+    if oa != 0 { v + (align - oa) } else { v }
+}
+
+impl Expr {
+    pub fn eval(&self, env: &Env) -> Result<u64, Error> {
+        let undefined = |what: &str, name: &[u8]| {
+            Error::LinkerScriptUndefined(format!("{} {}", what, String::from_utf8_lossy(name)))
+        };
+        Ok(match *self {
+            Expr::Number(v) => v,
+            Expr::Dot => env.dot,
+            Expr::Symbol(ref name) => match env.symbols.get(name) {
+                Some(v) => *v,
+                None => return Err(undefined("symbol", name)),
+            },
+            Expr::Neg(ref e) => e.eval(env)?.wrapping_neg(),
+            Expr::Not(ref e) => !e.eval(env)?,
+            Expr::Binary(ref op, ref a, ref b) => {
+                let a = a.eval(env)?;
+                let b = b.eval(env)?;
+                match *op {
+                    Op::Add => a.wrapping_add(b),
+                    Op::Sub => a.wrapping_sub(b),
+                    Op::Mul => a.wrapping_mul(b),
+                    Op::Div => a.checked_div(b).unwrap_or(0),
+                    Op::Mod => a.checked_rem(b).unwrap_or(0),
+                    Op::And => a & b,
+                    Op::Or  => a | b,
+                    Op::Shl => a.wrapping_shl(b as u32),
+                    Op::Shr => a.wrapping_shr(b as u32),
+                }
+            },
+            Expr::Align(ref e, ref align) => {
+                let v = match *e {
+                    Some(ref e) => e.eval(env)?,
+                    None => env.dot,
+                };
+                align_up(v, align.eval(env)?)
+            },
+            Expr::Max(ref a, ref b) => std::cmp::max(a.eval(env)?, b.eval(env)?),
+            Expr::Min(ref a, ref b) => std::cmp::min(a.eval(env)?, b.eval(env)?),
+            Expr::Origin(ref name) => match env.memory.iter().find(|r| &r.name == name) {
+                Some(r) => r.origin,
+                None => return Err(undefined("memory region", name)),
+            },
+            Expr::Length(ref name) => match env.memory.iter().find(|r| &r.name == name) {
+                Some(r) => r.length,
+                None => return Err(undefined("memory region", name)),
+            },
+            Expr::Addr(ref name) => match env.sections.get(name) {
+                Some(v) => v.0,
+                None => return Err(undefined("section", name)),
+            },
+            Expr::SizeOf(ref name) => match env.sections.get(name) {
+                Some(v) => v.1,
+                None => return Err(undefined("section", name)),
+            },
+        })
+    }
+
+    /// evaluate without anything but the location counter
+    pub fn eval_at(&self, dot: u64) -> Result<u64, Error> {
+        self.eval(&Env{
+            dot,
+            symbols:    &HashMap::new(),
+            sections:   &HashMap::new(),
+            memory:     &[],
+        })
+    }
+
+    /// does the value depend on the location counter
+    /// the symbols the expression refers to
+    pub fn symbols(&self) -> Vec<&[u8]> {
+        match *self {
+            Expr::Symbol(ref name) => vec![name],
+            Expr::Align(Some(ref a), ref b) |
+            Expr::Binary(_, ref a, ref b) |
+            Expr::Max(ref a, ref b) |
+            Expr::Min(ref a, ref b) => {
+                let mut v = a.symbols();
+                v.extend(b.symbols());
+                v
+            },
+            Expr::Align(None, ref e) | Expr::Neg(ref e) | Expr::Not(ref e) => e.symbols(),
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn uses_dot(&self) -> bool {
+        match *self {
+            Expr::Dot => true,
+            Expr::Align(None, _) => true,
+            Expr::Align(Some(ref a), ref b) |
+            Expr::Binary(_, ref a, ref b) |
+            Expr::Max(ref a, ref b) |
+            Expr::Min(ref a, ref b) => a.uses_dot() || b.uses_dot(),
+            Expr::Neg(ref e) | Expr::Not(ref e) => e.uses_dot(),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemoryRegion {
+    pub name:       Vec<u8>,
+    pub origin:     u64,
+    pub length:     u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Assignment {
+    /// `.` sets the location counter
+    pub symbol:     Vec<u8>,
+    pub expr:       Expr,
+    /// only define the symbol if nothing else does
+    pub provide:    bool,
+    pub hidden:     bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct InputSection {
+    pub file:       Vec<u8>,
+    /// section name patterns. empty matches all sections of the file
+    pub sections:   Vec<Vec<u8>>,
+    /// never garbage collect matching sections
+    pub keep:       bool,
+    /// place matching sections sorted by name
+    pub sort:       bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OutputCommand {
+    Assign(Assignment),
+    Input(InputSection),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputSection {
+    /// /DISCARD/ drops everything that goes in here
+    pub name:       Vec<u8>,
+    pub addr:       Option<Expr>,
+    pub align:      Option<Expr>,
+    pub region:     Option<Vec<u8>>,
+    /// allocated, but not loaded from the file
+    pub noload:     bool,
+    pub commands:   Vec<OutputCommand>,
+}
+
+impl OutputSection {
+    pub fn is_discard(&self) -> bool {
+        self.name == b"/DISCARD/"
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Assign(Assignment),
+    Output(OutputSection),
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Script {
+    pub entry:      Option<Vec<u8>>,
+    pub memory:     Vec<MemoryRegion>,
+    /// assignments outside of SECTIONS and the contents of SECTIONS, in order
+    pub commands:   Vec<Command>,
+}
+
+/// shell style wildcard match with `*`, `?` and `[...]`
+pub fn glob(pattern: &[u8], s: &[u8]) -> bool {
+    match pattern.first() {
+        None => s.is_empty(),
+        Some(&b'*') => (0..s.len() + 1).any(|i| glob(&pattern[1..], &s[i..])),
+        Some(&b'?') => !s.is_empty() && glob(&pattern[1..], &s[1..]),
+        Some(&b'[') => {
+            let end = match pattern.iter().position(|c| *c == b']') {
+                Some(end) => end,
+                None => return s.first() == Some(&b'[') && glob(&pattern[1..], &s[1..]),
+            };
+            let c = match s.first() {
+                Some(c) => *c,
+                None => return false,
+            };
+            let mut set = &pattern[1..end];
+            let negate = set.first() == Some(&b'!') || set.first() == Some(&b'^');
+            if negate {
+                set = &set[1..];
+            }
+            let mut found = false;
+            let mut i = 0;
+            while i < set.len() {
+                if i + 2 < set.len() && set[i + 1] == b'-' {
+                    found |= set[i] <= c && c <= set[i + 2];
+                    i += 3;
+                } else {
+                    found |= set[i] == c;
+                    i += 1;
+                }
+            }
+            found != negate && glob(&pattern[end + 1..], &s[1..])
+        },
+        Some(c) => s.first() == Some(c) && glob(&pattern[1..], &s[1..]),
+    }
+}
+
+impl InputSection {
+    /// file is the name of the object, with archive members as archive::member
+    pub fn matches(&self, file: &[u8], section: &[u8]) -> bool {
+        let member = match file.windows(2).rposition(|w| w == b"::") {
+            Some(i) => &file[i + 2..],
+            None => file,
+        };
+        if !glob(&self.file, file) && !glob(&self.file, member) {
+            return false;
+        }
+        self.sections.is_empty() || self.sections.iter().any(|p| glob(p, section))
+    }
+}
+
+impl Script {
+    pub fn parse(src: &[u8]) -> Result<Script, Error> {
+        let mut p = Parser{src, pos: 0, line: 1};
+        let mut script = Script::default();
+        loop {
+            p.skip_ws();
+            if p.pos >= p.src.len() {
+                break;
+            }
+            let word = p.name()?;
+            match word.as_slice() {
+                b"ENTRY" => {
+                    script.entry = Some(p.parenthesized_name()?);
+                },
+                b"OUTPUT_FORMAT" | b"OUTPUT_ARCH" => {
+                    // there is only one output format
+                    p.expect(b'(')?;
+                    while p.pos < p.src.len() && p.src[p.pos] != b')' {
+                        p.pos += 1;
+                    }
+                    p.expect(b')')?;
+                },
+                b"MEMORY" => {
+                    p.expect(b'{')?;
+                    while !p.eat(b'}') {
+                        script.memory.push(p.memory_region()?);
+                    }
+                },
+                b"SECTIONS" => {
+                    p.expect(b'{')?;
+                    while !p.eat(b'}') {
+                        let word = p.name()?;
+                        match word.as_slice() {
+                            b"ENTRY" => {
+                                script.entry = Some(p.parenthesized_name()?);
+                            },
+                            b"PROVIDE" | b"PROVIDE_HIDDEN" => {
+                                script.commands.push(Command::Assign(p.provide(&word)?));
+                            },
+                            _ if p.at_assignment() => {
+                                script.commands.push(Command::Assign(p.assignment(word)?));
+                            },
+                            _ => {
+                                script.commands.push(Command::Output(p.output_section(word)?));
+                            },
+                        }
+                    }
+                },
+                b"PROVIDE" | b"PROVIDE_HIDDEN" => {
+                    script.commands.push(Command::Assign(p.provide(&word)?));
+                },
+                _ if p.at_assignment() => {
+                    script.commands.push(Command::Assign(p.assignment(word)?));
+                },
+                _ => {
+                    return Err(p.error(format!("unsupported command {}", String::from_utf8_lossy(&word))));
+                },
+            }
+        }
+        Ok(script)
+    }
+
+    /// where an input section goes: the index of the output section in commands,
+    /// and the index of the first input section description matching it
+    pub fn input_section(&self, file: &[u8], section: &[u8]) -> Option<(usize, usize)> {
+        for (i, cmd) in self.commands.iter().enumerate() {
+            if let Command::Output(ref out) = *cmd {
+                for (j, cmd) in out.commands.iter().enumerate() {
+                    if let OutputCommand::Input(ref input) = *cmd {
+                        if input.matches(file, section) {
+                            return Some((i, j));
+                        }
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// is the input section protected from garbage collection by KEEP
+    pub fn keep(&self, file: &[u8], section: &[u8]) -> bool {
+        match self.input_section(file, section) {
+            Some((i, j)) => match self.commands[i] {
+                Command::Output(ref out) => match out.commands[j] {
+                    OutputCommand::Input(ref input) => input.keep,
+                    _ => false,
+                },
+                _ => false,
+            },
+            None => false,
+        }
+    }
+
+    /// all symbol assignments, wherever they are
+    pub fn assignments(&self) -> Vec<&Assignment> {
+        let mut r = Vec::new();
+        for cmd in &self.commands {
+            match *cmd {
+                Command::Assign(ref a) => r.push(a),
+                Command::Output(ref out) => {
+                    for cmd in &out.commands {
+                        if let OutputCommand::Assign(ref a) = *cmd {
+                            r.push(a);
+                        }
+                    }
+                },
+            }
+        }
+        r.into_iter().filter(|a| a.symbol != b".").collect()
+    }
+
+    /// the values of the assignments outside of output sections that don't depend on the layout
+    pub fn constants(&self) -> HashMap<Vec<u8>, u64> {
+        let mut symbols = HashMap::new();
+        for cmd in &self.commands {
+            if let Command::Assign(ref a) = *cmd {
+                if a.symbol == b"." || a.expr.uses_dot() {
+                    continue;
+                }
+                let v = a.expr.eval(&Env{
+                    dot:        0,
+                    symbols:    &symbols,
+                    sections:   &HashMap::new(),
+                    memory:     &self.memory,
+                });
+                if let Ok(v) = v {
+                    symbols.insert(a.symbol.clone(), v);
+                }
+            }
+        }
+        symbols
+    }
+
+    /// decide the addresses of the output sections in elf and evaluate the assignments outside of them.
+    /// the addresses end up in elf.section_start, so layout puts the sections there.
+    /// assignments inside of output sections that use `.` are not evaluated here, since they are
+    /// relative to the section and are resolved while merging.
+    /// returns the values of all other assigned symbols
+    pub fn place(&self, elf: &mut Elf) -> Result<HashMap<Vec<u8>, u64>, Error> {
+        let mut symbols  = HashMap::new();
+        let mut sections = HashMap::new();
+        let mut cursors : Vec<u64> = self.memory.iter().map(|r| r.origin).collect();
+        let mut dot = 0;
+
+        for cmd in &self.commands {
+            match *cmd {
+                Command::Assign(ref a) => {
+                    let v = a.expr.eval(&Env{
+                        dot,
+                        symbols:    &symbols,
+                        sections:   &sections,
+                        memory:     &self.memory,
+                    })?;
+                    if a.symbol == b"." {
+                        dot = v;
+                    } else {
+                        symbols.insert(a.symbol.clone(), v);
+                    }
+                },
+                Command::Output(ref out) => {
+                    if out.is_discard() {
+                        continue;
+                    }
+                    let sec = match elf.sections.iter().find(|s| s.name == out.name) {
+                        Some(sec) => sec,
+                        // nothing went in here
+                        None => continue,
+                    };
+                    let size = if sec.header.shtype == types::SectionType::NOBITS {
+                        sec.header.size
+                    } else {
+                        sec.size(&elf.header) as u64
+                    };
+
+                    let region = match out.region {
+                        Some(ref name) => match self.memory.iter().position(|r| &r.name == name) {
+                            Some(i) => Some(i),
+                            None => return Err(Error::LinkerScriptUndefined(
+                                    format!("memory region {}", String::from_utf8_lossy(name)))),
+                        },
+                        None => None,
+                    };
+
+                    let env = Env{
+                        dot,
+                        symbols:    &symbols,
+                        sections:   &sections,
+                        memory:     &self.memory,
+                    };
+                    let mut addr = match (&out.addr, region) {
+                        (Some(e), _) => e.eval(&env)?,
+                        (&None, Some(i)) => cursors[i],
+                        (&None, None) => dot,
+                    };
+                    if let Some(ref align) = out.align {
+                        addr = align_up(addr, align.eval(&env)?);
+                    }
+                    addr = align_up(addr, sec.header.addralign);
+
+                    if let Some(i) = region {
+                        let end = self.memory[i].origin + self.memory[i].length;
+                        if addr + size > end {
+                            return Err(Error::RegionOverflow{
+                                region: String::from_utf8_lossy(&self.memory[i].name).into_owned(),
+                                sec:    String::from_utf8_lossy(&out.name).into_owned(),
+                                by:     addr + size - end,
+                            });
+                        }
+                        cursors[i] = addr + size;
+                    }
+
+                    dot = addr + size;
+                    sections.insert(out.name.clone(), (addr, size));
+                    elf.section_start.insert(out.name.clone(), addr);
+
+                    for cmd in &out.commands {
+                        if let OutputCommand::Assign(ref a) = *cmd {
+                            if a.symbol == b"." || a.expr.uses_dot() {
+                                continue;
+                            }
+                            let v = a.expr.eval(&Env{
+                                dot,
+                                symbols:    &symbols,
+                                sections:   &sections,
+                                memory:     &self.memory,
+                            })?;
+                            symbols.insert(a.symbol.clone(), v);
+                        }
+                    }
+                },
+            }
+        }
+        Ok(symbols)
+    }
+}
+
+
+struct Parser<'a> {
+    src:    &'a [u8],
+    pos:    usize,
+    line:   usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, msg: String) -> Error {
+        Error::LinkerScriptSyntax{line: self.line, msg}
+    }
+
+    fn skip_ws(&mut self) {
+        while self.pos < self.src.len() {
+            match self.src[self.pos] {
+                b'\n' => {
+                    self.line += 1;
+                    self.pos += 1;
+                },
+                b' ' | b'\t' | b'\r' => {
+                    self.pos += 1;
+                },
+                b'/' if self.src[self.pos..].starts_with(b"/*") => {
+                    self.pos += 2;
+                    while self.pos < self.src.len() && !self.src[self.pos..].starts_with(b"*/") {
+                        if self.src[self.pos] == b'\n' {
+                            self.line += 1;
+                        }
+                        self.pos += 1;
+                    }
+                    self.pos += 2;
+                },
+                _ => break,
+            }
+        }
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        self.skip_ws();
+        self.src.get(self.pos).cloned()
+    }
+
+    fn eat(&mut self, c: u8) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: u8) -> Result<(), Error> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            Err(self.error(format!("expected '{}'", c as char)))
+        }
+    }
+
+    /// symbols, section names, file name patterns and keywords
+    fn name(&mut self) -> Result<Vec<u8>, Error> {
+        self.skip_ws();
+        let start = self.pos;
+        while self.pos < self.src.len() {
+            match self.src[self.pos] {
+                b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' |
+                b'_' | b'.' | b'$' | b'/' | b'*' | b'?' | b'[' | b']' | b'-' | b'~' | b'!' | b'^' => {
+                    self.pos += 1;
+                },
+                _ => break,
+            }
+        }
+        if start == self.pos {
+            return Err(self.error(String::from("expected a name")));
+        }
+        Ok(self.src[start..self.pos].to_vec())
+    }
+
+    fn parenthesized_name(&mut self) -> Result<Vec<u8>, Error> {
+        self.expect(b'(')?;
+        let name = self.name()?;
+        self.expect(b')')?;
+        Ok(name)
+    }
+
+    fn at_assignment(&mut self) -> bool {
+        match self.peek() {
+            Some(b'=') => !self.src[self.pos..].starts_with(b"=="),
+            Some(b'+') | Some(b'-') | Some(b'&') | Some(b'|') | Some(b'*') | Some(b'/') => {
+                self.src.get(self.pos + 1) == Some(&b'=')
+            },
+            _ => false,
+        }
+    }
+
+    /// sym = expr; with the name already read
+    fn assignment(&mut self, symbol: Vec<u8>) -> Result<Assignment, Error> {
+        let a = self.assignment_expr(symbol)?;
+        if !self.eat(b';') && self.peek() != Some(b'}') {
+            return Err(self.error(String::from("expected ';' after assignment")));
+        }
+        Ok(a)
+    }
+
+    fn assignment_expr(&mut self, symbol: Vec<u8>) -> Result<Assignment, Error> {
+        self.skip_ws();
+        let op = match self.src[self.pos] {
+            b'=' => None,
+            b'+' => Some(Op::Add),
+            b'-' => Some(Op::Sub),
+            b'&' => Some(Op::And),
+            b'|' => Some(Op::Or),
+            b'*' => Some(Op::Mul),
+            b'/' => Some(Op::Div),
+            _ => return Err(self.error(String::from("expected an assignment"))),
+        };
+        self.pos += if op.is_some() { 2 } else { 1 };
+
+        let mut expr = self.expr()?;
+        if let Some(op) = op {
+            let current = if symbol == b"." { Expr::Dot } else { Expr::Symbol(symbol.clone()) };
+            expr = Expr::Binary(op, Box::new(current), Box::new(expr));
+        }
+        Ok(Assignment{
+            symbol,
+            expr,
+            provide:    false,
+            hidden:     false,
+        })
+    }
+
+    /// PROVIDE(sym = expr) with the keyword already read
+    fn provide(&mut self, keyword: &[u8]) -> Result<Assignment, Error> {
+        self.expect(b'(')?;
+        let symbol = self.name()?;
+        let mut a = self.assignment_expr(symbol)?;
+        self.expect(b')')?;
+        self.eat(b';');
+        a.provide = true;
+        a.hidden  = keyword == b"PROVIDE_HIDDEN";
+        Ok(a)
+    }
+
+    fn memory_region(&mut self) -> Result<MemoryRegion, Error> {
+        let name = self.name()?;
+        // attributes only matter for placing orphans, which isn't done by region
+        if self.eat(b'(') {
+            while !self.eat(b')') {
+                if self.pos >= self.src.len() {
+                    return Err(self.error(String::from("unterminated memory attributes")));
+                }
+                self.pos += 1;
+            }
+        }
+        self.expect(b':')?;
+
+        let mut origin = None;
+        let mut length = None;
+        for i in 0..2 {
+            if i > 0 {
+                self.eat(b',');
+            }
+            let key = self.name()?;
+            self.expect(b'=')?;
+            let v = self.expr()?.eval_at(0)?;
+            match key.as_slice() {
+                b"ORIGIN" | b"org" | b"o" => origin = Some(v),
+                b"LENGTH" | b"len" | b"l" => length = Some(v),
+                _ => return Err(self.error(format!("unexpected {} in memory region",
+                                                   String::from_utf8_lossy(&key)))),
+            }
+        }
+        match (origin, length) {
+            (Some(origin), Some(length)) => Ok(MemoryRegion{
+                name,
+                origin,
+                length,
+            }),
+            _ => Err(self.error(String::from("memory region needs ORIGIN and LENGTH"))),
+        }
+    }
+
+    /// name [addr] [(NOLOAD)] : [ALIGN(align)] { ... } [>region]
+    fn output_section(&mut self, name: Vec<u8>) -> Result<OutputSection, Error> {
+        let mut out = OutputSection{
+            name,
+            addr:       None,
+            align:      None,
+            region:     None,
+            noload:     false,
+            commands:   Vec::new(),
+        };
+
+        if self.peek() == Some(b'(') && self.src[self.pos + 1..].iter()
+            .skip_while(|c| **c == b' ').take(6).eq(b"NOLOAD".iter()) {
+            self.pos += 1;
+            self.name()?;
+            self.expect(b')')?;
+            out.noload = true;
+        } else if self.peek() != Some(b':') {
+            out.addr = Some(self.expr()?);
+            if self.eat(b'(') {
+                if self.name()? != b"NOLOAD" {
+                    return Err(self.error(String::from("unsupported output section type")));
+                }
+                self.expect(b')')?;
+                out.noload = true;
+            }
+        }
+        self.expect(b':')?;
+
+        if self.peek() != Some(b'{') {
+            let word = self.name()?;
+            if word != b"ALIGN" {
+                return Err(self.error(format!("unsupported {} in output section {}",
+                                              String::from_utf8_lossy(&word),
+                                              String::from_utf8_lossy(&out.name))));
+            }
+            self.expect(b'(')?;
+            out.align = Some(self.expr()?);
+            self.expect(b')')?;
+        }
+
+        self.expect(b'{')?;
+        while !self.eat(b'}') {
+            let word = self.name()?;
+            match word.as_slice() {
+                b"PROVIDE" | b"PROVIDE_HIDDEN" => {
+                    out.commands.push(OutputCommand::Assign(self.provide(&word)?));
+                },
+                b"KEEP" => {
+                    self.expect(b'(')?;
+                    let file = self.name()?;
+                    let mut input = self.input_section(file)?;
+                    input.keep = true;
+                    self.expect(b')')?;
+                    out.commands.push(OutputCommand::Input(input));
+                },
+                _ if self.at_assignment() => {
+                    out.commands.push(OutputCommand::Assign(self.assignment(word)?));
+                },
+                _ => {
+                    out.commands.push(OutputCommand::Input(self.input_section(word)?));
+                },
+            }
+            self.eat(b';');
+        }
+
+        if self.eat(b'>') {
+            out.region = Some(self.name()?);
+        }
+        if self.peek() == Some(b'A') {
+            return Err(self.error(String::from("AT is not supported")));
+        }
+        Ok(out)
+    }
+
+    /// file(patterns) with the file pattern already read
+    fn input_section(&mut self, file: Vec<u8>) -> Result<InputSection, Error> {
+        let mut input = InputSection{
+            file,
+            sections:   Vec::new(),
+            keep:       false,
+            sort:       false,
+        };
+        if !self.eat(b'(') {
+            return Ok(input);
+        }
+        while !self.eat(b')') {
+            let pattern = self.name()?;
+            match pattern.as_slice() {
+                b"SORT" | b"SORT_BY_NAME" => {
+                    self.expect(b'(')?;
+                    while !self.eat(b')') {
+                        input.sections.push(self.name()?);
+                    }
+                    input.sort = true;
+                },
+                b"EXCLUDE_FILE" | b"SORT_BY_ALIGNMENT" | b"SORT_BY_INIT_PRIORITY" => {
+                    return Err(self.error(format!("{} is not supported", String::from_utf8_lossy(&pattern))));
+                },
+                _ => input.sections.push(pattern),
+            }
+            self.eat(b',');
+        }
+        Ok(input)
+    }
+
+    fn expr(&mut self) -> Result<Expr, Error> {
+        self.binary(0)
+    }
+
+    /// precedence climbing, from | down to * / %
+    fn binary(&mut self, level: usize) -> Result<Expr, Error> {
+        const LEVELS : &[&[(&[u8], Op)]] = &[
+            &[(b"|", Op::Or)],
+            &[(b"&", Op::And)],
+            &[(b"<<", Op::Shl), (b">>", Op::Shr)],
+            &[(b"+", Op::Add), (b"-", Op::Sub)],
+            &[(b"*", Op::Mul), (b"/", Op::Div), (b"%", Op::Mod)],
+        ];
+        if level >= LEVELS.len() {
+            return self.unary();
+        }
+        let mut lhs = self.binary(level + 1)?;
+        'outer: loop {
+            self.skip_ws();
+            for &(tok, ref op) in LEVELS[level] {
+                let rest = &self.src[self.pos..];
+                // the assignment operators belong to the statement, || && and comments aren't ours
+                if rest.starts_with(tok) && rest.get(tok.len()) != Some(&b'=') &&
+                    !(tok.len() == 1 && rest.get(1) == Some(&tok[0])) &&
+                    !rest.starts_with(b"/*") {
+                    self.pos += tok.len();
+                    let rhs = self.binary(level + 1)?;
+                    lhs = Expr::Binary(op.clone(), Box::new(lhs), Box::new(rhs));
+                    continue 'outer;
+                }
+            }
+            return Ok(lhs);
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, Error> {
+        match self.peek() {
+            Some(b'-') => {
+                self.pos += 1;
+                Ok(Expr::Neg(Box::new(self.unary()?)))
+            },
+            Some(b'~') => {
+                self.pos += 1;
+                Ok(Expr::Not(Box::new(self.unary()?)))
+            },
+            Some(b'(') => {
+                self.pos += 1;
+                let e = self.expr()?;
+                self.expect(b')')?;
+                Ok(e)
+            },
+            Some(b'0'..=b'9') => self.number(),
+            Some(_) => self.primary(),
+            None => Err(self.error(String::from("unexpected end of script"))),
+        }
+    }
+
+    fn number(&mut self) -> Result<Expr, Error> {
+        let start = self.pos;
+        while self.pos < self.src.len() && (self.src[self.pos] as char).is_ascii_alphanumeric() {
+            self.pos += 1;
+        }
+        let mut s = String::from_utf8_lossy(&self.src[start..self.pos]).into_owned();
+        let mul = if s.ends_with('K') || s.ends_with('k') {
+            1024
+        } else if s.ends_with('M') || s.ends_with('m') {
+            1024 * 1024
+        } else {
+            1
+        };
+        if mul > 1 {
+            s.pop();
+        }
+        let v = if s.starts_with("0x") || s.starts_with("0X") {
+            u64::from_str_radix(&s[2..], 16)
+        } else if s.len() > 1 && s.starts_with('0') {
+            u64::from_str_radix(&s[1..], 8)
+        } else {
+            s.parse()
+        };
+        match v {
+            Ok(v) => Ok(Expr::Number(v * mul)),
+            Err(_) => Err(self.error(format!("invalid number {}", s))),
+        }
+    }
+
+    /// `.`, symbols and function calls
+    fn primary(&mut self) -> Result<Expr, Error> {
+        let start = self.pos;
+        while self.pos < self.src.len() {
+            match self.src[self.pos] {
+                b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'_' | b'.' | b'$' => self.pos += 1,
+                _ => break,
+            }
+        }
+        if start == self.pos {
+            return Err(self.error(format!("unexpected '{}' in expression", self.src[self.pos] as char)));
+        }
+        let word = self.src[start..self.pos].to_vec();
+        if word == b"." {
+            return Ok(Expr::Dot);
+        }
+        if self.peek() != Some(b'(') {
+            return Ok(Expr::Symbol(word));
+        }
+
+        match word.as_slice() {
+            b"ALIGN" => {
+                self.expect(b'(')?;
+                let a = self.expr()?;
+                let e = if self.eat(b',') {
+                    let b = self.expr()?;
+                    Expr::Align(Some(Box::new(a)), Box::new(b))
+                } else {
+                    Expr::Align(None, Box::new(a))
+                };
+                self.expect(b')')?;
+                Ok(e)
+            },
+            b"MAX" | b"MIN" => {
+                self.expect(b'(')?;
+                let a = Box::new(self.expr()?);
+                self.expect(b',')?;
+                let b = Box::new(self.expr()?);
+                self.expect(b')')?;
+                Ok(if word == b"MAX" { Expr::Max(a, b) } else { Expr::Min(a, b) })
+            },
+            b"ORIGIN" => Ok(Expr::Origin(self.parenthesized_name()?)),
+            b"LENGTH" => Ok(Expr::Length(self.parenthesized_name()?)),
+            b"ADDR"   => Ok(Expr::Addr(self.parenthesized_name()?)),
+            b"SIZEOF" => Ok(Expr::SizeOf(self.parenthesized_name()?)),
+            _ => Err(self.error(format!("unsupported function {}", String::from_utf8_lossy(&word)))),
+        }
+    }
+}
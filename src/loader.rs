@@ -4,13 +4,13 @@ extern crate fnv;
 extern crate core;
 extern crate rayon;
 
-use {types, Header, Elf, Error, symbol, filetype, relocation, section};
+use {types, Header, ElfRef, Error, symbol, filetype, relocation, section};
+use elfref::SectionRef;
 use std;
 use std::io::{Read, Seek, Cursor};
 use std::hash::{Hash,Hasher};
-use std::fs::{File};
+use std::fs::{self, File};
 use std::collections::HashMap;
-use std::cell::RefCell;
 use self::rayon::prelude::*;
 use self::fnv::FnvHasher;
 use self::bit_vec::BitVec;
@@ -32,11 +32,12 @@ pub enum State {
         name:    String,
         archive: Archive<File>,
     },
+    /// an elf file of which only the symbols have been copied out so far
     Elf{
         hash:    String,
         name:    String,
-        elf:     Elf,
-        read:    RefCell<Box<ReadSeekSend>>,
+        header:  Header,
+        data:    Vec<u8>,
         bloom:   BloomFilter,
         symbols: Vec<symbol::Symbol>,
     },
@@ -47,6 +48,15 @@ pub enum State {
         header:   Header,
         sections: Vec<(usize, section::Section, Vec<relocation::Relocation>)>,
     },
+    /// a shared object. only its exported symbols are linked against,
+    /// the rest is resolved at runtime
+    Shared{
+        hash:     String,
+        name:     String,
+        /// DT_SONAME, or the name it was loaded from if it has none
+        soname:   Vec<u8>,
+        symbols:  Vec<symbol::Symbol>,
+    },
 }
 
 pub trait Loader {
@@ -54,6 +64,9 @@ pub trait Loader {
         where E: Fn(Error, String) -> Vec<State> + Sync;
     fn load_if<E>(self, needles: &Vec<&[u8]>, e: &E) ->  (Vec<State>,Vec<State>)
         where E: Fn(Error, String) -> Vec<State> + Sync;
+    /// load the files themselves, but leave the members of archives until they are needed
+    fn load_objects<E>(self, e: &E) ->  Vec<State>
+        where E: Fn(Error, String) -> Vec<State> + Sync;
 }
 
 impl Loader for Vec<State> {
@@ -71,7 +84,21 @@ impl Loader for Vec<State> {
         where E: Fn(Error, String) -> Vec<State> + Sync
     {
         self.into_par_iter().flat_map(|l| l.load_if(needles, e))
-            .partition(|o| if let &State::Object{..} = o {false} else {true})
+            .partition(|o| !matches!(o, State::Object{..} | State::Shared{..}))
+    }
+    fn load_objects<E>(self, e: &E) ->  Vec<State>
+        where E: Fn(Error, String) -> Vec<State> + Sync
+    {
+        self.into_par_iter()
+            .flat_map(|l| match l {
+                State::Path{..} => l.load(e),
+                any => vec![any],
+            })
+            .flat_map(|l| match l {
+                State::Elf{..} => l.load(e),
+                any => vec![any],
+            })
+            .collect()
     }
 }
 
@@ -103,6 +130,8 @@ impl State {
                 }
                 return false;
             },
+            // every shared object that is linked against ends up in DT_NEEDED
+            &mut State::Elf{ref header, ..} if header.etype == types::ElfType::DYN => true,
             &mut State::Elf{ref bloom, ref symbols, ..} => {
                 if bloom.contains(&needle_hash) {
                     for sym in symbols.iter() {
@@ -125,6 +154,7 @@ impl State {
                 return false;
             },
             &mut State::Object{..} => false,
+            &mut State::Shared{..} => false,
         }
     }
 
@@ -147,7 +177,8 @@ impl State {
                         }];
                     },
                     Ok(filetype::FileType::Elf) => {
-                        vec![match State::make_object(name.clone(), RefCell::new(Box::new(f))) {
+                        vec![match fs::read(&name).map_err(Error::from)
+                             .and_then(|data| State::make_object(name.clone(), data)) {
                             Err(e) => State::Error{
                                 error: e,
                                 name:  name,
@@ -190,118 +221,172 @@ impl State {
                 }
                 r
             },
-            State::Elf{name, hash, mut elf, read, symbols, ..} => {
-                if let Err(e) = elf.load_all(&mut *read.borrow_mut()) {
-                    return vec![State::Error{
-                        name:   name,
-                        error:  e,
-                    }]
-                }
-
-                let mut relocs : HashMap<usize, Vec<relocation::Relocation>> = HashMap::new();
-                for sec in elf.sections.iter_mut() {
-                    if sec.header.shtype == types::SectionType::RELA {
-                        relocs.insert(sec.header.info as usize,
-                                    std::mem::replace(sec, section::Section::default())
-                                    .content.into_relocations().unwrap()
-                                    );
-                    }
-                }
-
-                let mut sections = Vec::new();
-                for (i, sec) in elf.sections.into_iter().enumerate() {
-                    match sec.header.shtype {
-                        types::SectionType::NULL | types::SectionType::STRTAB => {},
-                        _ => {
-                            sections.push((i, sec, relocs.remove(&i).unwrap_or_else(||Vec::new())));
-                        },
-                    }
-                }
-
-                vec![State::Object{
-                    hash:       hash,
-                    name:       name,
-                    symbols:    symbols,
-                    header:     elf.header,
-                    sections:   sections,
+            State::Elf{name, hash, header, data, symbols, ..} if header.etype == types::ElfType::DYN => {
+                vec![match State::make_shared(name.clone(), hash, &data, symbols) {
+                    Err(e) => State::Error{
+                        error: e,
+                        name,
+                    },
+                    Ok(v) => v,
+                }]
+            },
+            State::Elf{name, hash, data, symbols, ..} => {
+                vec![match State::make_sections(name.clone(), hash, &data, symbols) {
+                    Err(e) => State::Error{
+                        error: e,
+                        name,
+                    },
+                    Ok(v) => v,
                 }]
             },
             any => vec![any],
         }
     }
 
-    fn make_object(name: String, io: RefCell<Box<ReadSeekSend>>) -> Result<State, Error> {
-
-        let mut elf = Elf::from_reader(&mut *io.borrow_mut())?;
+    fn make_object(name: String, data: Vec<u8>) -> Result<State, Error> {
+        let (header, symbols, bloom, hash) = {
+            let elf = ElfRef::from_bytes(&data)?;
+
+            let mut hasher = DefaultHasher::new();
+            let mut num_symbols = 0;
+            let mut symtab = None;
+
+            for sec in elf.sections() {
+                let sec = sec?;
+                match sec.header.shtype {
+                    types::SectionType::SYMTAB |
+                        types::SectionType::DYNSYM => {
+                            for sym in elf.symbols(&sec)? {
+                                hasher.write(sym?.name);
+                                num_symbols += 1;
+                            }
+                            if (sec.header.shtype == types::SectionType::SYMTAB &&
+                                elf.header.etype == types::ElfType::REL) ||
+                               (sec.header.shtype == types::SectionType::DYNSYM &&
+                                elf.header.etype == types::ElfType::DYN) {
+                                if symtab.is_some() {
+                                    return Err(Error::MultipleSymbolSections);
+                                }
+                                symtab = Some(sec);
+                            }
+                        },
+                    _ => {}
+                }
+            }
 
-        let mut hasher = DefaultHasher::new();
-        let mut num_symbols = 0;
+            if num_symbols == 0 {
+                return Err(Error::NoSymbolsInObject);
+            }
 
-        for i in 0..elf.sections.len() {
-            match elf.sections[i].header.shtype {
-                types::SectionType::SYMTAB |
-                    types::SectionType::DYNSYM => {
-                        elf.load(i, &mut *io.borrow_mut())?;
-                        let symbols = elf.sections[i].content.as_symbols().unwrap();
-                        for sym in symbols {
-                            hasher.write(&sym.name);
+            let symtab = match symtab {
+                Some(v) => v,
+                None => return Err(Error::MissingSymtabSection),
+            };
+
+            let mut bloom = BloomFilter::new(num_symbols);
+            let mut symbols = Vec::new();
+            for sym in elf.symbols(&symtab)? {
+                let sym = sym?;
+                match sym.bind {
+                    types::SymbolBind::GLOBAL | types::SymbolBind::WEAK => {
+                        match sym.shndx {
+                            symbol::SymbolSectionIndex::Undefined |
+                                symbol::SymbolSectionIndex::Absolute  => {},
+                            _ => {
+                                bloom.insert(&BloomFilter::hash(sym.name));
+                            },
                         }
-                        num_symbols += symbols.len();
                     },
-                _ => {}
+                    _ => {},
+                }
+                symbols.push(sym.to_owned());
             }
-        }
 
-        if num_symbols == 0 {
-            return Err(Error::NoSymbolsInObject);
+            (elf.header, symbols, bloom, hasher.finish())
+        };
+
+        Ok(State::Elf{
+            hash:    format!("{}>>{:x}!", name.split("::").last().unwrap(), hash),
+            name,
+            header,
+            data,
+            bloom,
+            symbols,
+        })
+    }
+
+    /// copy out the sections of a relocatable object with the relocations that apply to them.
+    /// the relocation sections, string tables and the symbol table aren't linked themselves
+    fn make_sections(name: String, hash: String, data: &[u8],
+                     symbols: Vec<symbol::Symbol>) -> Result<State, Error> {
+        let elf = ElfRef::from_bytes(data)?;
+
+        // REL keeps the addend in the target section, which the view already fetched.
+        // so the linker only has to deal with explicit addends
+        let mut relocs : HashMap<usize, Vec<relocation::Relocation>> = HashMap::new();
+        for sec in elf.sections() {
+            let sec = sec?;
+            if sec.header.shtype == types::SectionType::RELA ||
+                sec.header.shtype == types::SectionType::REL {
+                relocs.insert(sec.header.info as usize,
+                              elf.relocations(&sec)?.collect::<Result<Vec<_>, Error>>()?);
+            }
         }
 
-        let mut bloom = BloomFilter::new(num_symbols);
-
-        let mut symbols = None;
-        for i in 0..elf.sections.len() {
-            if (elf.sections[i].header.shtype == types::SectionType::SYMTAB &&
-               elf.header.etype == types::ElfType::REL) ||
-               (elf.sections[i].header.shtype == types::SectionType::DYNSYM &&
-               elf.header.etype == types::ElfType::DYN) {
-
-               if let Some(_) = symbols {
-                   return Err(Error::MultipleSymbolSections);
-               }
-
-               let syms = std::mem::replace(&mut elf.sections[i], section::Section::default())
-                   .content.into_symbols().unwrap();
-
-               for sym in syms.iter() {
-                   match sym.bind {
-                       types::SymbolBind::GLOBAL | types::SymbolBind::WEAK => {
-                           match sym.shndx {
-                               symbol::SymbolSectionIndex::Undefined |
-                                   symbol::SymbolSectionIndex::Absolute  => {},
-                               _ => {
-                                   bloom.insert(&BloomFilter::hash(&sym.name));
-                               },
-                           }
-                       },
-                       _ => {},
-
-                   }
-               }
-               symbols = Some(syms);
+        let mut sections = Vec::new();
+        for sec in elf.sections() {
+            let sec = sec?;
+            match sec.header.shtype {
+                types::SectionType::NULL | types::SectionType::STRTAB |
+                    types::SectionType::RELA | types::SectionType::REL |
+                    types::SectionType::SYMTAB => {},
+                _ => {
+                    let relocs = relocs.remove(&sec.index).unwrap_or_default();
+                    sections.push((sec.index, owned_section(&elf, &sec)?, relocs));
+                },
             }
         }
 
-        if let None = symbols {
-            return Err(Error::MissingSymtabSection);
+        Ok(State::Object{
+            hash,
+            name,
+            symbols,
+            header:     elf.header,
+            sections,
+        })
+    }
+
+    fn make_shared(name: String, hash: String, data: &[u8],
+                   symbols: Vec<symbol::Symbol>) -> Result<State, Error> {
+        let elf = ElfRef::from_bytes(data)?;
+
+        let mut soname = None;
+        for sec in elf.sections() {
+            let sec = sec?;
+            if sec.header.shtype == types::SectionType::DYNAMIC {
+                for dynamic in elf.dynamic(&sec)? {
+                    let dynamic = dynamic?;
+                    if dynamic.dhtype == types::DynamicType::SONAME {
+                        soname = dynamic.string.map(|s| s.to_vec());
+                    }
+                }
+            }
         }
 
-        Ok(State::Elf{
-            hash:    format!("{}>>{:x}!", name.split("::").last().unwrap(), hasher.finish()),
-            name:    name,
-            elf:     elf,
-            read:    io,
-            bloom:   bloom,
-            symbols: symbols.unwrap(),
+        let symbols = symbols.into_iter().filter(|sym| {
+            match sym.bind {
+                types::SymbolBind::GLOBAL | types::SymbolBind::WEAK => {
+                    sym.shndx != symbol::SymbolSectionIndex::Undefined
+                },
+                _ => false,
+            }
+        }).collect();
+
+        Ok(State::Shared{
+            hash,
+            soname:  soname.unwrap_or_else(|| name.clone().into_bytes()),
+            name,
+            symbols,
         })
     }
 
@@ -309,14 +394,34 @@ impl State {
         let mut entry = entry?;
         let mut buf = Vec::with_capacity(entry.header().size() as usize);
         entry.read_to_end(&mut buf)?;
-        let io = Cursor::new(buf);
-
-        State::make_object(name,
-        RefCell::new(Box::new(io)))
+        State::make_object(name, buf)
     }
 
 }
 
+/// an owned copy of a section, decompressed and parsed like Elf::load does it
+fn owned_section(elf: &ElfRef, sec: &SectionRef) -> Result<section::Section, Error> {
+    let load = |sec: &SectionRef, linked: Option<&section::Section>| {
+        let mut r = section::Section{
+            name:     sec.name.to_vec(),
+            header:   sec.header.clone(),
+            content:  section::SectionContent::Unloaded,
+            addrlock: false,
+        };
+        r.from_reader_decompressed(Cursor::new(elf.data()), linked, &elf.header).map(|_| r)
+    };
+
+    let linked = match sec.header.link {
+        0 => None,
+        link => Some(elf.section(link as usize)?),
+    };
+    let linked = match linked {
+        Some(ref l) if l.header.shtype == types::SectionType::STRTAB => Some(load(l, None)?),
+        _ => None,
+    };
+    load(sec, linked.as_ref())
+}
+
 
 
 pub struct BloomFilter {
@@ -0,0 +1,113 @@
+//! SHF_MERGE sections hold entries of entsize bytes, or with SHF_STRINGS nul terminated strings of
+//! entsize wide characters, that may be merged with the same entries from other sections.
+//! Nothing may depend on where an entry is, other than through a relocation or symbol.
+use std::collections::hash_map::{self, HashMap};
+
+/// the entries of a set of mergeable input sections, each of them only once
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Merged {
+    pub content:    Vec<u8>,
+    /// for each input, where its entries start in the input and where they ended up in content
+    pub offsets:    Vec<Vec<(u64, u64)>>,
+}
+
+impl Merged {
+    /// where an offset into an input ended up. offsets inside an entry stay inside it,
+    /// offsets before the first entry are relative to it
+    pub fn offset(&self, input: usize, off: i64) -> i64 {
+        let entries = &self.offsets[input];
+        let i = match entries.binary_search_by_key(&off, |&(i, _)| i as i64) {
+            Ok(i)  => i,
+            Err(0) => 0,
+            Err(i) => i - 1,
+        };
+        match entries.get(i) {
+            Some(&(i, o)) => o as i64 + off - i as i64,
+            None => off,
+        }
+    }
+}
+
+/// split a mergeable section into its entries
+fn entries(content: &[u8], entsize: usize, strings: bool) -> Vec<&[u8]> {
+    let mut entries = Vec::new();
+    let mut start = 0;
+    let mut at = 0;
+    while at + entsize <= content.len() {
+        at += entsize;
+        if !strings || content[at - entsize..at].iter().all(|b| *b == 0) {
+            entries.push(&content[start..at]);
+            start = at;
+        }
+    }
+    if start < content.len() {
+        entries.push(&content[start..]);
+    }
+    entries
+}
+
+/// merge the inputs, removing duplicate entries. strings that end another string are
+/// stored as part of that one
+pub fn merge(inputs: &[&[u8]], entsize: usize, strings: bool) -> Merged {
+    let entsize = if entsize == 0 { 1 } else { entsize };
+
+    // the distinct entries in the order they first appear, and which one every input entry is
+    let mut unique : Vec<&[u8]> = Vec::new();
+    let mut ids    = HashMap::new();
+    let mut inputs : Vec<Vec<(u64, usize)>> = inputs.iter().map(|content| {
+        let mut off = 0;
+        entries(content, entsize, strings).into_iter().map(|entry| {
+            let id = match ids.entry(entry) {
+                hash_map::Entry::Occupied(e) => *e.get(),
+                hash_map::Entry::Vacant(e) => {
+                    unique.push(entry);
+                    *e.insert(unique.len() - 1)
+                },
+            };
+            let r = (off, id);
+            off += entry.len() as u64;
+            r
+        }).collect()
+    }).collect();
+
+    // sorted backwards by their reversed bytes, a string is followed by those it ends with.
+    // both are whole characters, so the shared tail starts on a character.
+    // an unterminated rest can't have anything after it
+    let mut tail : Vec<Option<(usize, usize)>> = vec![None; unique.len()];
+    if strings {
+        let mut order : Vec<usize> = (0..unique.len()).collect();
+        order.sort_by(|a, b| unique[*b].iter().rev().cmp(unique[*a].iter().rev()));
+        let mut owner : Option<usize> = None;
+        for id in order {
+            let terminated = unique[id].ends_with(&vec![0; entsize]);
+            if let Some(o) = owner {
+                if terminated && unique[o].ends_with(unique[id]) {
+                    tail[id] = Some((o, unique[o].len() - unique[id].len()));
+                    continue;
+                }
+            }
+            owner = if terminated { Some(id) } else { None };
+        }
+    }
+
+    let mut content = Vec::new();
+    let mut at = vec![0; unique.len()];
+    for id in 0..unique.len() {
+        if tail[id].is_none() {
+            at[id] = content.len() as u64;
+            content.extend_from_slice(unique[id]);
+        }
+    }
+    for id in 0..unique.len() {
+        if let Some((o, d)) = tail[id] {
+            at[id] = at[o] + d as u64;
+        }
+    }
+
+    Merged {
+        content,
+        offsets: inputs.drain(..).map(|entries| {
+            entries.into_iter().map(|(off, id)| (off, at[id])).collect()
+        }).collect(),
+    }
+}
@@ -0,0 +1,325 @@
+use std::io::{Read, Write};
+use {Error, Header};
+use types;
+use utils::ElfEndianReadExt;
+
+pub const NT_GNU_ABI_TAG: u32           = 1;
+pub const NT_GNU_HWCAP: u32             = 2;
+pub const NT_GNU_BUILD_ID: u32          = 3;
+pub const NT_GNU_GOLD_VERSION: u32      = 4;
+pub const NT_GNU_PROPERTY_TYPE_0: u32   = 5;
+
+pub const GNU_PROPERTY_STACK_SIZE: u32              = 1;
+pub const GNU_PROPERTY_NO_COPY_ON_PROTECTED: u32    = 2;
+pub const GNU_PROPERTY_X86_FEATURE_1_AND: u32       = 0xc0000002;
+pub const GNU_PROPERTY_X86_FEATURE_2_NEEDED: u32    = 0xc0008001;
+pub const GNU_PROPERTY_X86_ISA_1_NEEDED: u32        = 0xc0008002;
+pub const GNU_PROPERTY_X86_FEATURE_2_USED: u32      = 0xc0010001;
+pub const GNU_PROPERTY_X86_ISA_1_USED: u32          = 0xc0010002;
+
+/// a single entry in a NT_GNU_PROPERTY_TYPE_0 note
+#[derive(Debug, Clone, PartialEq)]
+pub enum GnuProperty {
+    StackSize(u64),
+    NoCopyOnProtected,
+    X86Feature1And(types::X86Feature1),
+    X86Feature2Needed(types::X86Feature2),
+    X86Feature2Used(types::X86Feature2),
+    X86Isa1Needed(types::X86Isa1),
+    X86Isa1Used(types::X86Isa1),
+    Raw{
+        ptype:  u32,
+        data:   Vec<u8>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum NoteContent {
+    /// the minimum kernel version. os is 0 for linux, 1 hurd, 2 solaris, 3 freebsd
+    GnuAbiTag{
+        os:         u32,
+        major:      u32,
+        minor:      u32,
+        subminor:   u32,
+    },
+    GnuBuildId(Vec<u8>),
+    /// without the terminating 0
+    GnuGoldVersion(Vec<u8>),
+    GnuProperties(Vec<GnuProperty>),
+    /// anything we don't understand, including all notes with an owner other than GNU
+    Raw{
+        ntype:  u32,
+        desc:   Vec<u8>,
+    },
+}
+
+impl Default for NoteContent {
+    fn default() -> Self {
+        NoteContent::Raw{ntype: 0, desc: Vec::new()}
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Note {
+    /// the owner, without the terminating 0
+    pub name:       Vec<u8>,
+    pub content:    NoteContent,
+}
+
+/// alignment of the entries in a note section or segment.
+/// gnu property notes in ELF64 are 8 byte aligned, everything else is 4
+pub fn align(addralign: u64) -> u64 {
+    if addralign == 8 { 8 } else { 4 }
+}
+
+fn align_up(v: usize, align: u64) -> usize {
+    let align = align as usize;
+    (v + align - 1) & !(align - 1)
+}
+
+fn property_align(eh: &Header) -> u64 {
+    match eh.ident_class {
+        types::Class::Class64 => 8,
+        types::Class::Class32 => 4,
+    }
+}
+
+impl GnuProperty {
+    fn ptype(&self) -> u32 {
+        match *self {
+            GnuProperty::StackSize(_) => GNU_PROPERTY_STACK_SIZE,
+            GnuProperty::NoCopyOnProtected => GNU_PROPERTY_NO_COPY_ON_PROTECTED,
+            GnuProperty::X86Feature1And(_) => GNU_PROPERTY_X86_FEATURE_1_AND,
+            GnuProperty::X86Feature2Needed(_) => GNU_PROPERTY_X86_FEATURE_2_NEEDED,
+            GnuProperty::X86Feature2Used(_) => GNU_PROPERTY_X86_FEATURE_2_USED,
+            GnuProperty::X86Isa1Needed(_) => GNU_PROPERTY_X86_ISA_1_NEEDED,
+            GnuProperty::X86Isa1Used(_) => GNU_PROPERTY_X86_ISA_1_USED,
+            GnuProperty::Raw{ptype, ..} => ptype,
+        }
+    }
+
+    fn datasz(&self, eh: &Header) -> usize {
+        match *self {
+            GnuProperty::StackSize(_) => property_align(eh) as usize,
+            GnuProperty::NoCopyOnProtected => 0,
+            GnuProperty::Raw{ref data, ..} => data.len(),
+            _ => 4,
+        }
+    }
+
+    fn size(&self, eh: &Header) -> usize {
+        align_up(8 + self.datasz(eh), property_align(eh))
+    }
+
+    /// decode a property list. returns None if it isn't well formed,
+    /// in which case the whole note is kept as raw bytes
+    fn from_bytes(mut desc: &[u8], eh: &Header) -> Option<Vec<GnuProperty>> {
+        let mut r = Vec::new();
+        while !desc.is_empty() {
+            let raw    = desc;
+            let ptype  = desc.elf_read_u32(eh).ok()?;
+            let datasz = desc.elf_read_u32(eh).ok()? as usize;
+            let padded = align_up(datasz, property_align(eh));
+            if padded > desc.len() || desc[datasz..padded].iter().any(|b| *b != 0) {
+                return None;
+            }
+            let mut data = &desc[..datasz];
+            let prop = match (ptype, datasz) {
+                (GNU_PROPERTY_STACK_SIZE, sz) if sz as u64 == property_align(eh) =>
+                    GnuProperty::StackSize(data.elf_read_uclass(eh).ok()?),
+                (GNU_PROPERTY_NO_COPY_ON_PROTECTED, 0) => GnuProperty::NoCopyOnProtected,
+                (GNU_PROPERTY_X86_FEATURE_1_AND, 4) => GnuProperty::X86Feature1And(
+                    types::X86Feature1::from_bits_truncate(data.elf_read_u32(eh).ok()?)),
+                (GNU_PROPERTY_X86_FEATURE_2_NEEDED, 4) => GnuProperty::X86Feature2Needed(
+                    types::X86Feature2::from_bits_truncate(data.elf_read_u32(eh).ok()?)),
+                (GNU_PROPERTY_X86_FEATURE_2_USED, 4) => GnuProperty::X86Feature2Used(
+                    types::X86Feature2::from_bits_truncate(data.elf_read_u32(eh).ok()?)),
+                (GNU_PROPERTY_X86_ISA_1_NEEDED, 4) => GnuProperty::X86Isa1Needed(
+                    types::X86Isa1::from_bits_truncate(data.elf_read_u32(eh).ok()?)),
+                (GNU_PROPERTY_X86_ISA_1_USED, 4) => GnuProperty::X86Isa1Used(
+                    types::X86Isa1::from_bits_truncate(data.elf_read_u32(eh).ok()?)),
+                _ => GnuProperty::Raw{
+                    ptype,
+                    data:   data.to_vec(),
+                },
+            };
+
+            // anything that wouldn't be written back identically stays raw,
+            // for example flags we don't know
+            let raw = &raw[..8 + padded];
+            let mut encoded = Vec::with_capacity(raw.len());
+            prop.to_writer(&mut encoded, eh).ok()?;
+            r.push(if encoded == raw {
+                prop
+            } else {
+                GnuProperty::Raw{ptype, data: desc[..datasz].to_vec()}
+            });
+            desc = &desc[padded..];
+        }
+        Some(r)
+    }
+
+    fn to_writer<W>(&self, mut io: W, eh: &Header) -> Result<usize, Error> where W: Write {
+        elf_write_u32!(eh, io, self.ptype())?;
+        elf_write_u32!(eh, io, self.datasz(eh) as u32)?;
+        match *self {
+            GnuProperty::StackSize(v) => {
+                elf_write_uclass!(eh, io, v)?;
+            },
+            GnuProperty::NoCopyOnProtected => {},
+            GnuProperty::X86Feature1And(v) => elf_write_u32!(eh, io, v.bits())?,
+            GnuProperty::X86Feature2Needed(v) => elf_write_u32!(eh, io, v.bits())?,
+            GnuProperty::X86Feature2Used(v) => elf_write_u32!(eh, io, v.bits())?,
+            GnuProperty::X86Isa1Needed(v) => elf_write_u32!(eh, io, v.bits())?,
+            GnuProperty::X86Isa1Used(v) => elf_write_u32!(eh, io, v.bits())?,
+            GnuProperty::Raw{ref data, ..} => {
+                io.write_all(data)?;
+            },
+        }
+        let size = self.size(eh);
+        io.write_all(&vec![0; size - 8 - self.datasz(eh)])?;
+        Ok(size)
+    }
+}
+
+impl Note {
+    pub fn ntype(&self) -> u32 {
+        match self.content {
+            NoteContent::GnuAbiTag{..} => NT_GNU_ABI_TAG,
+            NoteContent::GnuBuildId(_) => NT_GNU_BUILD_ID,
+            NoteContent::GnuGoldVersion(_) => NT_GNU_GOLD_VERSION,
+            NoteContent::GnuProperties(_) => NT_GNU_PROPERTY_TYPE_0,
+            NoteContent::Raw{ntype, ..} => ntype,
+        }
+    }
+
+    fn namesz(&self) -> usize {
+        if !self.name.is_empty() { self.name.len() + 1 } else { 0 }
+    }
+
+    fn descsz(&self, eh: &Header) -> usize {
+        match self.content {
+            NoteContent::GnuAbiTag{..} => 16,
+            NoteContent::GnuBuildId(ref v) => v.len(),
+            NoteContent::GnuGoldVersion(ref v) => v.len() + 1,
+            NoteContent::GnuProperties(ref v) => v.iter().map(|p| p.size(eh)).sum(),
+            NoteContent::Raw{ref desc, ..} => desc.len(),
+        }
+    }
+
+    /// size including padding, for entries aligned to `align`
+    pub fn size(&self, eh: &Header, align: u64) -> usize {
+        align_up(align_up(12 + self.namesz(), align) + self.descsz(eh), align)
+    }
+
+    /// read all notes in a section or segment.
+    /// align is the alignment of the entries, see `align`
+    pub fn from_reader<R>(mut io: R, eh: &Header, align: u64) -> Result<Vec<Note>, Error>
+    where
+        R: Read,
+    {
+        let mut b = Vec::new();
+        io.read_to_end(&mut b)?;
+
+        let mut r = Vec::new();
+        let mut off = 0;
+        while off + 12 <= b.len() {
+            let mut h = &b[off..];
+            let namesz = h.elf_read_u32(eh)? as usize;
+            let descsz = h.elf_read_u32(eh)? as usize;
+            let ntype  = h.elf_read_u32(eh)?;
+
+            let descoff = align_up(12 + namesz, align);
+            let next    = align_up(descoff + descsz, align);
+            if off + descoff + descsz > b.len() {
+                return Err(Error::TruncatedInput);
+            }
+            let name = b[off + 12..off + 12 + namesz].split(|c| *c == 0).next()
+                .unwrap_or(&[]).to_vec();
+            let desc = &b[off + descoff..off + descoff + descsz];
+
+            let content = if name == b"GNU" {
+                Note::decode_gnu(ntype, desc, eh)
+            } else {
+                None
+            };
+
+            r.push(Note {
+                name,
+                content:    content.unwrap_or_else(|| NoteContent::Raw{
+                    ntype,
+                    desc:   desc.to_vec(),
+                }),
+            });
+            off += next;
+        }
+
+        Ok(r)
+    }
+
+    fn decode_gnu(ntype: u32, desc: &[u8], eh: &Header) -> Option<NoteContent> {
+        match ntype {
+            NT_GNU_ABI_TAG if desc.len() == 16 => {
+                let mut io = desc;
+                Some(NoteContent::GnuAbiTag{
+                    os:         io.elf_read_u32(eh).ok()?,
+                    major:      io.elf_read_u32(eh).ok()?,
+                    minor:      io.elf_read_u32(eh).ok()?,
+                    subminor:   io.elf_read_u32(eh).ok()?,
+                })
+            },
+            NT_GNU_BUILD_ID => Some(NoteContent::GnuBuildId(desc.to_vec())),
+            NT_GNU_GOLD_VERSION => match desc.split_last() {
+                Some((&0, v)) if !v.contains(&0) => Some(NoteContent::GnuGoldVersion(v.to_vec())),
+                _ => None,
+            },
+            NT_GNU_PROPERTY_TYPE_0 => {
+                GnuProperty::from_bytes(desc, eh).map(NoteContent::GnuProperties)
+            },
+            _ => None,
+        }
+    }
+
+    pub fn to_writer<W>(&self, mut io: W, eh: &Header, align: u64) -> Result<usize, Error>
+    where
+        W: Write,
+    {
+        let namesz = self.namesz();
+        let descsz = self.descsz(eh);
+        elf_write_u32!(eh, io, namesz as u32)?;
+        elf_write_u32!(eh, io, descsz as u32)?;
+        elf_write_u32!(eh, io, self.ntype())?;
+
+        let descoff = align_up(12 + namesz, align);
+        io.write_all(&self.name)?;
+        io.write_all(&vec![0; descoff - 12 - self.name.len()])?;
+
+        match self.content {
+            NoteContent::GnuAbiTag{os, major, minor, subminor} => {
+                elf_write_u32!(eh, io, os)?;
+                elf_write_u32!(eh, io, major)?;
+                elf_write_u32!(eh, io, minor)?;
+                elf_write_u32!(eh, io, subminor)?;
+            },
+            NoteContent::GnuBuildId(ref v) => {
+                io.write_all(v)?;
+            },
+            NoteContent::GnuGoldVersion(ref v) => {
+                io.write_all(v)?;
+                io.write_all(&[0])?;
+            },
+            NoteContent::GnuProperties(ref v) => {
+                for p in v {
+                    p.to_writer(&mut io, eh)?;
+                }
+            },
+            NoteContent::Raw{ref desc, ..} => {
+                io.write_all(desc)?;
+            },
+        }
+
+        let size = self.size(eh, align);
+        io.write_all(&vec![0; size - descoff - descsz])?;
+        Ok(size)
+    }
+}
@@ -1,7 +1,9 @@
+use std::fmt;
 use std::io::{Read, Write};
 use {Error, Header, SectionContent};
 use types;
-use num_traits::{FromPrimitive, ToPrimitive};
+use utils::ElfEndianReadExt;
+use byteorder::{ReadBytesExt, WriteBytesExt};
 
 /*
 A Represents the addend used to compute the value of the relocatable field.
@@ -23,88 +25,252 @@ P Represents the place (section offset or address) of the storage unit being rel
 .text which is loaded at 0x100 will have P = 0x103
 
 S Represents the value of the symbol whose index resides in the relocation entry.
-The AMD64 ABI architectures uses only Elf64_Rela relocation entries
-with explicit addends. The r_addend member serves as the relocation addend.
  */
-#[allow(non_camel_case_types)]
-#[derive(Debug, Primitive, PartialEq, Clone)]
-pub enum RelocationType {
-    R_X86_64_NONE = 0,      // none none
-    R_X86_64_64 = 1,        // word64 S + A
-    R_X86_64_PC32 = 2,      // word32 S + A - P
-    R_X86_64_GOT32 = 3,     // word32 G + A
-    R_X86_64_PLT32 = 4,     // word32 L + A - P
-    R_X86_64_COPY = 5,      // none none
-    R_X86_64_GLOB_DAT = 6,  // wordclass S
-    R_X86_64_JUMP_SLOT = 7, // wordclass S
-    R_X86_64_RELATIVE = 8,  // wordclass B + A
-    R_X86_64_GOTPCREL = 9,  // word32 G + GOT + A - P
-    R_X86_64_32 = 10,       // word32 S + A
-    R_X86_64_32S = 11,      // word32 S + A
-    R_X86_64_16 = 12,       // word16 S + A
-    R_X86_64_PC16 = 13,     // word16 S + A - P
-    R_X86_64_8 = 14,        // word8 S + A
-    R_X86_64_PC8 = 15,      // word8 S + A - P
+
+/// The r_type of a relocation. What it means depends on the machine, so the types of every
+/// supported machine are constants on it, and typename() gives the name for a machine.
+///
+/// This used to be an enum of the x86_64 types, see CHANGELOG.md for how to migrate.
+#[allow(non_upper_case_globals)]
+#[derive(Default, Clone, PartialEq, Eq)]
+pub struct RelocationType(pub u32);
+impl RelocationType {
+    pub const R_X86_64_NONE: RelocationType = RelocationType(0); // none none
+    pub const R_X86_64_64: RelocationType = RelocationType(1); // word64 S + A
+    pub const R_X86_64_PC32: RelocationType = RelocationType(2); // word32 S + A - P
+    pub const R_X86_64_GOT32: RelocationType = RelocationType(3); // word32 G + A
+    pub const R_X86_64_PLT32: RelocationType = RelocationType(4); // word32 L + A - P
+    pub const R_X86_64_COPY: RelocationType = RelocationType(5); // none none
+    pub const R_X86_64_GLOB_DAT: RelocationType = RelocationType(6); // wordclass S
+    pub const R_X86_64_JUMP_SLOT: RelocationType = RelocationType(7); // wordclass S
+    pub const R_X86_64_RELATIVE: RelocationType = RelocationType(8); // wordclass B + A
+    pub const R_X86_64_GOTPCREL: RelocationType = RelocationType(9); // word32 G + GOT + A - P
+    pub const R_X86_64_32: RelocationType = RelocationType(10); // word32 S + A
+    pub const R_X86_64_32S: RelocationType = RelocationType(11); // word32 S + A
+    pub const R_X86_64_16: RelocationType = RelocationType(12); // word16 S + A
+    pub const R_X86_64_PC16: RelocationType = RelocationType(13); // word16 S + A - P
+    pub const R_X86_64_8: RelocationType = RelocationType(14); // word8 S + A
+    pub const R_X86_64_PC8: RelocationType = RelocationType(15); // word8 S + A - P
 
     /// First part of the tls_index structure: ID of module containing symbol
     /// writes the module id at this location
     /// in an executable this is always exactly 1,
     /// so this reloc is only emitted for DYN where the dynamic linker
     /// needs to give the module an id
-    R_X86_64_DTPMOD64 = 16, // word64
+    pub const R_X86_64_DTPMOD64: RelocationType = RelocationType(16); // word64
 
     /// Second part of tls_index: The Offset of the symbol in the TLS Block
     /// this is written into the GOT of _this_ unit by the dynamic linker,
     /// and the offset is into the TLS block of some other unit that actually
     /// defines that symbol
-    R_X86_64_DTPOFF64 = 17, // word64
+    pub const R_X86_64_DTPOFF64: RelocationType = RelocationType(17); // word64
 
     /// Offset in initial TLS Block in initial exec model
     /// no idea why this needs a different reloc type, this appears to be identical
     /// to R_X86_64_DTPOFF64
-    R_X86_64_TPOFF64 = 18, // word64
+    pub const R_X86_64_TPOFF64: RelocationType = RelocationType(18); // word64
 
     /// PC Relative address to the tls_index structure in the GOT
     /// in general dynamic model
-    R_X86_64_TLSGD = 19, // word32
+    pub const R_X86_64_TLSGD: RelocationType = RelocationType(19); // word32
 
     /// PC Relative address to the tls_index structure in the GOT
     /// in local dynamic model.  that index only contains the module id,
     /// since the offset is known at link time and will be accessed via
     /// R_X86_64_DTPOFF32
-    R_X86_64_TLSLD = 20, // word32
+    pub const R_X86_64_TLSLD: RelocationType = RelocationType(20); // word32
 
     /// Offset of the symbol in TLS Block (local dynamic model)
-    R_X86_64_DTPOFF32 = 21, // word32
+    pub const R_X86_64_DTPOFF32: RelocationType = RelocationType(21); // word32
 
 
     /// in initial exec model, this is a PC Relative offset to a GOT entry
     /// which contains the 32bit offset into the thread local block.
     /// this is emitted by the compiler when the thread local var will definately
     /// be inside the executable.
-    R_X86_64_GOTTPOFF = 22, // word32
+    pub const R_X86_64_GOTTPOFF: RelocationType = RelocationType(22); // word32
 
     /// for initial exec model, this is the reloc on the GOT entry.
-    R_X86_64_TPOFF32 = 23, // word32
-
-    R_X86_64_PC64 = 24,            // word64 S + A - P
-    R_X86_64_GOTOFF64 = 25,        // word64 S + A - GOT
-    R_X86_64_GOTPC32 = 26,         // word32 GOT + A - P
-    R_X86_64_SIZE32 = 32,          // word32 Z + A
-    R_X86_64_SIZE64 = 33,          // word64 Z + A
-    R_X86_64_GOTPC32_TLSDESC = 34, // word32
-    R_X86_64_TLSDESC_CALL = 35,    // none
-    R_X86_64_TLSDESC = 36,         // word64×2
-    R_X86_64_IRELATIVE = 37,       // wordclass indirect (B + A)
-    R_X86_64_RELATIVE64 = 38,      // word64 B + A
-
-    //not documented. hopefully these are ok to be treated as R_X86_64_GOTPCREL
-    R_X86_64_GOTPCRELX = 41,     // word32 G + GOT + A - P
-    R_X86_64_REX_GOTPCRELX = 42, //word32 G + GOT + A - P
+    pub const R_X86_64_TPOFF32: RelocationType = RelocationType(23); // word32
+
+    pub const R_X86_64_PC64: RelocationType = RelocationType(24); // word64 S + A - P
+    pub const R_X86_64_GOTOFF64: RelocationType = RelocationType(25); // word64 S + A - GOT
+    pub const R_X86_64_GOTPC32: RelocationType = RelocationType(26); // word32 GOT + A - P
+    pub const R_X86_64_SIZE32: RelocationType = RelocationType(32); // word32 Z + A
+    pub const R_X86_64_SIZE64: RelocationType = RelocationType(33); // word64 Z + A
+    pub const R_X86_64_GOTPC32_TLSDESC: RelocationType = RelocationType(34); // word32
+    pub const R_X86_64_TLSDESC_CALL: RelocationType = RelocationType(35); // none
+    pub const R_X86_64_TLSDESC: RelocationType = RelocationType(36); // word64×2
+    pub const R_X86_64_IRELATIVE: RelocationType = RelocationType(37); // wordclass indirect (B + A)
+    pub const R_X86_64_RELATIVE64: RelocationType = RelocationType(38); // word64 B + A
+
+    // R_X86_64_GOTPCREL on instructions the linker may rewrite to not use the GOT
+    pub const R_X86_64_GOTPCRELX: RelocationType = RelocationType(41); // word32 G + GOT + A - P
+    pub const R_X86_64_REX_GOTPCRELX: RelocationType = RelocationType(42); //word32 G + GOT + A - P
+
+    pub const R_386_NONE: RelocationType = RelocationType(0);
+    pub const R_386_32: RelocationType = RelocationType(1);         // word32 S + A
+    pub const R_386_PC32: RelocationType = RelocationType(2);       // word32 S + A - P
+    pub const R_386_GOT32: RelocationType = RelocationType(3);      // word32 G + A
+    pub const R_386_PLT32: RelocationType = RelocationType(4);      // word32 L + A - P
+    pub const R_386_COPY: RelocationType = RelocationType(5);       // none
+    pub const R_386_GLOB_DAT: RelocationType = RelocationType(6);   // word32 S
+    pub const R_386_JMP_SLOT: RelocationType = RelocationType(7);   // word32 S
+    pub const R_386_RELATIVE: RelocationType = RelocationType(8);   // word32 B + A
+    pub const R_386_GOTOFF: RelocationType = RelocationType(9);     // word32 S + A - GOT
+    pub const R_386_GOTPC: RelocationType = RelocationType(10);     // word32 GOT + A - P
+    pub const R_386_TLS_TPOFF: RelocationType = RelocationType(14);
+    pub const R_386_TLS_IE: RelocationType = RelocationType(15);
+    pub const R_386_TLS_GOTIE: RelocationType = RelocationType(16);
+    pub const R_386_TLS_LE: RelocationType = RelocationType(17);
+    pub const R_386_TLS_GD: RelocationType = RelocationType(18);
+    pub const R_386_TLS_LDM: RelocationType = RelocationType(19);
+    pub const R_386_16: RelocationType = RelocationType(20);        // word16 S + A
+    pub const R_386_PC16: RelocationType = RelocationType(21);      // word16 S + A - P
+    pub const R_386_8: RelocationType = RelocationType(22);         // word8 S + A
+    pub const R_386_PC8: RelocationType = RelocationType(23);       // word8 S + A - P
+    pub const R_386_TLS_LDO_32: RelocationType = RelocationType(32);
+    pub const R_386_TLS_IE_32: RelocationType = RelocationType(33);
+    pub const R_386_TLS_LE_32: RelocationType = RelocationType(34);
+    pub const R_386_TLS_DTPMOD32: RelocationType = RelocationType(35);
+    pub const R_386_TLS_DTPOFF32: RelocationType = RelocationType(36);
+    pub const R_386_TLS_TPOFF32: RelocationType = RelocationType(37);
+    pub const R_386_SIZE32: RelocationType = RelocationType(38);
+    pub const R_386_TLS_GOTDESC: RelocationType = RelocationType(39);
+    pub const R_386_TLS_DESC_CALL: RelocationType = RelocationType(40);
+    pub const R_386_TLS_DESC: RelocationType = RelocationType(41);
+    pub const R_386_IRELATIVE: RelocationType = RelocationType(42);
+    pub const R_386_GOT32X: RelocationType = RelocationType(43);
+
+    pub const R_ARM_NONE: RelocationType = RelocationType(0);
+    pub const R_ARM_PC24: RelocationType = RelocationType(1);       // insn imm24 ((S + A) | T) - P
+    pub const R_ARM_ABS32: RelocationType = RelocationType(2);      // word32 (S + A) | T
+    pub const R_ARM_REL32: RelocationType = RelocationType(3);      // word32 ((S + A) | T) - P
+    pub const R_ARM_ABS16: RelocationType = RelocationType(5);      // word16 S + A
+    pub const R_ARM_ABS8: RelocationType = RelocationType(8);       // word8 S + A
+    pub const R_ARM_TLS_DTPMOD32: RelocationType = RelocationType(17);
+    pub const R_ARM_TLS_DTPOFF32: RelocationType = RelocationType(18);
+    pub const R_ARM_TLS_TPOFF32: RelocationType = RelocationType(19);
+    pub const R_ARM_COPY: RelocationType = RelocationType(20);
+    pub const R_ARM_GLOB_DAT: RelocationType = RelocationType(21);
+    pub const R_ARM_JUMP_SLOT: RelocationType = RelocationType(22);
+    pub const R_ARM_RELATIVE: RelocationType = RelocationType(23);
+    pub const R_ARM_GOTOFF32: RelocationType = RelocationType(24);
+    pub const R_ARM_BASE_PREL: RelocationType = RelocationType(25);
+    pub const R_ARM_GOT_BREL: RelocationType = RelocationType(26);
+    pub const R_ARM_PLT32: RelocationType = RelocationType(27);
+    pub const R_ARM_CALL: RelocationType = RelocationType(28);      // insn imm24
+    pub const R_ARM_JUMP24: RelocationType = RelocationType(29);    // insn imm24
+    pub const R_ARM_THM_JUMP24: RelocationType = RelocationType(30);
+    pub const R_ARM_TARGET1: RelocationType = RelocationType(38);   // word32, like ABS32 or REL32
+    pub const R_ARM_V4BX: RelocationType = RelocationType(40);
+    pub const R_ARM_TARGET2: RelocationType = RelocationType(41);
+    pub const R_ARM_PREL31: RelocationType = RelocationType(42);    // word31 ((S + A) | T) - P
+    pub const R_ARM_MOVW_ABS_NC: RelocationType = RelocationType(43); // insn imm16
+    pub const R_ARM_MOVT_ABS: RelocationType = RelocationType(44);  // insn imm16
+    pub const R_ARM_MOVW_PREL_NC: RelocationType = RelocationType(45); // insn imm16
+    pub const R_ARM_MOVT_PREL: RelocationType = RelocationType(46); // insn imm16
+    pub const R_ARM_THM_CALL: RelocationType = RelocationType(10);
+    pub const R_ARM_TLS_GD32: RelocationType = RelocationType(104);
+    pub const R_ARM_TLS_LDM32: RelocationType = RelocationType(105);
+    pub const R_ARM_TLS_LDO32: RelocationType = RelocationType(106);
+    pub const R_ARM_TLS_IE32: RelocationType = RelocationType(107);
+    pub const R_ARM_TLS_LE32: RelocationType = RelocationType(108);
+    pub const R_ARM_IRELATIVE: RelocationType = RelocationType(160);
+
+    pub const R_MIPS_NONE: RelocationType = RelocationType(0);
+    pub const R_MIPS_16: RelocationType = RelocationType(1);        // word16 S + A
+    pub const R_MIPS_32: RelocationType = RelocationType(2);        // word32 S + A
+    pub const R_MIPS_REL32: RelocationType = RelocationType(3);     // word32 A - EA + S
+    pub const R_MIPS_26: RelocationType = RelocationType(4);        // insn imm26
+    pub const R_MIPS_HI16: RelocationType = RelocationType(5);      // insn imm16, paired with LO16
+    pub const R_MIPS_LO16: RelocationType = RelocationType(6);      // insn imm16
+    pub const R_MIPS_GPREL16: RelocationType = RelocationType(7);   // insn imm16
+    pub const R_MIPS_LITERAL: RelocationType = RelocationType(8);   // insn imm16
+    pub const R_MIPS_GOT16: RelocationType = RelocationType(9);     // insn imm16
+    pub const R_MIPS_PC16: RelocationType = RelocationType(10);     // insn imm16
+    pub const R_MIPS_CALL16: RelocationType = RelocationType(11);   // insn imm16
+    pub const R_MIPS_GPREL32: RelocationType = RelocationType(12);  // word32
+    pub const R_MIPS_64: RelocationType = RelocationType(18);       // word64
+    pub const R_MIPS_TLS_DTPMOD32: RelocationType = RelocationType(38);
+    pub const R_MIPS_TLS_DTPREL32: RelocationType = RelocationType(39);
+    pub const R_MIPS_TLS_GD: RelocationType = RelocationType(42);
+    pub const R_MIPS_TLS_LDM: RelocationType = RelocationType(43);
+    pub const R_MIPS_TLS_DTPREL_HI16: RelocationType = RelocationType(44);
+    pub const R_MIPS_TLS_DTPREL_LO16: RelocationType = RelocationType(45);
+    pub const R_MIPS_TLS_GOTTPREL: RelocationType = RelocationType(46);
+    pub const R_MIPS_TLS_TPREL32: RelocationType = RelocationType(47);
+    pub const R_MIPS_TLS_TPREL_HI16: RelocationType = RelocationType(49);
+    pub const R_MIPS_TLS_TPREL_LO16: RelocationType = RelocationType(50);
+    pub const R_MIPS_COPY: RelocationType = RelocationType(126);
+    pub const R_MIPS_JUMP_SLOT: RelocationType = RelocationType(127);
+
+    pub fn to_u32(&self) -> u32 {
+        let &RelocationType(v) = self;
+        v
+    }
+
+    pub fn typename(&self, eh: &Header) -> Option<&'static str> {
+        self.name(&eh.machine)
+    }
+
+    fn name(&self, machine: &types::Machine) -> Option<&'static str> {
+        macro_rules! names {
+            ($v:expr, $($name:ident),*) => {
+                match $v {
+                    $(&RelocationType::$name => Some(&stringify!($name)[2..]),)*
+                    _ => None,
+                }
+            }
+        }
+        match *machine {
+            types::Machine::X86_64 => names!(self,
+                R_X86_64_NONE, R_X86_64_64, R_X86_64_PC32, R_X86_64_GOT32, R_X86_64_PLT32,
+                R_X86_64_COPY, R_X86_64_GLOB_DAT, R_X86_64_JUMP_SLOT, R_X86_64_RELATIVE,
+                R_X86_64_GOTPCREL, R_X86_64_32, R_X86_64_32S, R_X86_64_16, R_X86_64_PC16,
+                R_X86_64_8, R_X86_64_PC8, R_X86_64_DTPMOD64, R_X86_64_DTPOFF64,
+                R_X86_64_TPOFF64, R_X86_64_TLSGD, R_X86_64_TLSLD, R_X86_64_DTPOFF32,
+                R_X86_64_GOTTPOFF, R_X86_64_TPOFF32, R_X86_64_PC64, R_X86_64_GOTOFF64,
+                R_X86_64_GOTPC32, R_X86_64_SIZE32, R_X86_64_SIZE64, R_X86_64_GOTPC32_TLSDESC,
+                R_X86_64_TLSDESC_CALL, R_X86_64_TLSDESC, R_X86_64_IRELATIVE,
+                R_X86_64_RELATIVE64, R_X86_64_GOTPCRELX, R_X86_64_REX_GOTPCRELX),
+            types::Machine::EM386 => names!(self,
+                R_386_NONE, R_386_32, R_386_PC32, R_386_GOT32, R_386_PLT32, R_386_COPY,
+                R_386_GLOB_DAT, R_386_JMP_SLOT, R_386_RELATIVE, R_386_GOTOFF, R_386_GOTPC,
+                R_386_TLS_TPOFF, R_386_TLS_IE, R_386_TLS_GOTIE, R_386_TLS_LE, R_386_TLS_GD,
+                R_386_TLS_LDM, R_386_16, R_386_PC16, R_386_8, R_386_PC8, R_386_TLS_LDO_32,
+                R_386_TLS_IE_32, R_386_TLS_LE_32, R_386_TLS_DTPMOD32, R_386_TLS_DTPOFF32,
+                R_386_TLS_TPOFF32, R_386_SIZE32, R_386_TLS_GOTDESC, R_386_TLS_DESC_CALL,
+                R_386_TLS_DESC, R_386_IRELATIVE, R_386_GOT32X),
+            types::Machine::ARM => names!(self,
+                R_ARM_NONE, R_ARM_PC24, R_ARM_ABS32, R_ARM_REL32, R_ARM_ABS16, R_ARM_ABS8,
+                R_ARM_THM_CALL, R_ARM_TLS_DTPMOD32, R_ARM_TLS_DTPOFF32, R_ARM_TLS_TPOFF32,
+                R_ARM_COPY, R_ARM_GLOB_DAT, R_ARM_JUMP_SLOT, R_ARM_RELATIVE, R_ARM_GOTOFF32,
+                R_ARM_BASE_PREL, R_ARM_GOT_BREL, R_ARM_PLT32, R_ARM_CALL, R_ARM_JUMP24,
+                R_ARM_THM_JUMP24, R_ARM_TARGET1, R_ARM_V4BX, R_ARM_TARGET2, R_ARM_PREL31,
+                R_ARM_MOVW_ABS_NC, R_ARM_MOVT_ABS, R_ARM_MOVW_PREL_NC, R_ARM_MOVT_PREL,
+                R_ARM_TLS_GD32, R_ARM_TLS_LDM32, R_ARM_TLS_LDO32, R_ARM_TLS_IE32,
+                R_ARM_TLS_LE32, R_ARM_IRELATIVE),
+            types::Machine::MIPS => names!(self,
+                R_MIPS_NONE, R_MIPS_16, R_MIPS_32, R_MIPS_REL32, R_MIPS_26, R_MIPS_HI16,
+                R_MIPS_LO16, R_MIPS_GPREL16, R_MIPS_LITERAL, R_MIPS_GOT16, R_MIPS_PC16,
+                R_MIPS_CALL16, R_MIPS_GPREL32, R_MIPS_64, R_MIPS_TLS_DTPMOD32,
+                R_MIPS_TLS_DTPREL32, R_MIPS_TLS_GD, R_MIPS_TLS_LDM, R_MIPS_TLS_DTPREL_HI16,
+                R_MIPS_TLS_DTPREL_LO16, R_MIPS_TLS_GOTTPREL, R_MIPS_TLS_TPREL32,
+                R_MIPS_TLS_TPREL_HI16, R_MIPS_TLS_TPREL_LO16, R_MIPS_COPY, R_MIPS_JUMP_SLOT),
+            _ => None,
+        }
+    }
 }
-impl Default for RelocationType {
-    fn default() -> Self {
-        RelocationType::R_X86_64_NONE
+
+/// without a header there is no machine, so these are named like x86_64, which is what
+/// the linker deals with. others show the raw value
+impl fmt::Debug for RelocationType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.name(&types::Machine::X86_64) {
+            Some(name) => write!(f, "R_{}", name),
+            None => write!(f, "RelocationType({})", self.0),
+        }
     }
 }
 
@@ -116,61 +282,115 @@ pub struct Relocation {
     pub addend: i64,
 }
 
+/// how the addend of a REL entry is stored in the bytes it applies to
+enum ImplicitAddend {
+    Word8,
+    Word16,
+    Word32,
+    Word64,
+    /// arm b/bl: signed 24 bit word offset in the low bits of the instruction
+    ArmImm24,
+    /// arm movw/movt: 16 bit immediate split into imm4:imm12
+    ArmImm16,
+    /// thumb-2 bl/b.w: signed 25 bit halfword offset split as S:J1:J2:imm10:imm11
+    /// across the two halfwords of the instruction
+    ThumbBranch,
+    /// arm exception index: signed 31 bit offset, the top bit belongs to the entry
+    Prel31,
+    /// mips j/jal: 26 bit word index
+    MipsImm26,
+    /// mips immediate: signed low 16 bits of the instruction
+    MipsImm16,
+}
+
 impl Relocation {
+    /// size of a RELA entry, which is what the linker emits
     pub fn entsize(eh: &Header) -> usize {
-        match eh.machine {
-            types::Machine::X86_64 => 3 * 8,
-            _ => panic!("relocs for machine '{:?}' not implemented", eh.machine),
+        match eh.ident_class {
+            types::Class::Class64 => 3 * 8,
+            types::Class::Class32 => 3 * 4,
+        }
+    }
+
+    /// size of a REL entry, which has no addend field
+    pub fn entsize_rel(eh: &Header) -> usize {
+        match eh.ident_class {
+            types::Class::Class64 => 2 * 8,
+            types::Class::Class32 => 2 * 4,
         }
     }
 
     pub fn from_reader<R>(
-        mut io: R,
+        io: R,
         _: Option<&SectionContent>,
         eh: &Header,
     ) -> Result<SectionContent, Error>
     where
         R: Read,
     {
-        if eh.machine != types::Machine::X86_64 {
-            return Err(Error::UnsupportedMachineTypeForRelocation(
-                eh.machine.clone(),
-            ));
-        }
+        Self::read_entries(io, eh, true)
+    }
+
+    /// read a SHT_REL section.
+    /// the addends are left at 0 since they live in the section the relocations apply to.
+    /// use `implicit_addend` to fill them in.
+    pub fn from_reader_rel<R>(
+        io: R,
+        _: Option<&SectionContent>,
+        eh: &Header,
+    ) -> Result<SectionContent, Error>
+    where
+        R: Read,
+    {
+        Self::read_entries(io, eh, false)
+    }
 
+    fn read_entries<R>(mut io: R, eh: &Header, rela: bool) -> Result<SectionContent, Error>
+    where
+        R: Read,
+    {
         let mut r = Vec::new();
+        let mut b = vec![0; if rela { Self::entsize(eh) } else { Self::entsize_rel(eh) }];
+        while let Ok(()) = io.read_exact(&mut b) {
+            r.push(Relocation::from_bytes(&b, eh, rela)?);
+        }
 
-        while let Ok(addr) = elf_read_u64!(eh, io) {
-            let info = match elf_read_u64!(eh, io) {
-                Ok(v) => v,
-                _ => break,
-            };
-
-            let sym = (info >> 32) as u32;
-            let rtype = (info & 0xffffffff) as u32;
-            let rtype = match RelocationType::from_u32(rtype) {
-                Some(v) => v,
-                None => {
-                    println!(
-                        "warning: unknown relocation type {} skipped while reading",
-                        rtype
-                    );
-                    elf_read_u64!(eh, io)?;
-                    continue;
-                }
-            };
+        Ok(SectionContent::Relocations(r))
+    }
 
-            let addend = elf_read_u64!(eh, io)?;
+    /// decode a single REL or RELA entry
+    pub(crate) fn from_bytes(b: &[u8], eh: &Header, rela: bool) -> Result<Relocation, Error> {
+        let mut io = b;
+        let addr = io.elf_read_uclass(eh)?;
+        let info = io.elf_read_uclass(eh)?;
 
-            r.push(Relocation {
-                addr: addr,
-                sym: sym,
-                rtype: rtype,
-                addend: addend as i64,
-            });
-        }
+        let (sym, rtype) = match eh.ident_class {
+            types::Class::Class64 => ((info >> 32) as u32, (info & 0xffffffff) as u32),
+            types::Class::Class32 => ((info >> 8) as u32, (info & 0xff) as u32),
+        };
 
-        Ok(SectionContent::Relocations(r))
+        let addend = if rela {
+            match eh.ident_class {
+                types::Class::Class64 => io.elf_read_u64(eh)? as i64,
+                types::Class::Class32 => io.elf_read_u32(eh)? as i32 as i64,
+            }
+        } else {
+            0
+        };
+
+        Ok(Relocation {
+            addr,
+            sym,
+            rtype: RelocationType(rtype),
+            addend,
+        })
+    }
+
+    fn info(&self, eh: &Header) -> u64 {
+        match eh.ident_class {
+            types::Class::Class64 => ((self.sym as u64) << 32) + self.rtype.to_u32() as u64,
+            types::Class::Class32 => ((self.sym as u64) << 8) + (self.rtype.to_u32() & 0xff) as u64,
+        }
     }
 
     pub fn to_writer<W>(
@@ -181,13 +401,179 @@ impl Relocation {
     where
         W: Write,
     {
-        elf_write_u64!(eh, io, self.addr)?;
+        elf_write_uclass!(eh, io, self.addr)?;
+        elf_write_uclass!(eh, io, self.info(eh))?;
+        elf_write_uclass!(eh, io, self.addend as u64)?;
+
+        Ok(Self::entsize(eh))
+    }
+
+    /// write as REL entry. the addend is not written,
+    /// it must be stored in the target section with `write_implicit_addend`
+    pub fn to_writer_rel<W>(
+        &self,
+        mut io: W,
+        eh: &Header,
+    ) -> Result<usize, Error>
+    where
+        W: Write,
+    {
+        elf_write_uclass!(eh, io, self.addr)?;
+        elf_write_uclass!(eh, io, self.info(eh))?;
 
-        let info = (self.sym.to_u64().unwrap() << 32) + self.rtype.to_u64().unwrap();
-        elf_write_u64!(eh, io, info)?;
+        Ok(Self::entsize_rel(eh))
+    }
 
-        elf_write_u64!(eh, io, self.addend as u64)?;
+    fn implicit_addend_kind(&self, eh: &Header) -> Result<Option<ImplicitAddend>, Error> {
+        use self::ImplicitAddend::*;
+        let k = match eh.machine {
+            types::Machine::EM386 => match self.rtype {
+                RelocationType::R_386_NONE | RelocationType::R_386_COPY |
+                    RelocationType::R_386_GLOB_DAT | RelocationType::R_386_JMP_SLOT |
+                    RelocationType::R_386_TLS_DESC_CALL => None,
+                RelocationType::R_386_16 | RelocationType::R_386_PC16 => Some(Word16),
+                RelocationType::R_386_8  | RelocationType::R_386_PC8  => Some(Word8),
+                _ => Some(Word32),
+            },
+            types::Machine::ARM => match self.rtype {
+                RelocationType::R_ARM_NONE | RelocationType::R_ARM_COPY |
+                    RelocationType::R_ARM_GLOB_DAT | RelocationType::R_ARM_JUMP_SLOT |
+                    RelocationType::R_ARM_V4BX => None,
+                RelocationType::R_ARM_PC24 | RelocationType::R_ARM_CALL |
+                    RelocationType::R_ARM_JUMP24 | RelocationType::R_ARM_PLT32 => Some(ArmImm24),
+                RelocationType::R_ARM_MOVW_ABS_NC | RelocationType::R_ARM_MOVT_ABS |
+                    RelocationType::R_ARM_MOVW_PREL_NC | RelocationType::R_ARM_MOVT_PREL => Some(ArmImm16),
+                RelocationType::R_ARM_THM_CALL | RelocationType::R_ARM_THM_JUMP24 => Some(ThumbBranch),
+                RelocationType::R_ARM_PREL31 => Some(Prel31),
+                RelocationType::R_ARM_ABS16 => Some(Word16),
+                RelocationType::R_ARM_ABS8  => Some(Word8),
+                _ => Some(Word32),
+            },
+            types::Machine::MIPS => match self.rtype {
+                RelocationType::R_MIPS_NONE | RelocationType::R_MIPS_COPY |
+                    RelocationType::R_MIPS_JUMP_SLOT => None,
+                RelocationType::R_MIPS_16 => Some(Word16),
+                RelocationType::R_MIPS_64 => Some(Word64),
+                RelocationType::R_MIPS_26 => Some(MipsImm26),
+                RelocationType::R_MIPS_HI16 | RelocationType::R_MIPS_LO16 |
+                    RelocationType::R_MIPS_GPREL16 | RelocationType::R_MIPS_LITERAL |
+                    RelocationType::R_MIPS_GOT16 | RelocationType::R_MIPS_PC16 |
+                    RelocationType::R_MIPS_CALL16 | RelocationType::R_MIPS_TLS_GD |
+                    RelocationType::R_MIPS_TLS_LDM | RelocationType::R_MIPS_TLS_DTPREL_HI16 |
+                    RelocationType::R_MIPS_TLS_DTPREL_LO16 | RelocationType::R_MIPS_TLS_GOTTPREL |
+                    RelocationType::R_MIPS_TLS_TPREL_HI16 |
+                    RelocationType::R_MIPS_TLS_TPREL_LO16 => Some(MipsImm16),
+                _ => Some(Word32),
+            },
+            types::Machine::X86_64 => match self.rtype {
+                RelocationType::R_X86_64_NONE | RelocationType::R_X86_64_COPY |
+                    RelocationType::R_X86_64_GLOB_DAT | RelocationType::R_X86_64_JUMP_SLOT |
+                    RelocationType::R_X86_64_TLSDESC_CALL => None,
+                RelocationType::R_X86_64_64 | RelocationType::R_X86_64_RELATIVE |
+                    RelocationType::R_X86_64_DTPMOD64 | RelocationType::R_X86_64_DTPOFF64 |
+                    RelocationType::R_X86_64_TPOFF64 | RelocationType::R_X86_64_PC64 |
+                    RelocationType::R_X86_64_GOTOFF64 | RelocationType::R_X86_64_SIZE64 |
+                    RelocationType::R_X86_64_IRELATIVE |
+                    RelocationType::R_X86_64_RELATIVE64 => Some(Word64),
+                RelocationType::R_X86_64_16 | RelocationType::R_X86_64_PC16 => Some(Word16),
+                RelocationType::R_X86_64_8  | RelocationType::R_X86_64_PC8  => Some(Word8),
+                _ => Some(Word32),
+            },
+            _ => return Err(Error::UnsupportedMachineTypeForRelocation(eh.machine.clone())),
+        };
+        Ok(k)
+    }
+
+    /// the addend of a REL entry, read from the content of the section it applies to.
+    /// for mips HI16 this is only the high half, the low half is in the matching LO16.
+    pub fn implicit_addend(&self, target: &[u8], eh: &Header) -> Result<i64, Error> {
+        use self::ImplicitAddend::*;
+        let kind = match self.implicit_addend_kind(eh)? {
+            None => return Ok(0),
+            Some(k) => k,
+        };
+        let mut io = target.get(self.addr as usize..).ok_or(Error::TruncatedInput)?;
+        let v = match kind {
+            Word8   => io.read_u8()? as i8 as i64,
+            Word16  => io.elf_read_u16(eh)? as i16 as i64,
+            Word32  => io.elf_read_u32(eh)? as i32 as i64,
+            Word64  => io.elf_read_u64(eh)? as i64,
+            ArmImm24 => {
+                let insn = io.elf_read_u32(eh)?;
+                (((insn << 8) as i32) >> 6) as i64
+            },
+            ArmImm16 => {
+                let insn = io.elf_read_u32(eh)?;
+                (((insn >> 4) & 0xf000) | (insn & 0xfff)) as i16 as i64
+            },
+            ThumbBranch => {
+                let hi = io.elf_read_u16(eh)? as u32;
+                let lo = io.elf_read_u16(eh)? as u32;
+                let s  = (hi >> 10) & 1;
+                let i1 = !((lo >> 13) ^ s) & 1;
+                let i2 = !((lo >> 11) ^ s) & 1;
+                let v  = (s << 24) | (i1 << 23) | (i2 << 22) | ((hi & 0x3ff) << 12) | ((lo & 0x7ff) << 1);
+                (((v << 7) as i32) >> 7) as i64
+            },
+            Prel31 => {
+                let w = io.elf_read_u32(eh)?;
+                (((w << 1) as i32) >> 1) as i64
+            },
+            MipsImm26 => {
+                let insn = io.elf_read_u32(eh)?;
+                ((insn & 0x3ffffff) << 2) as i64
+            },
+            MipsImm16 => {
+                let insn = io.elf_read_u32(eh)?;
+                insn as u16 as i16 as i64
+            },
+        };
+        Ok(v)
+    }
 
-        Ok(8+8+8)
+    /// store the addend into the section this REL entry applies to,
+    /// the reverse of `implicit_addend`
+    pub fn write_implicit_addend(&self, target: &mut [u8], eh: &Header) -> Result<(), Error> {
+        use self::ImplicitAddend::*;
+        let kind = match self.implicit_addend_kind(eh)? {
+            None => return Ok(()),
+            Some(k) => k,
+        };
+        let a = self.addend;
+        let w = target.get_mut(self.addr as usize..).ok_or(Error::TruncatedInput)?;
+        let insn = {
+            let mut io = &*w;
+            match kind {
+                Word8 | Word16 | Word64 => 0,
+                // the halfwords are in target order, so keep them apart
+                ThumbBranch => ((io.elf_read_u16(eh)? as u32) << 16) | io.elf_read_u16(eh)? as u32,
+                _ => io.elf_read_u32(eh)?,
+            }
+        };
+        let mut io = w;
+        match kind {
+            Word8   => io.write_u8(a as u8)?,
+            Word16  => elf_write_u16!(eh, io, a as u16)?,
+            Word32  => elf_write_u32!(eh, io, a as u32)?,
+            Word64  => elf_write_u64!(eh, io, a as u64)?,
+            ArmImm24 => elf_write_u32!(eh, io,
+                                       (insn & 0xff000000) | ((a >> 2) as u32 & 0xffffff))?,
+            ArmImm16 => elf_write_u32!(eh, io,
+                                       (insn & 0xfff0f000) | ((a as u32 & 0xf000) << 4) |
+                                       (a as u32 & 0xfff))?,
+            ThumbBranch => {
+                let v  = a as u32;
+                let s  = (v >> 24) & 1;
+                let j1 = (!(v >> 23) ^ s) & 1;
+                let j2 = (!(v >> 22) ^ s) & 1;
+                elf_write_u16!(eh, io, ((insn >> 16) & 0xf800 | (s << 10) | ((v >> 12) & 0x3ff)) as u16)?;
+                elf_write_u16!(eh, io, (insn & 0xd000 | (j1 << 13) | (j2 << 11) | ((v >> 1) & 0x7ff)) as u16)?;
+            },
+            Prel31 => elf_write_u32!(eh, io, (insn & 0x80000000) | (a as u32 & 0x7fffffff))?,
+            MipsImm26 => elf_write_u32!(eh, io,
+                                        (insn & 0xfc000000) | ((a >> 2) as u32 & 0x3ffffff))?,
+            MipsImm16 => elf_write_u32!(eh, io, (insn & 0xffff0000) | (a as u32 & 0xffff))?,
+        };
+        Ok(())
     }
 }
@@ -4,6 +4,11 @@ use relocation::Relocation;
 use dynamic::Dynamic;
 use symbol::Symbol;
 use strtab::Strtab;
+use note::{self, Note};
+use hash::{GnuHash, SysvHash};
+use version::{Verdef, Verneed, Versym};
+use compress;
+use group::Group;
 use types;
 
 use std::io::{Read, Seek, SeekFrom, Write};
@@ -88,6 +93,13 @@ pub enum SectionContent {
     Symbols(Vec<Symbol>),
     Dynamic(Vec<Dynamic>),
     Strtab(Strtab),
+    Notes(Vec<Note>),
+    Hash(SysvHash),
+    GnuHash(GnuHash),
+    Versym(Vec<Versym>),
+    Verdef(Vec<Verdef>),
+    Verneed(Vec<Verneed>),
+    Group(Group),
 }
 
 impl Default for SectionContent {
@@ -168,13 +180,92 @@ impl SectionContent {
             _ => None,
         }
     }
+    pub fn as_notes(&self) -> Option<&Vec<Note>> {
+        match self {
+            SectionContent::Notes(v) => Some(v),
+            _ => None,
+        }
+    }
+    pub fn as_notes_mut(&mut self) -> Option<&mut Vec<Note>> {
+        match self {
+            &mut SectionContent::Notes(ref mut v) => Some(v),
+            _ => None,
+        }
+    }
+    pub fn into_notes(self) -> Option<Vec<Note>> {
+        match self {
+            SectionContent::Notes(v) => Some(v),
+            _ => None,
+        }
+    }
+    pub fn as_hash(&self) -> Option<&SysvHash> {
+        match self {
+            SectionContent::Hash(v) => Some(v),
+            _ => None,
+        }
+    }
+    pub fn as_gnu_hash(&self) -> Option<&GnuHash> {
+        match self {
+            SectionContent::GnuHash(v) => Some(v),
+            _ => None,
+        }
+    }
+    pub fn as_versym(&self) -> Option<&Vec<Versym>> {
+        match self {
+            SectionContent::Versym(v) => Some(v),
+            _ => None,
+        }
+    }
+    pub fn as_versym_mut(&mut self) -> Option<&mut Vec<Versym>> {
+        match self {
+            &mut SectionContent::Versym(ref mut v) => Some(v),
+            _ => None,
+        }
+    }
+    pub fn as_verdef(&self) -> Option<&Vec<Verdef>> {
+        match self {
+            SectionContent::Verdef(v) => Some(v),
+            _ => None,
+        }
+    }
+    pub fn as_verdef_mut(&mut self) -> Option<&mut Vec<Verdef>> {
+        match self {
+            &mut SectionContent::Verdef(ref mut v) => Some(v),
+            _ => None,
+        }
+    }
+    pub fn as_verneed(&self) -> Option<&Vec<Verneed>> {
+        match self {
+            SectionContent::Verneed(v) => Some(v),
+            _ => None,
+        }
+    }
+    pub fn as_verneed_mut(&mut self) -> Option<&mut Vec<Verneed>> {
+        match self {
+            &mut SectionContent::Verneed(ref mut v) => Some(v),
+            _ => None,
+        }
+    }
+    pub fn as_group(&self) -> Option<&Group> {
+        match self {
+            SectionContent::Group(v) => Some(v),
+            _ => None,
+        }
+    }
+    pub fn as_group_mut(&mut self) -> Option<&mut Group> {
+        match self {
+            &mut SectionContent::Group(ref mut v) => Some(v),
+            _ => None,
+        }
+    }
     pub fn into_raw(self) -> Option<Vec<u8>> {
         match self {
             SectionContent::Raw(v) => Some(v),
             _ => None,
         }
     }
-    pub fn size(&self, eh: &Header) -> usize {
+    // notes are padded to the alignment of their section, so only Section::size can call this
+    fn size(&self, eh: &Header, note_align: u64) -> usize {
         match self {
             &SectionContent::Unloaded => panic!("cannot size unloaded section"),
             &SectionContent::None => 0,
@@ -183,6 +274,13 @@ impl SectionContent {
             &SectionContent::Strtab(ref v) => v.len(eh),
             &SectionContent::Symbols(ref v) => v.len() * Symbol::entsize(eh),
             &SectionContent::Relocations(ref v) => v.len() * Relocation::entsize(eh),
+            SectionContent::Notes(v) => v.iter().map(|n| n.size(eh, note_align)).sum(),
+            SectionContent::Hash(v) => v.size(),
+            SectionContent::GnuHash(v) => v.size(eh),
+            SectionContent::Versym(v) => v.len() * Versym::entsize(eh),
+            SectionContent::Verdef(v) => v.iter().map(|v| v.size()).sum(),
+            SectionContent::Verneed(v) => v.iter().map(|v| v.size()).sum(),
+            SectionContent::Group(v) => v.size(),
         }
     }
 }
@@ -198,7 +296,12 @@ pub struct Section {
 
 impl Section {
     pub fn size(&self, eh: &Header) -> usize {
-        self.content.size(eh)
+        match (&self.header.shtype, &self.content) {
+            (&types::SectionType::REL, SectionContent::Relocations(v)) => {
+                v.len() * Relocation::entsize_rel(eh)
+            },
+            _ => self.content.size(eh, note::align(self.header.addralign)),
+        }
     }
     pub fn new(
         name:       Vec<u8>,
@@ -237,15 +340,16 @@ impl Section {
                 return Err(Error::SyncingUnloadedSection);
             },
             SectionContent::Relocations(_) => {
-                self.header.entsize = Relocation::entsize(eh) as u64;
+                self.header.entsize = if self.header.shtype == types::SectionType::REL {
+                    Relocation::entsize_rel(eh) as u64
+                } else {
+                    Relocation::entsize(eh) as u64
+                };
             }
             SectionContent::Symbols(ref mut vv) => {
-                for (i, sym) in vv.iter().enumerate() {
-                    if sym.bind == types::SymbolBind::GLOBAL {
-                        self.header.info = i as u32;
-                        break;
-                    }
-                }
+                // one greater than the last local symbol
+                self.header.info = vv.iter().position(|sym| sym.bind != types::SymbolBind::LOCAL)
+                    .unwrap_or(vv.len()) as u32;
                 for v in vv {
                     v.sync(linked.as_mut().map(|r| &mut **r), eh)?;
                 }
@@ -260,7 +364,29 @@ impl Section {
             SectionContent::Strtab(_) => {
                 self.header.entsize = Strtab::entsize(eh) as u64;
             }
-            SectionContent::None | SectionContent::Raw(_) => {}
+            SectionContent::Hash(_) => {
+                self.header.entsize = 4;
+            }
+            SectionContent::Versym(_) => {
+                self.header.entsize = Versym::entsize(eh) as u64;
+            }
+            SectionContent::Group(_) => {
+                self.header.entsize = Group::entsize(eh) as u64;
+            }
+            SectionContent::Verdef(ref mut vv) => {
+                for v in vv.iter_mut() {
+                    v.sync(linked.as_deref_mut(), eh)?;
+                }
+                self.header.info = vv.len() as u32;
+            }
+            SectionContent::Verneed(ref mut vv) => {
+                for v in vv.iter_mut() {
+                    v.sync(linked.as_deref_mut(), eh)?;
+                }
+                self.header.info = vv.len() as u32;
+            }
+            SectionContent::None | SectionContent::Raw(_) | SectionContent::Notes(_) |
+                SectionContent::GnuHash(_) => {}
         }
         if self.header.shtype != types::SectionType::NOBITS {
             self.header.size = self.size(eh) as u64;
@@ -268,11 +394,33 @@ impl Section {
         Ok(())
     }
 
+    /// load the content. SHF_COMPRESSED sections are loaded as Raw, including their chdr
     pub fn from_reader<T>(
+        &mut self,
+        io: T,
+        linked: Option<&Section>,
+        eh: &Header,
+    ) -> Result<(), Error> where T: Read + Seek {
+        self.read_content(io, linked, eh, false)
+    }
+
+    /// load the content like from_reader, but SHF_COMPRESSED sections are decompressed
+    /// first and then parsed like any other section of their type
+    pub fn from_reader_decompressed<T>(
+        &mut self,
+        io: T,
+        linked: Option<&Section>,
+        eh: &Header,
+    ) -> Result<(), Error> where T: Read + Seek {
+        self.read_content(io, linked, eh, true)
+    }
+
+    fn read_content<T>(
         &mut self,
         mut io: T,
         linked: Option<&Section>,
         eh: &Header,
+        decompress: bool,
     ) -> Result<(), Error> where T: Read + Seek {
         match self.content {
             SectionContent::Unloaded => {},
@@ -285,6 +433,17 @@ impl Section {
         io.seek(SeekFrom::Start(self.header.offset))?;
         let mut bb = vec![0; self.header.size as usize];
         io.read_exact(&mut bb)?;
+        if self.header.flags.contains(types::SectionFlags::COMPRESSED) {
+            if !decompress {
+                self.content = SectionContent::Raw(bb);
+                return Ok(());
+            }
+            let (chdr, data) = compress::decompress(&bb, eh)?;
+            self.header.flags.remove(types::SectionFlags::COMPRESSED);
+            self.header.addralign = chdr.addralign;
+            self.header.size = data.len() as u64;
+            bb = data;
+        }
         let linked = linked.map(|s|&s.content);
         self.content = match self.header.shtype {
             types::SectionType::NOBITS => {
@@ -298,6 +457,10 @@ impl Section {
                 let io = bb.as_slice();
                 Relocation::from_reader(io, linked, eh)?
             }
+            types::SectionType::REL => {
+                let io = bb.as_slice();
+                Relocation::from_reader_rel(io, linked, eh)?
+            }
             types::SectionType::SYMTAB | types::SectionType::DYNSYM => {
                 let io = bb.as_slice();
                 Symbol::from_reader(io, linked, eh)?
@@ -306,6 +469,31 @@ impl Section {
                 let io = bb.as_slice();
                 Dynamic::from_reader(io, linked, eh)?
             }
+            types::SectionType::NOTE => {
+                let io = bb.as_slice();
+                SectionContent::Notes(Note::from_reader(io, eh, note::align(self.header.addralign))?)
+            }
+            // some 64bit targets use 8 byte hash words, which we don't parse
+            types::SectionType::HASH if self.header.entsize != 8 => {
+                let io = bb.as_slice();
+                SectionContent::Hash(SysvHash::from_reader(io, eh)?)
+            }
+            types::SectionType::GNU_HASH => {
+                let io = bb.as_slice();
+                SectionContent::GnuHash(GnuHash::from_reader(io, eh)?)
+            }
+            types::SectionType::GNU_VERSYM => {
+                Versym::from_reader(bb.as_slice(), linked, eh)?
+            }
+            types::SectionType::GNU_VERDEF => {
+                Verdef::from_reader(bb.as_slice(), linked, eh)?
+            }
+            types::SectionType::GNU_VERNEED => {
+                Verneed::from_reader(bb.as_slice(), linked, eh)?
+            }
+            types::SectionType::GROUP => {
+                SectionContent::Group(Group::from_reader(bb.as_slice(), eh)?)
+            }
             _ => {
                 SectionContent::Raw(bb)
             }
@@ -313,6 +501,44 @@ impl Section {
         Ok(())
     }
 
+    /// decompress a loaded SHF_COMPRESSED section. the content stays Raw
+    pub fn decompress(&mut self, eh: &Header) -> Result<(), Error> {
+        if !self.header.flags.contains(types::SectionFlags::COMPRESSED) {
+            return Ok(());
+        }
+        let (chdr, data) = match self.content {
+            SectionContent::Raw(ref raw) => compress::decompress(raw, eh)?,
+            _ => return Err(Error::UnexpectedSectionContent),
+        };
+        self.header.flags.remove(types::SectionFlags::COMPRESSED);
+        self.header.addralign = chdr.addralign;
+        self.header.size = data.len() as u64;
+        self.content = SectionContent::Raw(data);
+        Ok(())
+    }
+
+    /// compress the Raw content of a section and set SHF_COMPRESSED.
+    /// sections that are loaded into memory can't be compressed
+    pub fn compress(&mut self, chtype: types::CompressionType, eh: &Header) -> Result<(), Error> {
+        if self.header.flags.contains(types::SectionFlags::COMPRESSED) {
+            return Ok(());
+        }
+        if self.header.flags.contains(types::SectionFlags::ALLOC) {
+            return Err(Error::CompressingAllocSection);
+        }
+        let data = match self.content {
+            SectionContent::Raw(ref raw) => compress::compress(raw, chtype, self.header.addralign, eh)?,
+            _ => return Err(Error::UnexpectedSectionContent),
+        };
+        self.header.flags.insert(types::SectionFlags::COMPRESSED);
+        self.header.addralign = match eh.ident_class {
+            types::Class::Class64 => 8,
+            types::Class::Class32 => 4,
+        };
+        self.header.size = data.len() as u64;
+        self.content = SectionContent::Raw(data);
+        Ok(())
+    }
 
     pub fn to_writer<R>(
         &self,
@@ -332,7 +558,11 @@ impl Section {
             &SectionContent::Relocations(ref vv) => {
                 let mut rs = 0;
                 for v in vv {
-                    rs += v.to_writer(&mut io, eh)?;
+                    rs += if self.header.shtype == types::SectionType::REL {
+                        v.to_writer_rel(&mut io, eh)?
+                    } else {
+                        v.to_writer(&mut io, eh)?
+                    };
                 }
                 rs
             }
@@ -353,6 +583,44 @@ impl Section {
             &SectionContent::Strtab(ref v) => {
                 v.to_writer(&mut io, eh)?
             }
+            SectionContent::Notes(vv) => {
+                let align = note::align(self.header.addralign);
+                let mut rs = 0;
+                for v in vv {
+                    rs += v.to_writer(&mut io, eh, align)?;
+                }
+                rs
+            }
+            SectionContent::Hash(v) => {
+                v.to_writer(&mut io, eh)?
+            }
+            SectionContent::GnuHash(v) => {
+                v.to_writer(&mut io, eh)?
+            }
+            SectionContent::Versym(vv) => {
+                let mut rs = 0;
+                for v in vv {
+                    rs += v.to_writer(&mut io, eh)?;
+                }
+                rs
+            }
+            SectionContent::Verdef(vv) => {
+                let mut rs = 0;
+                for (i, v) in vv.iter().enumerate() {
+                    rs += v.to_writer(&mut io, eh, i + 1 == vv.len())?;
+                }
+                rs
+            }
+            SectionContent::Verneed(vv) => {
+                let mut rs = 0;
+                for (i, v) in vv.iter().enumerate() {
+                    rs += v.to_writer(&mut io, eh, i + 1 == vv.len())?;
+                }
+                rs
+            }
+            SectionContent::Group(v) => {
+                v.to_writer(&mut io, eh)?
+            }
             &SectionContent::None => {
                 0
             },
@@ -363,10 +631,10 @@ impl Section {
 
         assert_eq!(
             io.seek(SeekFrom::Current(0))?,
-            self.header.offset + self.content.size(eh) as u64,
+            self.header.offset + self.size(eh) as u64,
             "writing {} with header.size {} and content.size {} returned a written size {}",
             String::from_utf8_lossy(&self.name),
-            self.content.size(eh),
+            self.size(eh),
             self.header.size,
             rs
             );
@@ -1,14 +1,16 @@
 use std::io::{Read, Write};
-use {types, Error, Header, SectionContent};
+use {types, hash, Error, Header, SectionContent};
+use section::Section;
 use utils::hextab;
 use num_traits::{FromPrimitive, ToPrimitive};
 use strtab::Strtab;
-use section::{Section, SectionHeader};
 use std::fmt;
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum SymbolSectionIndex {
-    Section(u16), // 1-6551
+    /// 1-0xfeff are stored in the symbol directly.
+    /// anything larger is stored in the SYMTAB_SHNDX section linked to the symbol table
+    Section(u32),
     Undefined,    // 0
     Absolute,     // 65521,
     Common,       // 6552,
@@ -53,58 +55,99 @@ impl fmt::Debug for Symbol {
     }
 }
 
-impl Symbol {
-    fn from_val(
-        tab:    Option<&Strtab>,
-        _name:  u32,
-        info:   u8,
-        other:  u8,
-        shndx:  u16,
-        value:  u64,
-        size:   u64,
-    ) -> Result<Symbol, Error> {
-        let name = match tab {
-            Some(tab) => tab.get(_name as usize),
-            None => Vec::default(),
-        };
+/// a symbol table entry exactly as it is stored, before resolving its name or indices
+pub(crate) struct SymbolEntry {
+    pub _name:  u32,
+    pub info:   u8,
+    pub other:  u8,
+    pub shndx:  u16,
+    pub value:  u64,
+    pub size:   u64,
+}
 
-        let shndx = match shndx {
-            0 => SymbolSectionIndex::Undefined,
-            65521 => SymbolSectionIndex::Absolute,
-            65522 => SymbolSectionIndex::Common,
-            _ if shndx > 0 && shndx < 6552 => SymbolSectionIndex::Section(shndx),
-            _ => return Err(Error::InvalidSymbolShndx(String::from_utf8_lossy(&name).into_owned(), shndx)),
+impl SymbolEntry {
+    pub(crate) fn from_bytes(b: &[u8], eh: &Header) -> Result<SymbolEntry, Error> {
+        let mut br = b;
+        elf_dispatch_endianness!(eh => {
+            let _name = read_u32(&mut br)?;
+            Ok(match eh.ident_class {
+                types::Class::Class64 => {
+                    let info  = br.read_u8()?;
+                    let other = br.read_u8()?;
+                    let shndx = read_u16(&mut br)?;
+                    let value = read_u64(&mut br)?;
+                    let size  = read_u64(&mut br)?;
+
+                    SymbolEntry{_name, info, other, shndx, value, size}
+                }
+                types::Class::Class32 => {
+                    let value = read_u32(&mut br)?;
+                    let size  = read_u32(&mut br)?;
+                    let info  = br.read_u8()?;
+                    let other = br.read_u8()?;
+                    let shndx = read_u16(&mut br)?;
+
+                    SymbolEntry{_name, info, other, shndx, value: value as u64, size: size as u64}
+                }
+            })
+        })
+    }
+
+    pub(crate) fn decode(&self, name: &[u8])
+        -> Result<(SymbolSectionIndex, types::SymbolType, types::SymbolBind, types::SymbolVis), Error> {
+
+        let shndx = match self.shndx {
+            types::SHN_UNDEF => SymbolSectionIndex::Undefined,
+            types::SHN_ABS => SymbolSectionIndex::Absolute,
+            types::SHN_COMMON => SymbolSectionIndex::Common,
+            // placeholder until the SYMTAB_SHNDX entry is applied, see Elf::load
+            types::SHN_XINDEX => SymbolSectionIndex::Section(types::SHN_XINDEX as u32),
+            shndx if shndx < types::SHN_LORESERVE => SymbolSectionIndex::Section(shndx as u32),
+            shndx => return Err(Error::InvalidSymbolShndx(String::from_utf8_lossy(name).into_owned(), shndx)),
         };
 
-        let reb = info & 0xf;
+        let reb = self.info & 0xf;
         let stype = match types::SymbolType::from_u8(reb) {
             Some(v) => v,
             None => return Err(Error::InvalidSymbolType(reb)),
         };
 
-        let reb = info >> 4;
+        let reb = self.info >> 4;
         let bind = match types::SymbolBind::from_u8(reb) {
             Some(v) => v,
             None => return Err(Error::InvalidSymbolBind(reb)),
         };
 
-        let reb = other & 0x3;
+        let reb = self.other & 0x3;
         let vis = match types::SymbolVis::from_u8(reb) {
             Some(v) => v,
             None => return Err(Error::InvalidSymbolVis(reb)),
         };
 
+        Ok((shndx, stype, bind, vis))
+    }
+}
+
+impl Symbol {
+    fn from_val(tab: Option<&Strtab>, e: SymbolEntry) -> Result<Symbol, Error> {
+        let name = match tab {
+            Some(tab) => tab.get(e._name as usize),
+            None => Vec::default(),
+        };
+
+        let (shndx, stype, bind, vis) = e.decode(&name)?;
+
         Ok(Symbol {
             shndx: shndx,
-            value: value,
-            size: size,
+            value: e.value,
+            size: e.size,
 
             name: name,
             stype: stype,
             bind: bind,
             vis: vis,
 
-            _name: _name,
+            _name: e._name,
         })
     }
 
@@ -135,32 +178,7 @@ impl Symbol {
         let mut r = Vec::new();
         let mut b = vec![0; Self::entsize(eh)];
         while io.read(&mut b)? > 0 {
-            let mut br = &b[..];
-            elf_dispatch_endianness!(eh => {
-                let _name = read_u32(&mut br)?;
-                r.push(match eh.ident_class {
-                    types::Class::Class64 => {
-                        let info = b[4];
-                        let other = b[5];
-                        br = &b[6..];
-                        let shndx = read_u16(&mut br)?;
-                        let value = read_u64(&mut br)?;
-                        let size  = read_u64(&mut br)?;
-
-                        Symbol::from_val(tab, _name, info, other, shndx, value, size)?
-                    }
-                    types::Class::Class32 => {
-                        let value = read_u32(&mut br)?;
-                        let size  = read_u32(&mut br)?;
-                        let info  = b[12];
-                        let other = b[13];
-                        br = &b[14..];
-                        let shndx = read_u16(&mut br)?;
-
-                        Symbol::from_val(tab, _name, info, other, shndx, value as u64, size as u64)?
-                    }
-                })
-            })
+            r.push(Symbol::from_val(tab, SymbolEntry::from_bytes(&b, eh)?)?);
         }
 
         Ok(SectionContent::Symbols(r))
@@ -178,10 +196,11 @@ impl Symbol {
         let other = self.vis.to_u8().unwrap();
 
         let shndx = match self.shndx {
-            SymbolSectionIndex::Section(i) => i,
-            SymbolSectionIndex::Undefined => 0,
-            SymbolSectionIndex::Absolute => 65521,
-            SymbolSectionIndex::Common => 65522,
+            SymbolSectionIndex::Section(i) if i >= types::SHN_LORESERVE as u32 => types::SHN_XINDEX,
+            SymbolSectionIndex::Section(i) => i as u16,
+            SymbolSectionIndex::Undefined => types::SHN_UNDEF,
+            SymbolSectionIndex::Absolute => types::SHN_ABS,
+            SymbolSectionIndex::Common => types::SHN_COMMON,
         };
 
         elf_write_u32!(eh, io, self._name)?;
@@ -206,6 +225,14 @@ impl Symbol {
         })
     }
 
+    /// the SYMTAB_SHNDX entry for this symbol. 0 if the index fits into the symbol itself
+    pub fn xindex(&self) -> u32 {
+        match self.shndx {
+            SymbolSectionIndex::Section(i) if i >= types::SHN_LORESERVE as u32 => i,
+            _ => 0,
+        }
+    }
+
     pub fn sync(&mut self, linked: Option<&mut SectionContent>, _: &Header) -> Result<(), Error> {
         match linked {
             Some(&mut SectionContent::Strtab(ref mut strtab)) => {
@@ -220,59 +247,17 @@ impl Symbol {
     }
 }
 
+#[deprecated(note = "use hash::sysv_hash, which hashes bytes into the 32 bit value ELF uses")]
+#[allow(clippy::ptr_arg)]
 pub fn sysv_hash(s: &String) -> u64 {
-    let mut h: u64 = 0;
-    let mut g: u64;
-
-    for byte in s.bytes() {
-        h = (h << 4) + byte as u64;
-        g = h & 0xf0000000;
-        if g > 0 {
-            h ^= g >> 24;
-        }
-        h &= !g;
-    }
-    return h;
+    hash::sysv_hash(s.as_bytes()) as u64
 }
 
-
-pub fn symhash(eh: &Header, symbols: &Vec<Symbol>, link: u32) -> Result<Section, Error> {
-    assert!(symbols.len() > 0);
-    //TODO i'm too lazy to do this correctly now, so we'll just emit a hashtable with nbuckets  == 1
-    let mut b = Vec::new();
-    {
-        let io = &mut b;
-        elf_write_uclass!(eh, io, 1)?; //nbuckets
-        elf_write_uclass!(eh, io, symbols.len() as u64)?; //nchains
-
-        elf_write_uclass!(eh, io, 1)?; //the bucket. pointing at symbol 1
-
-        elf_write_uclass!(eh, io, 0)?; //symbol 0
-
-        //the chains. every symbol just points at the next, because nbuckets == 1
-        for i in 1..symbols.len() - 1 {
-            elf_write_uclass!(eh, io, i as u64 + 1)?;
-        }
-
-        //except the last one
-        elf_write_uclass!(eh, io, 0)?;
-    }
-
-    Ok(Section {
-        name: b".hash".to_vec(),
-        header: SectionHeader {
-            name: 0,
-            shtype: types::SectionType::HASH,
-            flags: types::SectionFlags::ALLOC,
-            addr: 0,
-            offset: 0,
-            size: b.len() as u64,
-            link: link,
-            info: 0,
-            addralign: 0,
-            entsize: 8, // or 4 for CLass32
-        },
-        content: SectionContent::Raw(b),
-        addrlock: false,
-    })
+#[deprecated(note = "use SectionContent::Hash with hash::SysvHash::new, which builds an actual hash table")]
+#[allow(clippy::ptr_arg)]
+pub fn symhash(_: &Header, symbols: &Vec<Symbol>, link: u32) -> Result<Section, Error> {
+    let mut sec = Section::new(b".hash".to_vec(), types::SectionType::HASH, types::SectionFlags::ALLOC,
+                               SectionContent::Hash(hash::SysvHash::new(symbols)), link, 0);
+    sec.header.addralign = 8;
+    Ok(sec)
 }
@@ -7,6 +7,7 @@ use std::collections::hash_map::{self, HashMap};
 use std::collections::HashSet;
 use loader::{self, Loader};
 use std::sync::atomic::{self, AtomicUsize};
+use std::sync::Mutex;
 
 pub type LinkGlobalId = usize;
 
@@ -39,6 +40,43 @@ pub struct Object {
     oid:        LinkGlobalId,
 }
 
+/// a file on the command line, with the options that were in effect where it appeared
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Input {
+    pub path:           String,
+    /// link every member of an archive, not just the ones that resolve something
+    pub whole_archive:  bool,
+    /// only link a shared object if it resolves a symbol that is undefined at that point
+    pub as_needed:      bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LinkInput {
+    File(Input),
+    /// archives in a group are searched over and over, until none of them resolves anything new
+    Group(Vec<Input>),
+}
+
+/// which sections identical code folding may fold
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Icf {
+    None,
+    /// only functions whose address isn't taken, so pointers to different functions still differ
+    Safe,
+    All,
+}
+
+/// what a relocation refers to, when comparing sections for icf
+#[derive(Hash, PartialEq, Eq)]
+enum IcfTarget {
+    /// a section that may be folded, by its class, and the offset into it
+    Class(usize, u64),
+    /// any other section, and the offset into it
+    Object(LinkGlobalId, u64),
+    /// anything else is the same only if it is the same symbol
+    Symbol(usize),
+}
+
 #[derive(Default)]
 pub struct SymbolicLinker {
     pub objects: HashMap<LinkGlobalId, Object>,
@@ -48,14 +86,62 @@ pub struct SymbolicLinker {
     lid_counter: AtomicUsize,
 
     objects_seen: HashSet<String>,
+
+    /// sonames of the shared objects linked against, in link order
+    pub needed:  Vec<Vec<u8>>,
+
+    /// symbols exported by the shared objects, which are resolved at runtime.
+    /// the first definition wins
+    pub shared:  HashMap<Vec<u8>, symbol::Symbol>,
+
+    /// leave undefined references to the dynamic linker instead of failing, like shared objects do
+    pub allow_undefined: bool,
+
+    /// references to these are only made to pull in a definition, so it's up to the caller
+    /// what to do when there is none. like the entry point, which gnuld only warns about
+    pub optional: HashSet<Vec<u8>>,
+
+    /// undefined references to these go to __wrap_sym instead, and references to __real_sym to sym
+    pub wrap:    HashSet<Vec<u8>>,
+
+    /// report common symbols that are merged with or overridden by others in warnings
+    pub warn_common: bool,
+
+    /// diagnostics that don't stop the link, for the caller to show
+    pub warnings: Vec<String>,
+
+    /// signatures of the COMDAT groups that have been kept,
+    /// with the lids of their member sections by section name
+    comdat:      HashMap<Vec<u8>, HashMap<Vec<u8>, LinkGlobalId>>,
+}
+
+/// the first error the loader reports while it goes through the inputs in parallel,
+/// so the link can fail with it once the loader is done
+#[derive(Default)]
+struct LoadErrors(Mutex<Option<Error>>);
+
+impl LoadErrors {
+    fn report(&self, e: Error, name: String) -> Vec<loader::State> {
+        let mut first = self.0.lock().unwrap();
+        if first.is_none() {
+            *first = Some(Error::LoadFailed{name, cause: Box::new(e)});
+        }
+        Vec::new()
+    }
+
+    fn check(&self) -> Result<(), Error> {
+        match self.0.lock().unwrap().take() {
+            Some(e) => Err(e),
+            None    => Ok(()),
+        }
+    }
 }
 
 impl SymbolicLinker {
     pub fn link_all(&mut self, loader: Vec<loader::State>) -> Result<(), Error> {
-        let loader = loader.load_all(&|e,name| {
-            println!("elfkit::Linker {:?} while loading {}", e, name);
-            Vec::with_capacity(0)
-        });
+        let errors = LoadErrors::default();
+        let loader = loader.load_all(&|e,name| errors.report(e, name));
+        errors.check()?;
         self.objects.reserve(loader.len());
         for ma in loader {
             if let loader::State::Object{name, hash, header, symbols, sections} = ma {
@@ -68,39 +154,166 @@ impl SymbolicLinker {
     }
     pub fn link(&mut self, mut loader: Vec<loader::State>) -> Result<(), Error> {
         loop {
-            let (l2, matches) = self.link_iteration(loader);
+            let (l2, matches) = self.link_iteration(loader)?;
             loader = l2;
             if matches.len() == 0 {
-                for link in self.symtab.iter() {
-                    if link.sym.shndx == symbol::SymbolSectionIndex::Undefined &&
-                        link.sym.bind == types::SymbolBind::GLOBAL {
-                        return Err(Error::UndefinedReference{
-                            obj: self.objects[&link.obj].name.clone(),
-                            sym: String::from_utf8_lossy(&link.sym.name).into_owned(),
-                        });
-                    }
+                if self.allow_undefined {
+                    break;
                 }
-                break;
+                return self.check_undefined();
             }
 
             self.objects.reserve(matches.len());
             for ma in matches {
-                if let loader::State::Object{name, hash, header, symbols, sections} = ma {
-                    if self.objects_seen.insert(hash.clone()) {
-                        self.insert_object(name, hash, header, symbols, sections)?;
-                    }
+                self.insert_state(ma)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// link the inputs in order, like gnuld does.
+    /// archives only resolve references from what came before them, unless they are in a group
+    pub fn link_inputs(&mut self, inputs: Vec<LinkInput>) -> Result<(), Error> {
+        for input in inputs {
+            match input {
+                LinkInput::File(file)   => self.link_group(vec![file])?,
+                LinkInput::Group(files) => self.link_group(files)?,
+            }
+        }
+        if self.allow_undefined {
+            return Ok(());
+        }
+        self.check_undefined()
+    }
+
+    fn link_group(&mut self, files: Vec<Input>) -> Result<(), Error> {
+        let errors = LoadErrors::default();
+        let e = |e: Error, name: String| errors.report(e, name);
+
+        // what's left of each archive, and the shared objects that are only linked as needed
+        let mut pending = Vec::with_capacity(files.len());
+        for file in files {
+            let mut rest = Vec::new();
+            let states = vec![loader::State::Path{name: file.path.clone()}].load_objects(&e);
+            errors.check()?;
+            for state in states {
+                match state {
+                    loader::State::Object{..} => self.insert_state(state)?,
+                    loader::State::Shared{..} if !file.as_needed => self.insert_state(state)?,
+                    loader::State::Archive{..} if file.whole_archive => {
+                        let members = vec![state].load_all(&e);
+                        errors.check()?;
+                        for member in members {
+                            self.insert_state(member)?;
+                        }
+                    },
+                    any => rest.push(any),
+                }
+            }
+            self.search(&mut rest)?;
+            pending.push(rest);
+        }
+
+        while pending.len() > 1 {
+            let mut again = false;
+            for rest in &mut pending {
+                again |= self.search(rest)?;
+            }
+            if !again {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// link whatever resolves a currently undefined reference, until nothing does.
+    /// returns whether anything was linked
+    fn search(&mut self, states: &mut Vec<loader::State>) -> Result<bool, Error> {
+        let mut linked = false;
+        loop {
+            // load_if takes shared objects as they are, so they're decided on here
+            let mut matches = Vec::new();
+            let mut shared  = Vec::new();
+            let mut rest    = Vec::new();
+            for state in std::mem::take(states) {
+                match state {
+                    loader::State::Shared{..} if self.resolves(&state) => matches.push(state),
+                    loader::State::Shared{..} => shared.push(state),
+                    any => rest.push(any),
                 }
             }
+            let (mut rest, mut pulled) = self.link_iteration(rest)?;
+            rest.append(&mut shared);
+            *states = rest;
+            matches.append(&mut pulled);
+
+            if matches.is_empty() {
+                return Ok(linked);
+            }
+            linked = true;
+            self.objects.reserve(matches.len());
+            for ma in matches {
+                self.insert_state(ma)?;
+            }
+        }
+    }
+
+    /// whether a shared object defines any symbol that is still undefined
+    fn resolves(&self, state: &loader::State) -> bool {
+        let symbols = match *state {
+            loader::State::Shared{ref symbols, ..} => symbols,
+            _ => return false,
+        };
+        symbols.iter().any(|sym| match self.lookup.get(&sym.name) {
+            Some(&i) => {
+                let link = &self.symtab[i];
+                link.sym.shndx == symbol::SymbolSectionIndex::Undefined &&
+                    link.sym.bind == types::SymbolBind::GLOBAL &&
+                    !self.shared.contains_key(&link.sym.name)
+            },
+            None => false,
+        })
+    }
+
+    fn check_undefined(&self) -> Result<(), Error> {
+        for link in self.symtab.iter() {
+            if link.sym.shndx == symbol::SymbolSectionIndex::Undefined &&
+                link.sym.bind == types::SymbolBind::GLOBAL &&
+                !self.shared.contains_key(&link.sym.name) &&
+                !self.optional.contains(&link.sym.name) {
+                return Err(Error::UndefinedReference{
+                    obj: self.objects[&link.obj].name.clone(),
+                    sym: String::from_utf8_lossy(&link.sym.name).into_owned(),
+                });
+            }
         }
         Ok(())
     }
 
-    fn link_iteration(&mut self, loader: Vec<loader::State>) -> (Vec<loader::State>, Vec<loader::State>) {
+    fn insert_state(&mut self, state: loader::State) -> Result<(), Error> {
+        match state {
+            loader::State::Object{name, hash, header, symbols, sections}
+                if self.objects_seen.insert(hash.clone()) => {
+                    self.insert_object(name, hash, header, symbols, sections)?;
+                },
+            loader::State::Shared{hash, soname, symbols, ..}
+                if self.objects_seen.insert(hash.clone()) => {
+                    self.insert_shared(soname, symbols);
+                },
+            _ => {},
+        }
+        Ok(())
+    }
+
+    fn link_iteration(&mut self, loader: Vec<loader::State>)
+        -> Result<(Vec<loader::State>, Vec<loader::State>), Error> {
+        let errors = LoadErrors::default();
         let (state2, matches) : (Vec<loader::State>, Vec<loader::State>) = {
             let undefined_refs = self.symtab.iter().filter_map(|link|{
                 match link.sym.shndx {
                     symbol::SymbolSectionIndex::Undefined => {
-                        if link.sym.bind == types::SymbolBind::GLOBAL {
+                        if link.sym.bind == types::SymbolBind::GLOBAL &&
+                            !self.shared.contains_key(&link.sym.name) {
                             Some(link.sym.name.as_ref())
                         } else {
                             None
@@ -115,23 +328,23 @@ impl SymbolicLinker {
                 }
             }).collect();
 
-            loader.load_if(&undefined_refs, &|e,name| {
-                println!("elfkit::Linker {:?} while loading {}", e, name);
-                Vec::with_capacity(0)
-            })
+            loader.load_if(&undefined_refs, &|e,name| errors.report(e, name))
         };
-        (state2, matches)
+        errors.check()?;
+        Ok((state2, matches))
     }
 
     fn insert_object(&mut self, name: String, hash:String, header: Header, symbols: Vec<symbol::Symbol>,
                      sections: Vec<(usize, section::Section, Vec<relocation::Relocation>)>)
         -> Result<(), Error>  {
 
-        assert!((sections.len() as u16) <= header.shnum,
+        assert!((sections.len() as u32) <= header.shnum,
         "incoming object header.shnum is {} but loader gave us {} sections ", header.shnum, sections.len());
         let lid_base = self.lid_counter.fetch_add(header.shnum as usize, atomic::Ordering::Acquire);
 
-        let locations = match self.link_locations(lid_base, symbols) {
+        let discarded = self.comdat_groups(lid_base, &symbols, &sections);
+
+        let locations = match self.link_locations(lid_base, name.split("/").last().unwrap(), symbols, &discarded) {
             Ok(v) => v,
             Err(Error::ConflictingSymbol{sym, obj1_name, obj1_hash, ..}) => {
                 return Err(Error::ConflictingSymbol{sym, obj1_name, obj2_name:name,
@@ -143,6 +356,10 @@ impl SymbolicLinker {
 
         let name = name.split("/").last().unwrap().to_owned();
         for (sec_shndx, sec, mut relocs) in sections {
+            // groups are resolved by now. discarded members take their relocations with them
+            if sec.header.shtype == types::SectionType::GROUP || discarded.contains_key(&sec_shndx) {
+                continue;
+            }
 
             // point the relocs at the global symtab
             for reloc in &mut relocs {
@@ -179,13 +396,97 @@ impl SymbolicLinker {
         Ok(())
     }
 
-    fn link_locations(&mut self, lid_base: LinkGlobalId, symbols: Vec<symbol::Symbol>)
+    fn insert_shared(&mut self, soname: Vec<u8>, symbols: Vec<symbol::Symbol>) {
+        if !self.needed.contains(&soname) {
+            self.needed.push(soname);
+        }
+        for sym in symbols {
+            if let hash_map::Entry::Vacant(e) = self.shared.entry(sym.name.clone()) {
+                e.insert(sym);
+            }
+        }
+    }
+
+    /// keep only the first COMDAT group with any given signature.
+    /// returns the section indices of the discarded members of this object,
+    /// along with the lid of the same section in the kept group, if it has one
+    fn comdat_groups(&mut self, lid_base: LinkGlobalId, symbols: &[symbol::Symbol],
+                     sections: &[(usize, section::Section, Vec<relocation::Relocation>)])
+        -> HashMap<usize, Option<LinkGlobalId>> {
+
+        let section_name = |shndx: usize| sections.iter()
+            .find(|s| s.0 == shndx)
+            .map(|s| s.1.name.clone());
+
+        let mut discarded = HashMap::new();
+        for (_, sec, _) in sections {
+            let group = match sec.content.as_group() {
+                Some(g) if g.is_comdat() => g,
+                _ => continue,
+            };
+            let signature = match symbols.get(sec.header.info as usize) {
+                None => continue,
+                Some(sym) => match sym.shndx {
+                    // the signature of a section symbol is the name of the section
+                    symbol::SymbolSectionIndex::Section(i) if sym.stype == types::SymbolType::SECTION => {
+                        section_name(i as usize).unwrap_or_default()
+                    },
+                    _ => sym.name.clone(),
+                },
+            };
+
+            match self.comdat.entry(signature) {
+                hash_map::Entry::Vacant(e) => {
+                    e.insert(group.members.iter().filter_map(|m| {
+                        section_name(*m as usize).map(|name| (name, lid_base + *m as usize))
+                    }).collect());
+                },
+                hash_map::Entry::Occupied(e) => {
+                    for m in &group.members {
+                        let kept = section_name(*m as usize).and_then(|name| e.get().get(&name).cloned());
+                        discarded.insert(*m as usize, kept);
+                    }
+                },
+            }
+        }
+        discarded
+    }
+
+    fn link_locations(&mut self, lid_base: LinkGlobalId, name: &str, symbols: Vec<symbol::Symbol>,
+                      discarded: &HashMap<usize, Option<LinkGlobalId>>)
         -> Result<Vec<usize>, Error> {
 
         let mut locations = Vec::with_capacity(symbols.len());
         for mut sym in symbols {
+            // symbols in discarded group members are resolved against the kept group instead
+            if let symbol::SymbolSectionIndex::Section(shndx) = sym.shndx {
+                if let Some(kept) = discarded.get(&(shndx as usize)) {
+                    if sym.bind == types::SymbolBind::LOCAL {
+                        locations.push(self.symtab.len());
+                        match *kept {
+                            Some(lid) => {
+                                self.symtab.push(LinkableSymbol{sym, obj: lid});
+                            },
+                            None => {
+                                sym.shndx = symbol::SymbolSectionIndex::Undefined;
+                                sym.value = 0;
+                                self.symtab.push(LinkableSymbol{sym, obj: lid_base});
+                            },
+                        }
+                        continue;
+                    }
+                    sym.shndx = symbol::SymbolSectionIndex::Undefined;
+                    sym.value = 0;
+                }
+            }
+
             match sym.shndx {
                 symbol::SymbolSectionIndex::Undefined => {
+                    if sym.bind != types::SymbolBind::LOCAL {
+                        if let Some(name) = self.wrapped(&sym.name) {
+                            sym.name = name;
+                        }
+                    }
                     if sym.name == b"_GLOBAL_OFFSET_TABLE_" {
                         //emit as not linkable, because nothing should relocate here
                         //the symbol appears to be mainly a hint that the linker needs to
@@ -214,13 +515,44 @@ impl SymbolicLinker {
                     locations.push(gsi);
                 },
                 symbol::SymbolSectionIndex::Common => {
+                    // value is the alignment. commons are merged into the largest and most aligned one,
+                    // a weak definition loses against them and a real definition wins
                     let gsi = match self.lookup.entry(sym.name.clone()) {
                         hash_map::Entry::Occupied(e) => {
                             let i = *e.get();
-                            if let symbol::SymbolSectionIndex::Undefined = self.symtab[i].sym.shndx {
-                                self.symtab[i] = LinkableSymbol{sym: sym, obj: lid_base};
-                            } else {
-                                //TODO check that the existing symbol is common with the same size
+                            match self.symtab[i].sym.shndx.clone() {
+                                symbol::SymbolSectionIndex::Undefined => {
+                                    self.symtab[i] = LinkableSymbol{sym, obj: lid_base};
+                                },
+                                symbol::SymbolSectionIndex::Common => {
+                                    let prev = self.object_name(self.symtab[i].obj);
+                                    if self.warn_common {
+                                        let what = if sym.size > self.symtab[i].sym.size {
+                                            "overridden by larger common"
+                                        } else if sym.size < self.symtab[i].sym.size {
+                                            "overriding smaller common"
+                                        } else {
+                                            "also common"
+                                        };
+                                        self.warnings.push(format!("common of {} in {} {} in {}",
+                                            String::from_utf8_lossy(&sym.name), prev, what, name));
+                                    }
+                                    let align = std::cmp::max(self.symtab[i].sym.value, sym.value);
+                                    if sym.size > self.symtab[i].sym.size {
+                                        self.symtab[i] = LinkableSymbol{sym, obj: lid_base};
+                                    }
+                                    self.symtab[i].sym.value = align;
+                                },
+                                symbol::SymbolSectionIndex::Section(_) => {
+                                    let prev = self.object_name(self.symtab[i].obj);
+                                    if self.symtab[i].sym.bind == types::SymbolBind::WEAK {
+                                        self.symtab[i] = LinkableSymbol{sym, obj: lid_base};
+                                    } else if self.warn_common {
+                                        self.warnings.push(format!("common of {} in {} overridden by definition in {}",
+                                            String::from_utf8_lossy(&sym.name), name, prev));
+                                    }
+                                },
+                                symbol::SymbolSectionIndex::Absolute => {},
                             }
                             i
                         },
@@ -233,6 +565,30 @@ impl SymbolicLinker {
                     };
                     locations.push(gsi);
                 },
+                symbol::SymbolSectionIndex::Absolute if sym.bind != types::SymbolBind::LOCAL => {
+                    // defines the symbol unless something else already does
+                    let gsi = match self.lookup.entry(sym.name.clone()) {
+                        hash_map::Entry::Occupied(e) => {
+                            let i = *e.get();
+                            let replace = match self.symtab[i].sym.shndx {
+                                symbol::SymbolSectionIndex::Undefined => true,
+                                _ => self.symtab[i].sym.bind == types::SymbolBind::WEAK &&
+                                    sym.bind == types::SymbolBind::GLOBAL,
+                            };
+                            if replace {
+                                self.symtab[i] = LinkableSymbol{sym, obj: lid_base};
+                            }
+                            i
+                        },
+                        hash_map::Entry::Vacant(e) => {
+                            let i = self.symtab.len();
+                            self.symtab.push(LinkableSymbol{sym, obj: lid_base});
+                            e.insert(i);
+                            i
+                        },
+                    };
+                    locations.push(gsi);
+                },
                 symbol::SymbolSectionIndex::Absolute  => {
                     locations.push(self.symtab.len());
                     self.symtab.push(LinkableSymbol{sym: sym, obj: lid_base});
@@ -243,6 +599,12 @@ impl SymbolicLinker {
                             let gsi = match self.lookup.entry(sym.name.clone()) {
                                 hash_map::Entry::Occupied(e) => {
                                     let i = *e.get();
+                                    if self.warn_common &&
+                                        self.symtab[i].sym.shndx == symbol::SymbolSectionIndex::Common {
+                                        let prev = self.object_name(self.symtab[i].obj);
+                                        self.warnings.push(format!("definition of {} in {} overriding common in {}",
+                                            String::from_utf8_lossy(&sym.name), name, prev));
+                                    }
                                     if let symbol::SymbolSectionIndex::Section(_) = self.symtab[i].sym.shndx {
                                         if self.symtab[i].sym.bind != types::SymbolBind::WEAK {
                                             return Err(Error::ConflictingSymbol{
@@ -299,8 +661,72 @@ impl SymbolicLinker {
         Ok(locations)
     }
 
+    /// name of the object a link comes from, for diagnostics
+    fn object_name(&self, lid: LinkGlobalId) -> String {
+        self.objects.get(&lid).map(|o| o.name.clone()).unwrap_or_default()
+    }
+
+    /// give every common symbol a zeroed section of its own, named COMMON like in linker scripts,
+    /// so it ends up in .bss. relocatable outputs keep them common instead
+    pub fn allocate_commons(&mut self) {
+        for i in 0..self.symtab.len() {
+            if self.symtab[i].sym.shndx != symbol::SymbolSectionIndex::Common {
+                continue;
+            }
+            let lid = self.lid_counter.fetch_add(1, atomic::Ordering::Acquire);
+            let (name, hash, header) = match self.objects.get(&self.symtab[i].obj) {
+                Some(o) => (o.name.clone() + "(COMMON)", o.hash.clone(), o.header.clone()),
+                None => (String::from("COMMON"), String::new(), Header::default()),
+            };
+
+            let mut sec = section::Section::new(b"COMMON".to_vec(), types::SectionType::NOBITS,
+            types::SectionFlags::ALLOC | types::SectionFlags::WRITE, section::SectionContent::None, 0, 0);
+            sec.header.size      = self.symtab[i].sym.size;
+            sec.header.addralign = std::cmp::max(self.symtab[i].sym.value, 1);
+
+            self.objects.insert(lid, Object {
+                oid:        lid,
+                lid,
+                hash,
+                name,
+                header,
+                section:    sec,
+                relocs:     Vec::new(),
+            });
+            self.symtab[i].obj = lid;
+            self.symtab[i].sym.shndx = symbol::SymbolSectionIndex::Section(0);
+            self.symtab[i].sym.value = 0;
+        }
+    }
+
+    /// what an undefined reference to name resolves to instead, if it is wrapped
+    fn wrapped(&self, name: &[u8]) -> Option<Vec<u8>> {
+        if self.wrap.contains(name) {
+            return Some([&b"__wrap_"[..], name].concat());
+        }
+        if name.starts_with(b"__real_") && self.wrap.contains(&name[7..]) {
+            return Some(name[7..].to_vec());
+        }
+        None
+    }
+
+    /// names of the global symbols defined here that are visible to other objects at runtime
+    pub fn exported(&self) -> Vec<Vec<u8>> {
+        self.lookup.iter().filter(|&(_, &i)| {
+            let sym = &self.symtab[i].sym;
+            let defined = matches!(sym.shndx, symbol::SymbolSectionIndex::Section(_));
+            defined && (sym.vis == types::SymbolVis::DEFAULT || sym.vis == types::SymbolVis::PROTECTED)
+        }).map(|(name, _)| name.clone()).collect()
+    }
+
+    /// remove everything that can't be reached from the symbols named in roots
     //TODO: maybe too aggressive because stuff like .comment and .note.GNU-stack are culled?
-    pub fn gc(&mut self) {
+    pub fn gc(&mut self, roots: &[Vec<u8>]) {
+        self.gc_keep(roots, |_| false)
+    }
+
+    /// like gc, but objects for which keep returns true are roots as well
+    pub fn gc_keep<F>(&mut self, roots: &[Vec<u8>], keep: F) where F: Fn(&Object) -> bool {
 
         let mut again = true;
         let mut symtab_remap : Vec<Option<usize>> = vec![None;self.symtab.len()];
@@ -314,7 +740,7 @@ impl SymbolicLinker {
                    obj.section.header.shtype == types::SectionType::FINI_ARRAY {
                    continue;
                 }
-                removelids.insert(*lid, true);
+                removelids.insert(*lid, !keep(obj));
             }
 
             for (lid, obj) in &self.objects {
@@ -337,9 +763,10 @@ impl SymbolicLinker {
 
             }
 
-            //TODO this feels like a hack. I think we should be able to mark root nodes before gc
-            if let Some(i) = self.lookup.get(&(b"_start".to_vec())) {
-                removelids.insert(self.symtab[*i].obj, false);
+            for root in roots {
+                if let Some(i) = self.lookup.get(root) {
+                    removelids.insert(self.symtab[*i].obj, false);
+                }
             }
 
 
@@ -382,6 +809,120 @@ impl SymbolicLinker {
     }
 
 
+    /// identical code folding. executable sections with the same content, whose relocations refer to
+    /// the same things or to sections that are folded as well, are replaced by the first of them.
+    /// in safe mode, sections that define one of the roots are not folded.
+    /// returns how many sections were folded and their size in bytes
+    pub fn icf(&mut self, mode: Icf, roots: &[Vec<u8>]) -> (usize, u64) {
+        if mode == Icf::None {
+            return (0, 0);
+        }
+
+        // anything but a call takes the address. references from debug info and unwind tables don't
+        let mut taken = HashSet::new();
+        if mode == Icf::Safe {
+            for obj in self.objects.values() {
+                if !obj.section.header.flags.contains(types::SectionFlags::ALLOC) ||
+                    obj.section.name == b".eh_frame" {
+                    continue;
+                }
+                for reloc in &obj.relocs {
+                    if reloc.rtype != relocation::RelocationType::R_X86_64_PLT32 {
+                        taken.insert(self.symtab[reloc.sym as usize].obj);
+                    }
+                }
+            }
+            for link in &self.symtab {
+                if link.sym.bind != types::SymbolBind::LOCAL && roots.contains(&link.sym.name) {
+                    taken.insert(link.obj);
+                }
+            }
+        }
+
+        let mut lids : Vec<LinkGlobalId> = self.objects.values().filter(|obj| {
+            let h = &obj.section.header;
+            h.shtype == types::SectionType::PROGBITS && !h.flags.contains(types::SectionFlags::WRITE) &&
+                h.flags.contains(types::SectionFlags::ALLOC | types::SectionFlags::EXECINSTR) &&
+                obj.section.name.starts_with(b".text") && obj.section.content.as_raw().is_some() &&
+                !taken.contains(&obj.lid)
+        }).map(|obj| obj.lid).collect();
+        lids.sort();
+
+        // relocations by address, so the same code has them in the same order
+        let relocs : HashMap<LinkGlobalId, Vec<&relocation::Relocation>> = lids.iter().map(|lid| {
+            let mut relocs : Vec<&relocation::Relocation> = self.objects[lid].relocs.iter().collect();
+            relocs.sort_by_key(|reloc| reloc.addr);
+            (*lid, relocs)
+        }).collect();
+
+        // start with what doesn't depend on other sections
+        let mut class = HashMap::new();
+        let mut classes = {
+            let mut keys = HashMap::new();
+            for lid in &lids {
+                let obj = &self.objects[lid];
+                let key = (obj.section.content.as_raw().unwrap(), obj.section.header.flags.bits(),
+                    obj.section.header.addralign,
+                    relocs[lid].iter().map(|r| (r.addr, r.rtype.0, r.addend)).collect::<Vec<_>>());
+                let n = keys.len();
+                class.insert(*lid, *keys.entry(key).or_insert(n));
+            }
+            keys.len()
+        };
+
+        // then split classes whose members refer to different things, until none are split anymore
+        loop {
+            let mut keys = HashMap::new();
+            let mut next = HashMap::new();
+            for lid in &lids {
+                let targets : Vec<IcfTarget> = relocs[lid].iter().map(|reloc| {
+                    let link = &self.symtab[reloc.sym as usize];
+                    match link.sym.shndx {
+                        symbol::SymbolSectionIndex::Section(_) => match class.get(&link.obj) {
+                            Some(c) => IcfTarget::Class(*c, link.sym.value),
+                            None => IcfTarget::Object(link.obj, link.sym.value),
+                        },
+                        _ => IcfTarget::Symbol(reloc.sym as usize),
+                    }
+                }).collect();
+                let n = keys.len();
+                next.insert(*lid, *keys.entry((class[lid], targets)).or_insert(n));
+            }
+            class = next;
+            if keys.len() == classes {
+                break;
+            }
+            classes = keys.len();
+        }
+
+        // the first section of each class is kept
+        let mut kept = HashMap::new();
+        let mut folded = HashMap::new();
+        for lid in &lids {
+            match kept.entry(class[lid]) {
+                hash_map::Entry::Vacant(e) => {
+                    e.insert(*lid);
+                },
+                hash_map::Entry::Occupied(e) => {
+                    folded.insert(*lid, *e.get());
+                },
+            }
+        }
+
+        for link in &mut self.symtab {
+            if let Some(lid) = folded.get(&link.obj) {
+                link.obj = *lid;
+            }
+        }
+        let mut size = 0;
+        for lid in folded.keys() {
+            if let Some(obj) = self.objects.remove(lid) {
+                size += obj.section.content.as_raw().map_or(0, |r| r.len() as u64);
+            }
+        }
+        (folded.len(), size)
+    }
+
     pub fn write_graphviz<W : Write> (&self, mut file: W) -> std::io::Result<()> {
 
         for (lid, object) in self.objects.iter() {
@@ -185,6 +185,26 @@ impl Default for Class {
     }
 }
 
+/// ch_type in the header of SHF_COMPRESSED sections
+#[allow(non_camel_case_types)]
+#[derive(Debug, Primitive, PartialEq, Clone, Copy)]
+pub enum CompressionType {
+    ZLIB = 1,
+    ZSTD = 2,
+}
+
+/// Undefined section
+pub const SHN_UNDEF: u16 = 0;
+/// Start of reserved indices. section indices from here on don't fit into 16 bits
+pub const SHN_LORESERVE: u16 = 0xff00;
+/// Associated symbol is absolute
+pub const SHN_ABS: u16 = 0xfff1;
+/// Associated symbol is common
+pub const SHN_COMMON: u16 = 0xfff2;
+/// Index is in extra table (SYMTAB_SHNDX), or for shstrndx in section 0's sh_link
+pub const SHN_XINDEX: u16 = 0xffff;
+/// The number of segments is in section 0's sh_info
+pub const PN_XNUM: u16 = 0xffff;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct SectionType(pub u32);
@@ -821,6 +841,8 @@ pub enum SegmentType {
     GNU_STACK = 0x6474e551,
     /// Read-only after relocation
     GNU_RELRO = 0x6474e552,
+    /// GNU property notes, the .note.gnu.property section
+    GNU_PROPERTY = 0x6474e553,
     /// PaX memory protection
     PAX_FLAGS = 0x65041580,
     /// Sun Specific segment
@@ -909,8 +931,14 @@ pub enum DynamicType {
     PREINIT_ARRAY = 32,
     /// size in bytes of DT_PREINIT_ARRAY
     PREINIT_ARRAYSZ = 33,
-    /// Number used
-    NUM = 34,
+    /// Address of SYMTAB_SHNDX section
+    SYMTAB_SHNDX = 34,
+    /// Total size of RELR relative relocations
+    RELRSZ = 35,
+    /// Address of RELR relative relocations
+    RELR = 36,
+    /// Size of one RELR relative relocation
+    RELRENT = 37,
 
     /// Prelinking timestamp
     GNU_PRELINKED = 0x6ffffdf5,
@@ -952,6 +980,7 @@ pub enum DynamicType {
     /// Syminfo table
     SYMINFO = 0x6ffffeff,
 
+    /// Address of the symbol version table
     VERSYM = 0x6ffffff0,
     RELACOUNT = 0x6ffffff9,
     RELCOUNT = 0x6ffffffa,
@@ -996,6 +1025,33 @@ impl Default for DynamicType {
     }
 }
 
+impl DynamicType {
+    /// the value of these tags is an offset into the linked string table
+    pub fn is_string(&self) -> bool {
+        matches!(*self,
+            DynamicType::NEEDED | DynamicType::SONAME | DynamicType::RPATH |
+            DynamicType::RUNPATH | DynamicType::AUXILIARY | DynamicType::FILTER |
+            DynamicType::CONFIG | DynamicType::DEPAUDIT | DynamicType::AUDIT)
+    }
+
+    /// tags whose value is a size in bytes
+    pub fn is_size(&self) -> bool {
+        matches!(*self,
+            DynamicType::PLTRELSZ | DynamicType::RELASZ | DynamicType::RELAENT |
+            DynamicType::STRSZ | DynamicType::SYMENT | DynamicType::RELSZ |
+            DynamicType::RELENT | DynamicType::INIT_ARRAYSZ | DynamicType::FINI_ARRAYSZ |
+            DynamicType::PREINIT_ARRAYSZ | DynamicType::RELRSZ | DynamicType::RELRENT |
+            DynamicType::SYMINSZ | DynamicType::SYMINENT)
+    }
+
+    /// tags whose value is a number of entries
+    pub fn is_count(&self) -> bool {
+        matches!(*self,
+            DynamicType::RELACOUNT | DynamicType::RELCOUNT |
+            DynamicType::VERDEFNUM | DynamicType::VERNEEDNUM)
+    }
+}
+
 
 
 // docs text from https://git.kindwolf.org/elfwalk/blob/master/elfwalk
@@ -1058,5 +1114,95 @@ bitflags! {
             const STUB = 1 << 26;
             ///position independant executable
             const PIE  = 1 << 27;
+            ///kernel module
+            const KMOD = 1 << 28;
+            ///weak filter
+            const WEAKFILTER = 1 << 29;
+            ///no copy relocations against this object
+            const NOCOMMON = 1 << 30;
+    }
+}
+
+bitflags! {
+#[derive(Default)]
+    pub struct DynamicFlags: u64 {
+            ///object may use $ORIGIN
+            const ORIGIN     = 1 << 0;
+            ///symbol resolution starts with this object
+            const SYMBOLIC   = 1 << 1;
+            ///relocations might modify non-writable segments
+            const TEXTREL    = 1 << 2;
+            ///perform complete relocation processing at load time
+            const BIND_NOW   = 1 << 3;
+            ///object uses the static thread local storage model
+            const STATIC_TLS = 1 << 4;
+    }
+}
+
+bitflags! {
+#[derive(Default)]
+    pub struct X86Feature1: u32 {
+            ///indirect branch tracking
+            const IBT       = 1 << 0;
+            ///shadow stack
+            const SHSTK     = 1 << 1;
+            const LAM_U48   = 1 << 2;
+            const LAM_U57   = 1 << 3;
+    }
+}
+
+bitflags! {
+#[derive(Default)]
+    pub struct X86Isa1: u32 {
+            ///x86-64 baseline, i.e. cmov, cx8, fpu, fxsr, mmx, sse, sse2
+            const BASELINE  = 1 << 0;
+            ///x86-64-v2, adds cx16, lahf-sahf, popcnt, sse3, sse4.1, sse4.2, ssse3
+            const V2        = 1 << 1;
+            ///x86-64-v3, adds avx, avx2, bmi1, bmi2, f16c, fma, lzcnt, movbe, xsave
+            const V3        = 1 << 2;
+            ///x86-64-v4, adds avx512f, avx512bw, avx512cd, avx512dq, avx512vl
+            const V4        = 1 << 3;
+    }
+}
+
+bitflags! {
+#[derive(Default)]
+    pub struct X86Feature2: u32 {
+            const X86       = 1 << 0;
+            const X87       = 1 << 1;
+            const MMX       = 1 << 2;
+            const XMM       = 1 << 3;
+            const YMM       = 1 << 4;
+            const ZMM       = 1 << 5;
+            const FXSR      = 1 << 6;
+            const XSAVE     = 1 << 7;
+            const XSAVEOPT  = 1 << 8;
+            const XSAVEC    = 1 << 9;
+            const TMM       = 1 << 10;
+            const MASK      = 1 << 11;
+    }
+}
+
+bitflags! {
+#[derive(Default)]
+    pub struct VersionFlags: u16 {
+            ///the version definition of the file itself
+            const BASE      = 0x1;
+            ///weak version reference
+            const WEAK      = 0x2;
+            ///reference exists for information only
+            const INFO      = 0x4;
+    }
+}
+
+bitflags! {
+#[derive(Default)]
+    pub struct GroupFlags: u32 {
+            ///only one group with the same signature is kept by the linker
+            const COMDAT    = 0x1;
+            ///OS-specific
+            const MASKOS    = 0x0ff00000;
+            ///Processor-specific
+            const MASKPROC  = 0xf0000000;
     }
 }
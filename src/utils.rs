@@ -18,6 +18,19 @@ pub trait ElfEndianReadExt: Read {
             types::Endianness::BigEndian    => self.read_u32::<BigEndian>(),
         }
     }
+    fn elf_read_u64(&mut self, eh: &Header) -> Result<u64> {
+        use byteorder::{BigEndian, LittleEndian, ReadBytesExt};
+        match eh.ident_endianness {
+            types::Endianness::LittleEndian => self.read_u64::<LittleEndian>(),
+            types::Endianness::BigEndian    => self.read_u64::<BigEndian>(),
+        }
+    }
+    fn elf_read_uclass(&mut self, eh: &Header) -> Result<u64> {
+        match eh.ident_class {
+            types::Class::Class32 => Ok(self.elf_read_u32(eh)? as u64),
+            types::Class::Class64 => self.elf_read_u64(eh),
+        }
+    }
 }
 impl<R: Read + ?Sized> ElfEndianReadExt for R {}
 
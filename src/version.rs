@@ -0,0 +1,319 @@
+use std::io::Write;
+use {Error, Header, SectionContent};
+use hash::sysv_hash;
+use strtab::Strtab;
+use types;
+use utils::ElfEndianReadExt;
+
+/// versym index of symbols that are local to the object
+pub const VER_NDX_LOCAL: u16    = 0;
+/// versym index of symbols that are global but not versioned
+pub const VER_NDX_GLOBAL: u16   = 1;
+/// set in a versym entry if the symbol is not the default version
+pub const VERSYM_HIDDEN: u16    = 0x8000;
+
+/// an entry in .gnu.version. there is one for every symbol in the linked .dynsym
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Versym {
+    /// VER_NDX_LOCAL, VER_NDX_GLOBAL or the index of a Verdef or Vernaux
+    pub index:  u16,
+    pub hidden: bool,
+}
+
+/// a version definition in .gnu.version_d
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Verdef {
+    pub version:    u16,
+    pub flags:      types::VersionFlags,
+    pub index:      u16,
+    /// sysv hash of the first name, updated by sync
+    pub hash:       u32,
+    /// the first name is the version being defined, the others are its parents
+    pub names:      Vec<Verdaux>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Verdaux {
+    pub name:   Vec<u8>,
+    pub _name:  u32,
+}
+
+/// the versions required from one file, in .gnu.version_r
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Verneed {
+    pub version:    u16,
+    pub file:       Vec<u8>,
+    pub _file:      u32,
+    pub aux:        Vec<Vernaux>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Vernaux {
+    /// sysv hash of name, updated by sync
+    pub hash:   u32,
+    pub flags:  types::VersionFlags,
+    /// the versym index referring to this version
+    pub other:  u16,
+    pub name:   Vec<u8>,
+    pub _name:  u32,
+}
+
+const VERDEF_SIZE: usize    = 20;
+const VERDAUX_SIZE: usize   = 8;
+const VERNEED_SIZE: usize   = 16;
+const VERNAUX_SIZE: usize   = 16;
+
+fn at(data: &[u8], offset: usize, size: usize) -> Result<&[u8], Error> {
+    match offset.checked_add(size) {
+        Some(end) if end <= data.len() => Ok(&data[offset..end]),
+        _ => Err(Error::TruncatedInput),
+    }
+}
+
+fn strtab_of<'a>(linked: Option<&'a SectionContent>, during: &'static str)
+    -> Result<Option<&'a Strtab>, Error> {
+    match linked {
+        None => Ok(None),
+        Some(SectionContent::Strtab(s)) => Ok(Some(s)),
+        any => Err(Error::LinkedSectionIsNotStrtab{
+            during,
+            link: any.cloned(),
+        }),
+    }
+}
+
+fn insert(linked: &mut Option<&mut SectionContent>, during: &'static str, name: &[u8])
+    -> Result<u32, Error> {
+    match linked.as_mut().map(|r| &mut **r) {
+        Some(&mut SectionContent::Strtab(ref mut strtab)) => Ok(strtab.insert(name) as u32),
+        any => Err(Error::LinkedSectionIsNotStrtab{
+            during,
+            link: any.map(|v|v.clone()),
+        }),
+    }
+}
+
+/// the name of the version that a versym index refers to
+pub fn version_name<'a>(index: u16, verdefs: &'a [Verdef], verneeds: &'a [Verneed]) -> Option<&'a [u8]> {
+    let index = index & !VERSYM_HIDDEN;
+    if index == VER_NDX_LOCAL || index == VER_NDX_GLOBAL {
+        return None;
+    }
+    for vd in verdefs {
+        if vd.index == index {
+            return vd.names.first().map(|n| n.name.as_slice());
+        }
+    }
+    for vn in verneeds {
+        for aux in &vn.aux {
+            if aux.other == index {
+                return Some(&aux.name);
+            }
+        }
+    }
+    None
+}
+
+impl Versym {
+    pub fn entsize(_: &Header) -> usize {
+        2
+    }
+
+    pub fn from_u16(v: u16) -> Versym {
+        Versym {
+            index:  v & !VERSYM_HIDDEN,
+            hidden: v & VERSYM_HIDDEN != 0,
+        }
+    }
+
+    pub fn to_u16(&self) -> u16 {
+        self.index | if self.hidden { VERSYM_HIDDEN } else { 0 }
+    }
+
+    pub fn from_reader(mut io: &[u8], _: Option<&SectionContent>, eh: &Header)
+        -> Result<SectionContent, Error> {
+        let mut r = Vec::new();
+        while io.len() >= 2 {
+            r.push(Versym::from_u16(io.elf_read_u16(eh)?));
+        }
+        Ok(SectionContent::Versym(r))
+    }
+
+    pub fn to_writer<W>(&self, mut io: W, eh: &Header) -> Result<usize, Error> where W: Write {
+        elf_write_u16!(eh, io, self.to_u16())?;
+        Ok(2)
+    }
+}
+
+impl Verdef {
+    pub fn size(&self) -> usize {
+        VERDEF_SIZE + self.names.len() * VERDAUX_SIZE
+    }
+
+    /// the entries are found by following the offsets stored in them,
+    /// so this needs the complete section
+    pub fn from_reader(data: &[u8], linked: Option<&SectionContent>, eh: &Header)
+        -> Result<SectionContent, Error> {
+        let strtab = strtab_of(linked, "reading verdef")?;
+        Ok(SectionContent::Verdef(Verdef::from_bytes(data, eh, |i| match strtab {
+            Some(s) => s.get(i as usize),
+            None => Vec::default(),
+        })?))
+    }
+
+    pub(crate) fn from_bytes<F>(data: &[u8], eh: &Header, name: F) -> Result<Vec<Verdef>, Error>
+        where F: Fn(u32) -> Vec<u8> {
+        let mut r = Vec::new();
+        let mut off = 0;
+        while off < data.len() {
+            let mut b = at(data, off, VERDEF_SIZE)?;
+            let mut vd = Verdef {
+                version: b.elf_read_u16(eh)?,
+                flags:   types::VersionFlags::from_bits_truncate(b.elf_read_u16(eh)?),
+                index:   b.elf_read_u16(eh)?,
+                ..Default::default()
+            };
+            let cnt  = b.elf_read_u16(eh)?;
+            vd.hash  = b.elf_read_u32(eh)?;
+            let aux  = b.elf_read_u32(eh)? as usize;
+            let next = b.elf_read_u32(eh)? as usize;
+
+            let mut auxoff = off + aux;
+            for _ in 0..cnt {
+                let mut b = at(data, auxoff, VERDAUX_SIZE)?;
+                let _name = b.elf_read_u32(eh)?;
+                vd.names.push(Verdaux{
+                    name:  name(_name),
+                    _name,
+                });
+                match b.elf_read_u32(eh)? {
+                    0 => break,
+                    n => auxoff += n as usize,
+                }
+            }
+            r.push(vd);
+
+            if next == 0 {
+                break;
+            }
+            off += next;
+        }
+        Ok(r)
+    }
+
+    /// writes this entry and its names. last must be set on the final entry of the section
+    pub fn to_writer<W>(&self, mut io: W, eh: &Header, last: bool) -> Result<usize, Error> where W: Write {
+        elf_write_u16!(eh, io, self.version)?;
+        elf_write_u16!(eh, io, self.flags.bits())?;
+        elf_write_u16!(eh, io, self.index)?;
+        elf_write_u16!(eh, io, self.names.len() as u16)?;
+        elf_write_u32!(eh, io, self.hash)?;
+        elf_write_u32!(eh, io, if !self.names.is_empty() { VERDEF_SIZE as u32 } else { 0 })?;
+        elf_write_u32!(eh, io, if last { 0 } else { self.size() as u32 })?;
+        for (i, aux) in self.names.iter().enumerate() {
+            elf_write_u32!(eh, io, aux._name)?;
+            elf_write_u32!(eh, io, if i + 1 == self.names.len() { 0 } else { VERDAUX_SIZE as u32 })?;
+        }
+        Ok(self.size())
+    }
+
+    pub fn sync(&mut self, mut linked: Option<&mut SectionContent>, _: &Header) -> Result<(), Error> {
+        if let Some(first) = self.names.first() {
+            self.hash = sysv_hash(&first.name);
+        }
+        for aux in &mut self.names {
+            aux._name = insert(&mut linked, "syncing verdef", &aux.name)?;
+        }
+        Ok(())
+    }
+}
+
+impl Verneed {
+    pub fn size(&self) -> usize {
+        VERNEED_SIZE + self.aux.len() * VERNAUX_SIZE
+    }
+
+    /// the entries are found by following the offsets stored in them,
+    /// so this needs the complete section
+    pub fn from_reader(data: &[u8], linked: Option<&SectionContent>, eh: &Header)
+        -> Result<SectionContent, Error> {
+        let strtab = strtab_of(linked, "reading verneed")?;
+        Ok(SectionContent::Verneed(Verneed::from_bytes(data, eh, |i| match strtab {
+            Some(s) => s.get(i as usize),
+            None => Vec::default(),
+        })?))
+    }
+
+    pub(crate) fn from_bytes<F>(data: &[u8], eh: &Header, name: F) -> Result<Vec<Verneed>, Error>
+        where F: Fn(u32) -> Vec<u8> {
+        let mut r = Vec::new();
+        let mut off = 0;
+        while off < data.len() {
+            let mut b = at(data, off, VERNEED_SIZE)?;
+            let version = b.elf_read_u16(eh)?;
+            let cnt     = b.elf_read_u16(eh)?;
+            let _file   = b.elf_read_u32(eh)?;
+            let aux     = b.elf_read_u32(eh)? as usize;
+            let next    = b.elf_read_u32(eh)? as usize;
+            let mut vn = Verneed {
+                version,
+                file:    name(_file),
+                _file,
+                aux:     Vec::new(),
+            };
+
+            let mut auxoff = off + aux;
+            for _ in 0..cnt {
+                let mut b = at(data, auxoff, VERNAUX_SIZE)?;
+                let hash  = b.elf_read_u32(eh)?;
+                let flags = types::VersionFlags::from_bits_truncate(b.elf_read_u16(eh)?);
+                let other = b.elf_read_u16(eh)?;
+                let _name = b.elf_read_u32(eh)?;
+                vn.aux.push(Vernaux{
+                    hash,
+                    flags,
+                    other,
+                    name:   name(_name),
+                    _name,
+                });
+                match b.elf_read_u32(eh)? {
+                    0 => break,
+                    n => auxoff += n as usize,
+                }
+            }
+            r.push(vn);
+
+            if next == 0 {
+                break;
+            }
+            off += next;
+        }
+        Ok(r)
+    }
+
+    /// writes this entry and its versions. last must be set on the final entry of the section
+    pub fn to_writer<W>(&self, mut io: W, eh: &Header, last: bool) -> Result<usize, Error> where W: Write {
+        elf_write_u16!(eh, io, self.version)?;
+        elf_write_u16!(eh, io, self.aux.len() as u16)?;
+        elf_write_u32!(eh, io, self._file)?;
+        elf_write_u32!(eh, io, if !self.aux.is_empty() { VERNEED_SIZE as u32 } else { 0 })?;
+        elf_write_u32!(eh, io, if last { 0 } else { self.size() as u32 })?;
+        for (i, aux) in self.aux.iter().enumerate() {
+            elf_write_u32!(eh, io, aux.hash)?;
+            elf_write_u16!(eh, io, aux.flags.bits())?;
+            elf_write_u16!(eh, io, aux.other)?;
+            elf_write_u32!(eh, io, aux._name)?;
+            elf_write_u32!(eh, io, if i + 1 == self.aux.len() { 0 } else { VERNAUX_SIZE as u32 })?;
+        }
+        Ok(self.size())
+    }
+
+    pub fn sync(&mut self, mut linked: Option<&mut SectionContent>, _: &Header) -> Result<(), Error> {
+        self._file = insert(&mut linked, "syncing verneed", &self.file)?;
+        for aux in &mut self.aux {
+            aux.hash  = sysv_hash(&aux.name);
+            aux._name = insert(&mut linked, "syncing verneed", &aux.name)?;
+        }
+        Ok(())
+    }
+}
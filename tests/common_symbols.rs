@@ -0,0 +1,91 @@
+extern crate elfkit;
+
+use elfkit::{Header, Section, SectionContent, SymbolicLinker, types, symbol};
+use elfkit::loader;
+
+fn common(size: u64, align: u64) -> symbol::Symbol {
+    symbol::Symbol{
+        shndx:  symbol::SymbolSectionIndex::Common,
+        name:   b"counters".to_vec(),
+        stype:  types::SymbolType::OBJECT,
+        bind:   types::SymbolBind::GLOBAL,
+        value:  align,
+        size,
+        ..Default::default()
+    }
+}
+
+fn defined(bind: types::SymbolBind) -> symbol::Symbol {
+    symbol::Symbol{
+        shndx:  symbol::SymbolSectionIndex::Section(1),
+        name:   b"counters".to_vec(),
+        stype:  types::SymbolType::OBJECT,
+        bind,
+        size:   4,
+        ..Default::default()
+    }
+}
+
+fn object(name: &str, sym: symbol::Symbol) -> loader::State {
+    let data = Section::new(b".data".to_vec(), types::SectionType::PROGBITS,
+    types::SectionFlags::ALLOC | types::SectionFlags::WRITE,
+    SectionContent::Raw(vec![0; 4]), 0, 0);
+
+    loader::State::Object{
+        hash:     String::from(name),
+        name:     String::from(name),
+        symbols:  vec![symbol::Symbol::default(), sym],
+        header:   Header{shnum: 2, ..Default::default()},
+        sections: vec![(1, data, Vec::new())],
+    }
+}
+
+fn counters(linker: &SymbolicLinker) -> &symbol::Symbol {
+    let defs : Vec<_> = linker.symtab.iter().filter(|l| l.sym.name == b"counters").collect();
+    assert_eq!(defs.len(), 1);
+    &defs[0].sym
+}
+
+#[test]
+fn common_merge() {
+    let mut linker = SymbolicLinker::default();
+    linker.warn_common = true;
+    linker.link_all(vec![
+        object("a.o", common(16, 4)),
+        object("b.o", defined(types::SymbolBind::WEAK)),
+        object("c.o", common(8, 32)),
+    ]).unwrap();
+
+    // the largest size and the strictest alignment, and the weak definition lost
+    assert_eq!(counters(&linker).shndx, symbol::SymbolSectionIndex::Common);
+    assert_eq!(counters(&linker).size, 16);
+    assert_eq!(counters(&linker).value, 32);
+    assert_eq!(linker.warnings.len(), 1);
+
+    linker.allocate_commons();
+    let com : Vec<_> = linker.objects.values().filter(|o| o.section.name == b"COMMON").collect();
+    assert_eq!(com.len(), 1);
+    assert_eq!(com[0].section.header.shtype, types::SectionType::NOBITS);
+    assert_eq!(com[0].section.header.size, 16);
+    assert_eq!(com[0].section.header.addralign, 32);
+    assert_eq!(com[0].name, "a.o(COMMON)");
+    assert_eq!(linker.symtab.iter().find(|l| l.sym.name == b"counters").unwrap().obj, com[0].lid);
+}
+
+#[test]
+fn common_overridden_by_definition() {
+    for order in &[false, true] {
+        let mut linker = SymbolicLinker::default();
+        let mut objects = vec![object("a.o", common(16, 4)), object("b.o", defined(types::SymbolBind::GLOBAL))];
+        if *order {
+            objects.reverse();
+        }
+        linker.link_all(objects).unwrap();
+        assert_eq!(counters(&linker).shndx, symbol::SymbolSectionIndex::Section(1));
+        assert_eq!(counters(&linker).size, 4);
+        assert!(linker.warnings.is_empty());
+
+        linker.allocate_commons();
+        assert_eq!(linker.objects.values().filter(|o| o.section.name == b"COMMON").count(), 0);
+    }
+}
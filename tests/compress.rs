@@ -0,0 +1,114 @@
+extern crate elfkit;
+
+use elfkit::{Elf, Header, Section, SectionContent, types};
+use elfkit::compress::{self, Chdr};
+use std::fs::File;
+use std::io::Cursor;
+
+fn header(class: types::Class) -> Header {
+    Header{ident_class: class, ..Default::default()}
+}
+
+fn debug_section() -> Section {
+    let data = (0..4000u32).map(|i| (i % 251) as u8 ^ (i / 500) as u8).collect();
+    let mut sec = Section::new(b".debug_info".to_vec(), types::SectionType::PROGBITS,
+                               types::SectionFlags::empty(), SectionContent::Raw(data), 0, 0);
+    sec.header.addralign = 1;
+    sec
+}
+
+#[test]
+fn compress_chdr() {
+    let chdr = Chdr {
+        chtype:     types::CompressionType::ZSTD,
+        size:       0x1234,
+        addralign:  8,
+    };
+
+    let eh = header(types::Class::Class64);
+    let mut w = Vec::new();
+    assert_eq!(chdr.to_writer(&mut w, &eh).unwrap(), 24);
+    assert_eq!(w, vec![2, 0, 0, 0, 0, 0, 0, 0,
+                       0x34, 0x12, 0, 0, 0, 0, 0, 0,
+                       8, 0, 0, 0, 0, 0, 0, 0]);
+    assert_eq!(Chdr::from_reader(w.as_slice(), &eh).unwrap(), chdr);
+
+    let eh = header(types::Class::Class32);
+    let mut w = Vec::new();
+    assert_eq!(chdr.to_writer(&mut w, &eh).unwrap(), 12);
+    assert_eq!(w, vec![2, 0, 0, 0, 0x34, 0x12, 0, 0, 8, 0, 0, 0]);
+    assert_eq!(Chdr::from_reader(w.as_slice(), &eh).unwrap(), chdr);
+
+    assert!(Chdr::from_reader(&[3u8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0][..], &eh).is_err());
+}
+
+#[test]
+fn compress_roundtrip() {
+    for class in [types::Class::Class64, types::Class::Class32] {
+        for chtype in [types::CompressionType::ZLIB, types::CompressionType::ZSTD] {
+            let eh  = header(class.clone());
+            let orig = debug_section();
+            let data = orig.content.as_raw().unwrap().clone();
+
+            let mut sec = orig.clone();
+            sec.compress(chtype, &eh).unwrap();
+            assert!(sec.header.flags.contains(types::SectionFlags::COMPRESSED));
+            assert_eq!(sec.header.addralign, if class == types::Class::Class64 { 8 } else { 4 });
+            let compressed = sec.content.as_raw().unwrap().clone();
+            assert!(compressed.len() < data.len());
+            assert_eq!(sec.header.size, compressed.len() as u64);
+
+            let (chdr, _) = compress::decompress(&compressed, &eh).unwrap();
+            assert_eq!(chdr, Chdr{chtype, size: data.len() as u64, addralign: 1});
+
+            // through a file, the way Elf::load reads it
+            let mut io = Cursor::new(Vec::new());
+            sec.to_writer(&mut io, &eh).unwrap();
+
+            let mut raw = Section::clone(&sec);
+            raw.content = SectionContent::Unloaded;
+            let mut unpacked = raw.clone();
+            raw.from_reader(&mut io, None, &eh).unwrap();
+            assert_eq!(raw.content.as_raw(), Some(&compressed));
+            unpacked.from_reader_decompressed(&mut io, None, &eh).unwrap();
+            assert_eq!(unpacked.content.as_raw(), Some(&data));
+            assert!(!unpacked.header.flags.contains(types::SectionFlags::COMPRESSED));
+            assert_eq!(unpacked.header.addralign, 1);
+            assert_eq!(unpacked.header.size, data.len() as u64);
+
+            sec.decompress(&eh).unwrap();
+            assert_eq!(sec.content.as_raw(), Some(&data));
+            assert_eq!(sec.header.flags, orig.header.flags);
+        }
+    }
+
+    let mut sec = debug_section();
+    sec.header.flags = types::SectionFlags::ALLOC;
+    assert!(sec.compress(types::CompressionType::ZLIB, &Header::default()).is_err());
+}
+
+#[test]
+fn compress_fixture() {
+    // gcc -g -gz=zlib -c
+    let mut f = File::open("tests/debug-zlib.o").unwrap();
+    let mut elf = Elf::from_reader(&mut f).unwrap();
+    elf.load_all(&mut f).unwrap();
+    let sec = elf.sections.iter().find(|s| s.name == b".debug_info").unwrap();
+    assert!(sec.header.flags.contains(types::SectionFlags::COMPRESSED));
+    let (chdr, _) = compress::decompress(sec.content.as_raw().unwrap(), &elf.header).unwrap();
+    assert_eq!(chdr.chtype, types::CompressionType::ZLIB);
+
+    let mut f = File::open("tests/debug-zlib.o").unwrap();
+    let mut elf = Elf::from_reader(&mut f).unwrap();
+    elf.decompress = true;
+    elf.load_all(&mut f).unwrap();
+    for sec in &elf.sections {
+        assert!(!sec.header.flags.contains(types::SectionFlags::COMPRESSED));
+    }
+    let sec = elf.sections.iter().find(|s| s.name == b".debug_info").unwrap();
+    let data = sec.content.as_raw().unwrap();
+    assert_eq!(data.len() as u64, chdr.size);
+    assert_eq!(sec.header.size, chdr.size);
+    // unit_length of the only compilation unit covers the rest of the section
+    assert_eq!(data[0] as usize + ((data[1] as usize) << 8) + 4, data.len());
+}
@@ -0,0 +1,136 @@
+extern crate elfkit;
+
+use elfkit::{Dynamic, DynamicContent, Header, Section, SectionContent, Strtab, types};
+use elfkit::types::DynamicType;
+use std::fs::{self, File};
+use std::io::Cursor;
+
+const DYNSTR: &[u8] = b"\0libx.so.1\0/opt/x\0libc.so.6\0";
+
+fn entries() -> Vec<(u64, u64)> {
+    vec![
+        (1, 18),                // NEEDED libc.so.6
+        (14, 1),                // SONAME libx.so.1
+        (29, 11),               // RUNPATH /opt/x
+        (30, 0x18),             // FLAGS BIND_NOW | STATIC_TLS
+        (0x6ffffffb, 0x1),      // FLAGS_1 NOW
+        (25, 0x3dc0),           // INIT_ARRAY
+        (27, 16),               // INIT_ARRAYSZ
+        (2, 48),                // PLTRELSZ
+        (20, 7),                // PLTREL RELA
+        (23, 0x648),            // JMPREL
+        (36, 0x25270),          // RELR
+        (35, 280),              // RELRSZ
+        (37, 8),                // RELRENT
+        (0x6ffffff9, 3),        // RELACOUNT
+        (0, 0),
+    ]
+}
+
+fn bytes() -> Vec<u8> {
+    let mut r = Vec::new();
+    for (tag, val) in entries() {
+        for v in [tag, val] {
+            for i in 0..8 {
+                r.push((v >> (i * 8)) as u8);
+            }
+        }
+    }
+    r
+}
+
+fn dynstr() -> SectionContent {
+    Strtab::from_reader(DYNSTR, None, &Header::default()).unwrap()
+}
+
+#[test]
+fn dynamic_typed_content() {
+    let eh = Header::default();
+    let linked = dynstr();
+    let dynamic = Dynamic::from_reader(bytes().as_slice(), Some(&linked), &eh).unwrap()
+        .into_dynamic().unwrap();
+
+    let expect = vec![
+        DynamicContent::String((b"libc.so.6".to_vec(), Some(18))),
+        DynamicContent::String((b"libx.so.1".to_vec(), Some(1))),
+        DynamicContent::String((b"/opt/x".to_vec(), Some(11))),
+        DynamicContent::Flags(types::DynamicFlags::BIND_NOW | types::DynamicFlags::STATIC_TLS),
+        DynamicContent::Flags1(types::DynamicFlags1::NOW),
+        DynamicContent::Address(0x3dc0),
+        DynamicContent::Size(16),
+        DynamicContent::Size(48),
+        DynamicContent::PltRel(DynamicType::RELA),
+        DynamicContent::Address(0x648),
+        DynamicContent::Address(0x25270),
+        DynamicContent::Size(280),
+        DynamicContent::Size(8),
+        DynamicContent::Count(3),
+        DynamicContent::None,
+    ];
+    assert_eq!(dynamic.iter().map(|d| d.content.clone()).collect::<Vec<_>>(), expect);
+    assert_eq!(dynamic[10].dhtype, DynamicType::RELR);
+
+    let mut w = Vec::new();
+    for d in &dynamic {
+        d.to_writer(&mut w, &eh).unwrap();
+    }
+    assert_eq!(w, bytes());
+}
+
+#[test]
+fn dynamic_strings_sync_through_dynstr() {
+    let eh = Header::default();
+    let mut linked = dynstr();
+    let mut dynamic = Dynamic::from_reader(bytes().as_slice(), Some(&linked), &eh).unwrap()
+        .into_dynamic().unwrap();
+
+    dynamic[1].content = DynamicContent::String((b"libx.so.2".to_vec(), None));
+    dynamic[2].content = DynamicContent::String((b"libc.so.6".to_vec(), None));
+    for d in &mut dynamic {
+        d.sync(Some(&mut linked), &eh).unwrap();
+    }
+
+    let strtab = match linked {
+        SectionContent::Strtab(ref s) => s,
+        _ => unreachable!(),
+    };
+    match dynamic[1].content {
+        DynamicContent::String((_, Some(off))) => {
+            assert!(off as usize >= DYNSTR.len());
+            assert_eq!(strtab.get(off as usize), b"libx.so.2");
+        },
+        ref any => panic!("expected synced string, got {:?}", any),
+    }
+    // already in the table
+    assert_eq!(dynamic[2].content, DynamicContent::String((b"libc.so.6".to_vec(), Some(18))));
+
+    assert!(Dynamic{
+        dhtype:  DynamicType::SONAME,
+        content: DynamicContent::String((b"x".to_vec(), None)),
+    }.sync(Some(&mut SectionContent::None), &eh).is_err());
+}
+
+#[test]
+fn dynamic_fixture() {
+    let mut f = File::open("tests/ld.so").unwrap();
+    let mut elf = elfkit::Elf::from_reader(&mut f).unwrap();
+    elf.load_all(&mut f).unwrap();
+
+    let sec = elf.sections.iter().find(|s| s.name == b".dynamic").unwrap();
+    let dynamic = sec.content.as_dynamic().unwrap();
+    let get = |t: DynamicType| dynamic.iter().find(|d| d.dhtype == t).unwrap().content.clone();
+    assert_eq!(get(DynamicType::SONAME), DynamicContent::String((b"ld-linux-x86-64.so.2".to_vec(), Some(0x179))));
+    assert_eq!(get(DynamicType::SYMENT), DynamicContent::Size(24));
+    assert_eq!(get(DynamicType::VERDEFNUM), DynamicContent::Count(5));
+
+    // everything up to DT_NULL is written back as it was, the padding behind it isn't kept
+    let mut io = Cursor::new(Vec::new());
+    let mut sec = Section::clone(sec);
+    sec.header.offset = 0;
+    sec.to_writer(&mut io, &elf.header).unwrap();
+    let written = io.into_inner();
+    assert_eq!(written.len(), dynamic.len() * 16);
+    let data = fs::read("tests/ld.so").unwrap();
+    let off = elf.sections.iter().find(|s| s.name == b".dynamic").unwrap().header.offset as usize;
+    assert_eq!(&data[off..off + written.len()], written.as_slice());
+}
@@ -0,0 +1,111 @@
+extern crate elfkit;
+
+use std::fs::{self, File};
+use elfkit::{Elf, ElfRef, Error, types};
+
+fn fixture() -> (Vec<u8>, Elf) {
+    let data = fs::read("tests/ld.so").unwrap();
+    let mut f = File::open("tests/ld.so").unwrap();
+    let mut elf = Elf::from_reader(&mut f).unwrap();
+    elf.load_all(&mut f).unwrap();
+    (data, elf)
+}
+
+#[test]
+fn elfref_sections_match_elf() {
+    let (data, elf) = fixture();
+    let r = ElfRef::from_bytes(&data).unwrap();
+
+    assert_eq!(r.section_count(), elf.sections.len());
+    for (a, b) in r.sections().zip(elf.sections.iter()) {
+        let a = a.unwrap();
+        assert_eq!(a.name, &b.name[..]);
+        assert_eq!(a.header.offset, b.header.offset);
+        assert_eq!(a.header.size, b.header.size);
+        if b.header.shtype != types::SectionType::NOBITS {
+            assert_eq!(a.data.len() as u64, b.header.size);
+        }
+    }
+
+    let dynsym = r.section_by_name(b".dynsym").unwrap();
+    assert_eq!(dynsym.header.shtype, types::SectionType::DYNSYM);
+    assert!(r.section_by_name(b".doesnotexist").is_none());
+}
+
+#[test]
+fn elfref_symbols_match_elf() {
+    let (data, elf) = fixture();
+    let r = ElfRef::from_bytes(&data).unwrap();
+
+    let mut checked = 0;
+    for (a, b) in r.sections().zip(elf.sections.iter()) {
+        let a = a.unwrap();
+        if let Some(syms) = b.content.as_symbols() {
+            let refs : Vec<_> = r.symbols(&a).unwrap().map(|s|s.unwrap()).collect();
+            assert_eq!(refs.len(), syms.len());
+            for (sr, s) in refs.iter().zip(syms.iter()) {
+                assert_eq!(sr.name, &s.name[..]);
+                assert_eq!(sr.value, s.value);
+                assert_eq!(sr.size, s.size);
+                assert_eq!(sr.shndx, s.shndx);
+                assert_eq!(sr.to_owned().name, s.name);
+            }
+            checked += 1;
+        }
+    }
+    assert!(checked > 0);
+}
+
+#[test]
+fn elfref_relocations_and_dynamic() {
+    let (data, elf) = fixture();
+    let r = ElfRef::from_bytes(&data).unwrap();
+
+    for (a, b) in r.sections().zip(elf.sections.iter()) {
+        let a = a.unwrap();
+        if let Some(relocs) = b.content.as_relocations() {
+            let refs : Vec<_> = r.relocations(&a).unwrap().map(|s|s.unwrap()).collect();
+            assert_eq!(refs.len(), relocs.len());
+            for (rr, r) in refs.iter().zip(relocs.iter()) {
+                assert_eq!(rr.addr, r.addr);
+                assert_eq!(rr.sym, r.sym);
+                assert_eq!(rr.addend, r.addend);
+            }
+        }
+        if let Some(dynamic) = b.content.as_dynamic() {
+            let refs : Vec<_> = r.dynamic(&a).unwrap().map(|d|d.unwrap()).collect();
+            assert_eq!(refs.len(), dynamic.len());
+            for (dr, d) in refs.iter().zip(dynamic.iter()) {
+                assert_eq!(dr.dhtype, d.dhtype);
+            }
+        }
+    }
+}
+
+#[test]
+fn elfref_truncated() {
+    let data = fs::read("tests/ld.so").unwrap();
+    let r = ElfRef::from_bytes(&data[..data.len() - 100]);
+    assert!(r.is_err() || r.unwrap().sections().any(|s| s.is_err()));
+}
+
+#[test]
+fn elfref_truncated_entries() {
+    let data = fs::read("tests/ld.so").unwrap();
+    let r = ElfRef::from_bytes(&data).unwrap();
+
+    // a section that isn't made of whole entries
+    for name in &[&b".dynsym"[..], b".rela.dyn", b".dynamic"] {
+        let mut sec = r.section_by_name(name).unwrap();
+        sec.data = &sec.data[..sec.data.len() - 5];
+        let err = match *name {
+            b".dynsym"   => r.symbols(&sec).err(),
+            b".rela.dyn" => r.relocations(&sec).err(),
+            _            => r.dynamic(&sec).err(),
+        };
+        match err {
+            Some(Error::TruncatedInput) => {},
+            any => panic!("expected TruncatedInput for {}, got {:?}", String::from_utf8_lossy(name), any),
+        }
+    }
+}
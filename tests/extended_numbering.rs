@@ -0,0 +1,105 @@
+extern crate elfkit;
+
+use elfkit::{Elf, ElfRef, Header, Section, SectionContent, types, symbol, strtab};
+use std::io::Cursor;
+
+const NUM_TEXT: usize = 0xff08;
+
+fn fixture() -> Elf {
+    let header = Header{etype: types::ElfType::REL, ..Default::default()};
+    let mut elf = Elf::from_header(header);
+
+    elf.sections.push(Section::default());
+    for _ in 0..NUM_TEXT {
+        elf.sections.push(Section::new(b".text".to_vec(), types::SectionType::PROGBITS,
+        types::SectionFlags::ALLOC | types::SectionFlags::EXECINSTR,
+        SectionContent::Raw(vec![0xc3]), 0, 0));
+    }
+
+    let sh_index_strtab = elf.sections.len();
+    elf.sections.push(Section::new(b".strtab".to_vec(), types::SectionType::STRTAB,
+    types::SectionFlags::empty(),
+    SectionContent::Strtab(strtab::Strtab::default()), 0, 0));
+
+    let mut symbols = vec![symbol::Symbol::default()];
+    for (name, shndx) in [(b"low".to_vec(), 10), (b"high".to_vec(), NUM_TEXT as u32 - 2)] {
+        symbols.push(symbol::Symbol{
+            shndx:  symbol::SymbolSectionIndex::Section(shndx),
+            name,
+            stype:  types::SymbolType::FUNC,
+            bind:   types::SymbolBind::GLOBAL,
+            size:   1,
+            ..Default::default()
+        });
+    }
+    let sh_index_symtab = elf.sections.len();
+    elf.sections.push(Section::new(b".symtab".to_vec(), types::SectionType::SYMTAB,
+    types::SectionFlags::empty(),
+    SectionContent::Symbols(symbols), sh_index_strtab as u32, 1));
+
+    elf.sections.push(Section::new(b".symtab_shndx".to_vec(), types::SectionType::SYMTAB_SHNDX,
+    types::SectionFlags::empty(),
+    SectionContent::Raw(Vec::new()), sh_index_symtab as u32, 0));
+
+    elf.sections.push(Section::new(b".shstrtab".to_vec(), types::SectionType::STRTAB,
+    types::SectionFlags::empty(),
+    SectionContent::Strtab(strtab::Strtab::default()), 0, 0));
+
+    elf.sync_all().unwrap();
+
+    let mut off = elf.header.size() as u64;
+    for sec in &mut elf.sections[1..] {
+        sec.header.offset = off;
+        off += sec.header.size;
+    }
+    elf
+}
+
+fn write(elf: &mut Elf) -> Vec<u8> {
+    let mut io = Cursor::new(Vec::new());
+    elf.to_writer(&mut io).unwrap();
+    io.into_inner()
+}
+
+#[test]
+fn extended_numbering_roundtrip() {
+    let mut elf = fixture();
+    let num = elf.sections.len();
+    let bytes = write(&mut elf);
+
+    let mut io = Cursor::new(&bytes);
+    let mut elf = Elf::from_reader(&mut io).unwrap();
+    assert_eq!(elf.header.shnum as usize, num);
+    assert_eq!(elf.header.shstrndx as usize, num - 1);
+    assert_eq!(elf.sections.len(), num);
+    assert_eq!(elf.sections[num - 1].name, b".shstrtab");
+
+    elf.load_all(&mut io).unwrap();
+    let symbols = elf.sections[num - 3].content.as_symbols().unwrap();
+    assert_eq!(symbols[1].name, b"low");
+    assert_eq!(symbols[1].shndx, symbol::SymbolSectionIndex::Section(10));
+    assert_eq!(symbols[2].name, b"high");
+    assert_eq!(symbols[2].shndx, symbol::SymbolSectionIndex::Section(NUM_TEXT as u32 - 2));
+}
+
+#[test]
+fn extended_numbering_elfref() {
+    let mut elf = fixture();
+    let num = elf.sections.len();
+    let bytes = write(&mut elf);
+
+    let r = ElfRef::from_bytes(&bytes).unwrap();
+    assert_eq!(r.section_count(), num);
+    let symtab = r.section_by_name(b".symtab").unwrap();
+    let symbols : Vec<_> = r.symbols(&symtab).unwrap().map(|s|s.unwrap()).collect();
+    assert_eq!(symbols[2].name, b"high");
+    assert_eq!(symbols[2].shndx, symbol::SymbolSectionIndex::Section(NUM_TEXT as u32 - 2));
+}
+
+#[test]
+fn extended_numbering_needs_symtab_shndx() {
+    let mut elf = fixture();
+    let i = elf.sections.iter().position(|s| s.name == b".symtab_shndx").unwrap();
+    elf.sections[i].header.shtype = types::SectionType::PROGBITS;
+    assert!(elf.sync_all().is_err());
+}
@@ -0,0 +1,141 @@
+extern crate elfkit;
+
+use elfkit::{Elf, ElfRef, Header, Section, SectionContent, SymbolicLinker, types, symbol, strtab};
+use elfkit::group::Group;
+use elfkit::loader;
+use elfkit::relocation::{Relocation, RelocationType};
+use std::io::Cursor;
+
+fn comdat(members: Vec<u32>) -> SectionContent {
+    SectionContent::Group(Group {
+        flags:   types::GroupFlags::COMDAT,
+        members,
+    })
+}
+
+fn text(name: &[u8]) -> Section {
+    Section::new(name.to_vec(), types::SectionType::PROGBITS,
+    types::SectionFlags::ALLOC | types::SectionFlags::EXECINSTR | types::SectionFlags::GROUP,
+    SectionContent::Raw(vec![0xc3]), 0, 0)
+}
+
+fn symbols() -> Vec<symbol::Symbol> {
+    vec![
+        symbol::Symbol::default(),
+        symbol::Symbol{
+            shndx:  symbol::SymbolSectionIndex::Section(3),
+            name:   b".L.local".to_vec(),
+            stype:  types::SymbolType::NOTYPE,
+            bind:   types::SymbolBind::LOCAL,
+            ..Default::default()
+        },
+        symbol::Symbol{
+            shndx:  symbol::SymbolSectionIndex::Section(2),
+            name:   b"inline_fn".to_vec(),
+            stype:  types::SymbolType::FUNC,
+            bind:   types::SymbolBind::GLOBAL,
+            size:   1,
+            ..Default::default()
+        },
+    ]
+}
+
+#[test]
+fn group_roundtrip() {
+    let header = Header{etype: types::ElfType::REL, ..Default::default()};
+    let mut elf = Elf::from_header(header);
+
+    elf.sections.push(Section::default());
+    elf.sections.push(Section::new(b".group".to_vec(), types::SectionType::GROUP,
+    types::SectionFlags::empty(), comdat(vec![2, 3]), 5, 2));
+    elf.sections.push(text(b".text.inline_fn"));
+    elf.sections.push(text(b".rodata.inline_fn"));
+    elf.sections.push(Section::new(b".strtab".to_vec(), types::SectionType::STRTAB,
+    types::SectionFlags::empty(),
+    SectionContent::Strtab(strtab::Strtab::default()), 0, 0));
+    elf.sections.push(Section::new(b".symtab".to_vec(), types::SectionType::SYMTAB,
+    types::SectionFlags::empty(),
+    SectionContent::Symbols(symbols()), 4, 0));
+    elf.sections.push(Section::new(b".shstrtab".to_vec(), types::SectionType::STRTAB,
+    types::SectionFlags::empty(),
+    SectionContent::Strtab(strtab::Strtab::default()), 0, 0));
+    elf.sync_all().unwrap();
+
+    let mut off = elf.header.size() as u64;
+    for sec in &mut elf.sections[1..] {
+        sec.header.offset = off;
+        off += sec.header.size;
+    }
+    assert_eq!(elf.sections[1].header.size, 12);
+    assert_eq!(elf.sections[1].header.entsize, 4);
+
+    let mut io = Cursor::new(Vec::new());
+    elf.to_writer(&mut io).unwrap();
+    let bytes = io.into_inner();
+
+    let mut io = Cursor::new(&bytes);
+    let mut elf = Elf::from_reader(&mut io).unwrap();
+    elf.load_all(&mut io).unwrap();
+    let group = elf.sections[1].content.as_group().unwrap().clone();
+    assert!(group.is_comdat());
+    assert_eq!(group.members, vec![2, 3]);
+
+    let r = ElfRef::from_bytes(&bytes).unwrap();
+    assert_eq!(r.group(&r.section(1).unwrap()).unwrap(), group);
+
+    // members follow sections around
+    elf.remove_section(2).unwrap();
+    assert_eq!(elf.sections[1].content.as_group().unwrap().members, vec![2]);
+    elf.insert_section(1, text(b".text.other")).unwrap();
+    assert_eq!(elf.sections[2].content.as_group().unwrap().members, vec![3]);
+    elf.move_section(3, 1).unwrap();
+    assert_eq!(elf.sections[3].content.as_group().unwrap().members, vec![1]);
+}
+
+fn object(name: &str) -> loader::State {
+    let group = Section::new(b".group".to_vec(), types::SectionType::GROUP,
+    types::SectionFlags::empty(), comdat(vec![2, 3, 4]), 5, 2);
+
+    // section 4 was .rela.text.inline_fn, which the loader folds into its target
+    let relocs = vec![Relocation{
+        addr:   0,
+        sym:    1,
+        rtype:  RelocationType::R_X86_64_PC32,
+        addend: -4,
+    }];
+
+    loader::State::Object{
+        hash:     String::from(name),
+        name:     String::from(name),
+        symbols:  symbols(),
+        header:   Header{shnum: 6, ..Default::default()},
+        sections: vec![
+            (1, group, Vec::new()),
+            (2, text(b".text.inline_fn"), relocs),
+            (3, text(b".rodata.inline_fn"), Vec::new()),
+        ],
+    }
+}
+
+#[test]
+fn group_comdat_link() {
+    let mut linker = SymbolicLinker::default();
+    linker.link_all(vec![object("a.o"), object("b.o")]).unwrap();
+
+    let text : Vec<_> = linker.objects.values().filter(|o| o.section.name == b".text.inline_fn").collect();
+    assert_eq!(text.len(), 1);
+    let kept = text[0];
+    assert_eq!(kept.relocs.len(), 1);
+    assert_eq!(linker.objects.values().filter(|o| o.section.name == b".rodata.inline_fn").count(), 1);
+    assert_eq!(linker.objects.values().filter(|o| o.section.name == b".group").count(), 0);
+
+    let defs : Vec<_> = linker.symtab.iter().filter(|l| l.sym.name == b"inline_fn").collect();
+    assert_eq!(defs.len(), 1);
+    assert_eq!(defs[0].obj, kept.lid);
+
+    // the local symbol of the discarded copy points at the kept rodata
+    let rodata = linker.objects.values().find(|o| o.section.name == b".rodata.inline_fn").unwrap();
+    for l in linker.symtab.iter().filter(|l| l.sym.name == b".L.local") {
+        assert_eq!(l.obj, rodata.lid);
+    }
+}
@@ -0,0 +1,136 @@
+extern crate elfkit;
+
+use elfkit::{Elf, ElfRef, Header, Section, SectionContent, Symbol, SymbolSectionIndex, types};
+use elfkit::hash::{self, GnuHash, SysvHash};
+use std::fs::{self, File};
+use std::io::Cursor;
+
+fn symbols() -> Vec<Symbol> {
+    let mut r = vec![Symbol::default()];
+    r.push(Symbol{
+        name:   b"local".to_vec(),
+        shndx:  SymbolSectionIndex::Section(1),
+        bind:   types::SymbolBind::LOCAL,
+        ..Default::default()
+    });
+    for i in 0..40 {
+        r.push(Symbol{
+            name:   format!("sym{}", i).into_bytes(),
+            shndx:  SymbolSectionIndex::Section(1),
+            bind:   types::SymbolBind::GLOBAL,
+            value:  i,
+            ..Default::default()
+        });
+        if i % 10 == 0 {
+            r.push(Symbol{
+                name:   format!("undef{}", i).into_bytes(),
+                bind:   types::SymbolBind::GLOBAL,
+                ..Default::default()
+            });
+        }
+    }
+    r
+}
+
+#[test]
+fn hash_functions() {
+    assert_eq!(hash::gnu_hash(b""), 5381);
+    assert_eq!(hash::gnu_hash(b"printf"), 0x156b2bb8);
+    assert_eq!(hash::sysv_hash(b"printf"), 0x077905a6);
+    assert_eq!(hash::sysv_hash(b"exit"), 0x0006cf04);
+}
+
+#[test]
+#[allow(deprecated)]
+fn hash_deprecated_symbol_shims() {
+    assert_eq!(elfkit::symbol::sysv_hash(&String::from("printf")), 0x077905a6);
+
+    let syms = symbols();
+    let sec = elfkit::symbol::symhash(&Header::default(), &syms, 3).unwrap();
+    assert_eq!(sec.header.link, 3);
+    match sec.content {
+        SectionContent::Hash(ref h) => assert_eq!(h.chains.len(), syms.len()),
+        _ => panic!("symhash did not make a hash section"),
+    }
+}
+
+#[test]
+fn hash_generate_and_lookup() {
+    let eh = Header::default();
+    let mut syms = symbols();
+    let remap = hash::gnu_hash_order(&mut syms);
+    assert_eq!(remap[0], 0);
+    assert_eq!(remap[1], 1);
+
+    // unhashed symbols first, in their original order
+    let symoffset = syms.iter().position(hash::is_gnu_hashed).unwrap();
+    assert_eq!(symoffset, 6);
+    assert!(syms[symoffset..].iter().all(hash::is_gnu_hashed));
+
+    let gnu  = GnuHash::new(&syms, &eh).unwrap();
+    let sysv = SysvHash::new(&syms);
+    assert_eq!(gnu.symoffset as usize, symoffset);
+    assert_eq!(gnu.chains.len(), 40);
+    assert_eq!(sysv.buckets.len(), 37);
+    assert_eq!(sysv.chains.len(), syms.len());
+
+    for i in 0..40 {
+        let name = format!("sym{}", i).into_bytes();
+        let at = syms.iter().position(|s| s.name == name).unwrap();
+        assert_eq!(gnu.lookup(&name, &syms, &eh), Some(at));
+        assert_eq!(sysv.lookup(&name, &syms), Some(at));
+    }
+    assert_eq!(gnu.lookup(b"undef0", &syms, &eh), None);
+    assert_eq!(gnu.lookup(b"nope", &syms, &eh), None);
+    assert_eq!(sysv.lookup(b"undef0", &syms), Some(2));
+    assert_eq!(sysv.lookup(b"nope", &syms), None);
+
+    let mut sec = Section::new(b".gnu.hash".to_vec(), types::SectionType::GNU_HASH,
+                               types::SectionFlags::ALLOC,
+                               SectionContent::GnuHash(gnu.clone()), 0, 0);
+    sec.sync(&eh, None).unwrap();
+    let mut io = Cursor::new(Vec::new());
+    sec.to_writer(&mut io, &eh).unwrap();
+    let bytes = io.into_inner();
+    assert_eq!(bytes.len() as u64, sec.header.size);
+    assert_eq!(GnuHash::from_reader(bytes.as_slice(), &eh).unwrap(), gnu);
+}
+
+#[test]
+fn hash_gnu_needs_sorted_symbols() {
+    let eh = Header::default();
+    let mut syms = symbols();
+    assert!(GnuHash::new(&syms, &eh).is_err());
+    hash::gnu_hash_order(&mut syms);
+    syms[6..].reverse();
+    assert!(GnuHash::new(&syms, &eh).is_err());
+}
+
+#[test]
+fn hash_fixture() {
+    let mut f = File::open("tests/ld.so").unwrap();
+    let mut elf = Elf::from_reader(&mut f).unwrap();
+    elf.load_all(&mut f).unwrap();
+
+    let find = |name: &[u8]| elf.sections.iter().find(|s| s.name == name).unwrap();
+    let syms = find(b".dynsym").content.as_symbols().unwrap();
+    let sysv = find(b".hash").content.as_hash().unwrap();
+    let gnu  = find(b".gnu.hash").content.as_gnu_hash().unwrap();
+
+    for (i, sym) in syms.iter().enumerate().skip(1) {
+        assert_eq!(sysv.lookup(&sym.name, syms), Some(i));
+        assert_eq!(gnu.lookup(&sym.name, syms, &elf.header), Some(i));
+    }
+
+    let data = fs::read("tests/ld.so").unwrap();
+    let r = ElfRef::from_bytes(&data).unwrap();
+    for name in [&b".hash"[..], &b".gnu.hash"[..]] {
+        let sec = r.section_by_name(name).unwrap();
+        let mut io = Cursor::new(Vec::new());
+        find(name).to_writer(&mut io, &elf.header).unwrap();
+        let written = io.into_inner();
+        assert_eq!(&written[sec.header.offset as usize..], sec.data);
+    }
+    assert_eq!(&r.hash(&r.section_by_name(b".hash").unwrap()).unwrap(), sysv);
+    assert_eq!(&r.gnu_hash(&r.section_by_name(b".gnu.hash").unwrap()).unwrap(), gnu);
+}
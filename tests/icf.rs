@@ -0,0 +1,81 @@
+extern crate elfkit;
+
+use elfkit::{Header, Section, SectionContent, SymbolicLinker, types, symbol};
+use elfkit::symbolic_linker::Icf;
+use elfkit::loader;
+use elfkit::relocation::{Relocation, RelocationType};
+
+fn text(name: &[u8], code: Vec<u8>) -> Section {
+    Section::new(name.to_vec(), types::SectionType::PROGBITS,
+    types::SectionFlags::ALLOC | types::SectionFlags::EXECINSTR,
+    SectionContent::Raw(code), 0, 0)
+}
+
+fn func(name: &[u8], shndx: u32) -> symbol::Symbol {
+    symbol::Symbol{
+        shndx:  symbol::SymbolSectionIndex::Section(shndx),
+        name:   name.to_vec(),
+        stype:  types::SymbolType::FUNC,
+        bind:   types::SymbolBind::GLOBAL,
+        size:   6,
+        ..Default::default()
+    }
+}
+
+fn call(sym: u32, rtype: RelocationType) -> Vec<Relocation> {
+    vec![Relocation{addr: 1, sym, rtype, addend: -4}]
+}
+
+/// f_a and f_b call g_a and g_b, which are the same. h calls f_b, or takes its address
+fn object(rtype: RelocationType) -> loader::State {
+    let code = vec![0xe8, 0, 0, 0, 0, 0xc3];
+    loader::State::Object{
+        hash:     String::from("a.o"),
+        name:     String::from("a.o"),
+        symbols:  vec![symbol::Symbol::default(),
+            func(b"f_a", 1), func(b"f_b", 2), func(b"g_a", 3), func(b"g_b", 4), func(b"h", 5)],
+        header:   Header{shnum: 6, ..Default::default()},
+        sections: vec![
+            (1, text(b".text.f_a", code.clone()), call(3, RelocationType::R_X86_64_PLT32)),
+            (2, text(b".text.f_b", code.clone()), call(4, RelocationType::R_X86_64_PLT32)),
+            (3, text(b".text.g_a", vec![0x31, 0xc0, 0xc3]), Vec::new()),
+            (4, text(b".text.g_b", vec![0x31, 0xc0, 0xc3]), Vec::new()),
+            (5, text(b".text.h", code), call(2, rtype)),
+        ],
+    }
+}
+
+fn link(rtype: RelocationType, mode: Icf) -> (SymbolicLinker, (usize, u64)) {
+    let mut linker = SymbolicLinker::default();
+    linker.link_all(vec![object(rtype)]).unwrap();
+    let folded = linker.icf(mode, &[]);
+    (linker, folded)
+}
+
+fn defined_in<'a>(linker: &'a SymbolicLinker, name: &[u8]) -> &'a [u8] {
+    let link = linker.symtab.iter().find(|l| l.sym.name == name).unwrap();
+    &linker.objects[&link.obj].section.name
+}
+
+#[test]
+fn icf_calls() {
+    let (linker, folded) = link(RelocationType::R_X86_64_PLT32, Icf::Safe);
+    assert_eq!(folded, (2, 9));
+    assert_eq!(defined_in(&linker, b"g_b"), b".text.g_a");
+    assert_eq!(defined_in(&linker, b"f_b"), b".text.f_a");
+    assert_eq!(defined_in(&linker, b"h"), b".text.h");
+}
+
+#[test]
+fn icf_address_taken() {
+    let (linker, folded) = link(RelocationType::R_X86_64_PC32, Icf::Safe);
+    assert_eq!(folded, (1, 3));
+    assert_eq!(defined_in(&linker, b"f_b"), b".text.f_b");
+
+    let (linker, folded) = link(RelocationType::R_X86_64_PC32, Icf::All);
+    assert_eq!(folded, (2, 9));
+    assert_eq!(defined_in(&linker, b"f_b"), b".text.f_a");
+
+    let (_, folded) = link(RelocationType::R_X86_64_PC32, Icf::None);
+    assert_eq!(folded, (0, 0));
+}
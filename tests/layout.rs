@@ -1,6 +1,6 @@
 extern crate elfkit;
 
-use elfkit::{Elf, Section, SectionHeader, SectionContent, types, dynamic, segment};
+use elfkit::{Elf, Error, Section, SectionHeader, SectionContent, types, dynamic, segment};
 
 
 fn fixture_section_dynamic() -> Section {
@@ -137,8 +137,8 @@ fn layout_just_text() {
     assert_eq!(elf.sections[1].header.offset, elf.sections[1].header.addr,
        ".text section offset and address must be identical");
 
-    assert_eq!(elf.segments.len(), 2,
-        "expect exactly two segments");
+    assert_eq!(elf.segments.len(), 3,
+        "expect exactly 3 segments, including GNU_STACK");
 
     let phdr_segments :Vec<&segment::SegmentHeader> = elf.segments.iter().filter(|x| x.phtype == types::SegmentType::PHDR).collect();
     assert_eq!(phdr_segments.len(), 1,
@@ -247,8 +247,8 @@ fn layout_text_and_bss_1() {
     assert_eq!(elf.sections[2].name, b".bss",
        ".bss section must be at shndx 2");
 
-    assert_eq!(elf.segments.len(), 3,
-        "expect exactly 3 segments");
+    assert_eq!(elf.segments.len(), 4,
+        "expect exactly 4 segments, including GNU_STACK");
     let load_segments :Vec<&segment::SegmentHeader> = elf.segments.iter().filter(|x| x.phtype == types::SegmentType::LOAD).collect();
     assert_eq!(load_segments.len(), 2,
         "expect exactly 2 load segments");
@@ -298,8 +298,8 @@ fn layout_text_and_bss_2() {
     assert_eq!(elf.sections[2].name, b".text",
        ".text section must be at shndx 1");
 
-    assert_eq!(elf.segments.len(), 4,
-        "expect exactly 4 segments");
+    assert_eq!(elf.segments.len(), 5,
+        "expect exactly 5 segments, including GNU_STACK");
     let load_segments :Vec<&segment::SegmentHeader> = elf.segments.iter().filter(|x| x.phtype == types::SegmentType::LOAD).collect();
     assert_eq!(load_segments.len(), 3,
         "expect exactly 3 load segments");
@@ -352,8 +352,8 @@ fn layout_dynamic_and_interp() {
     assert_eq!(elf.sections[4].name, b".dynamic",
        ".dynamic section must be at shndx 4");
 
-    assert_eq!(elf.segments.len(), 6,
-        "expect exactly 6 segments");
+    assert_eq!(elf.segments.len(), 7,
+        "expect exactly 7 segments, including GNU_STACK");
     let load_segments :Vec<&segment::SegmentHeader> = elf.segments.iter().filter(|x| x.phtype == types::SegmentType::LOAD).collect();
     assert_eq!(load_segments.len(), 3,
         "expect exactly 3 load segments");
@@ -377,8 +377,8 @@ fn layout_align() {
     elf.sections.push(fixture_section_text());
     elf.layout().unwrap();
 
-    assert_eq!(elf.segments.len(), 2,
-        "expect exactly two segments");
+    assert_eq!(elf.segments.len(), 3,
+        "expect exactly 3 segments, including GNU_STACK");
 
     assert!(elf.sections[1].header.addr % 16 == 0,
         "expect section 1 to be aligned by 16 bytes");
@@ -414,8 +414,8 @@ fn layout_stable() {
     assert_eq!(elf.sections[0].header.offset , 0,
        "section 0 is always offset 0");
 
-    assert_eq!(elf.segments.len(), 5,
-        "expect exactly 5 segments");
+    assert_eq!(elf.segments.len(), 6,
+        "expect exactly 6 segments, including GNU_STACK");
 
     for i in 0..5 {
         elf.sections[i].addrlock = true;
@@ -464,11 +464,154 @@ fn layout_many_bss() {
         println!("{:?}", seg);
     }
 
-    assert_eq!(elf.segments.len(), 5,
-        "expect exactly 5 segments");
+    assert_eq!(elf.segments.len(), 6,
+        "expect exactly 6 segments, including GNU_STACK");
 
     let load_segments :Vec<&segment::SegmentHeader> = elf.segments.iter().filter(|x| x.phtype == types::SegmentType::LOAD).collect();
     let segment0 = load_segments.get(0).unwrap();;
     assert!(!segment0.flags.contains(types::SegmentFlags::WRITABLE),
         "first load segment must NOT be writable");
 }
+
+#[test]
+fn layout_image_base() {
+    let mut elf = Elf{image_base: 0x10000, ..Default::default()};
+    elf.sections.push(Section::default());
+    elf.sections.push(fixture_section_rodata());
+    elf.sections.push(fixture_section_text());
+    elf.section_start.insert(b".text".to_vec(), 0x11000);
+    elf.layout().unwrap();
+
+    assert_eq!(elf.sections[2].header.addr, 0x11000,
+        ".text must be at its fixed address");
+    assert_eq!(elf.sections[2].header.offset % 0x1000, 0,
+        ".text offset must be congruent to its address");
+    assert!(elf.sections[1].header.addr > 0x10000 && elf.sections[1].header.addr < 0x11000,
+        ".rodata must be placed above the image base");
+
+    let load_segments :Vec<&segment::SegmentHeader> = elf.segments.iter().filter(|x| x.phtype == types::SegmentType::LOAD).collect();
+    assert_eq!(load_segments.len(), 2,
+        "the fixed address must start a new load segment");
+    assert_eq!(load_segments[0].vaddr, 0x10000);
+    assert_eq!(load_segments[0].offset, 0);
+    assert_eq!(load_segments[1].vaddr, 0x11000);
+    assert_eq!(load_segments[1].offset, elf.sections[2].header.offset);
+    assert_eq!(load_segments[1].align, 0x1000,
+        "segments must be aligned to what the image base allows");
+
+    // nothing before the fixed address, so the headers aren't mapped
+    let mut elf = Elf{image_base: 0x400000, ..Default::default()};
+    elf.sections.push(Section::default());
+    elf.sections.push(fixture_section_text());
+    elf.section_start.insert(b".text".to_vec(), 0x400000);
+    elf.layout().unwrap();
+    assert_eq!(elf.sections[1].header.addr, 0x400000);
+    assert_eq!(elf.segments.iter().filter(|x| x.phtype == types::SegmentType::PHDR).count(), 0,
+        "headers outside of any segment must not get a phdr segment");
+    assert_eq!(elf.header.phnum as usize, elf.segments.len());
+}
+
+#[test]
+fn layout_section_start_overlaps() {
+    let mut elf = Elf{image_base: 0x400000, ..Default::default()};
+    elf.sections.push(Section::default());
+    elf.sections.push(fixture_section_text());
+    elf.sections.push(fixture_section_data());
+    elf.section_start.insert(b".data".to_vec(), 0x400010);
+    match elf.layout() {
+        Err(Error::SectionStartOverlaps{sec, ..}) => assert_eq!(sec, ".data"),
+        any => panic!("expected overlap error, got {:?}", any),
+    }
+}
+
+#[test]
+fn layout_relro() {
+    let mut elf = Elf{relro: vec![b".data.rel.ro".to_vec()], ..Default::default()};
+    elf.sections.push(Section::default());
+    elf.sections.push(fixture_section_data());
+    elf.sections.push(fixture_section_text());
+    let mut relro = fixture_section_data();
+    relro.name = b".data.rel.ro".to_vec();
+    elf.sections.push(relro);
+    elf.sections.push(fixture_section_bss());
+    elf.reorder().unwrap();
+    assert_eq!(elf.sections[2].name, b".data.rel.ro",
+        "relro must come first in the writable segment");
+    elf.layout().unwrap();
+
+    let relro :Vec<&segment::SegmentHeader> = elf.segments.iter().filter(|x| x.phtype == types::SegmentType::GNU_RELRO).collect();
+    assert_eq!(relro.len(), 1,
+        "expect exactly 1 relro segment");
+    let relro = relro[0];
+    assert_eq!(relro.vaddr, elf.sections[2].header.addr);
+    assert_eq!((relro.vaddr + relro.memsz) % 0x1000, 0,
+        "relro must end on a page, or the dynamic linker doesn't protect the last one");
+    assert!(elf.sections[3].header.addr >= relro.vaddr + relro.memsz,
+        ".data must not be in relro");
+
+    let load = elf.segments.iter().find(|x| x.phtype == types::SegmentType::LOAD && x.vaddr == relro.vaddr)
+        .expect("relro must start a load segment");
+    assert!(load.flags.contains(types::SegmentFlags::WRITABLE));
+    assert_eq!(load.vaddr + load.memsz, elf.sections[4].header.addr + elf.sections[4].header.size,
+        "the writable segment must still cover .bss");
+}
+
+#[test]
+fn layout_separate_code() {
+    let mut elf = Elf{separate_code: true, execstack: true, ..Default::default()};
+    elf.sections.push(Section::default());
+    elf.sections.push(fixture_section_rodata());
+    elf.sections.push(fixture_section_text());
+    elf.sections.push(fixture_section_data());
+    elf.layout().unwrap();
+
+    let load_segments :Vec<&segment::SegmentHeader> = elf.segments.iter().filter(|x| x.phtype == types::SegmentType::LOAD).collect();
+    assert_eq!(load_segments.len(), 3,
+        "expect exactly 3 load segments");
+    assert!(!load_segments[0].flags.contains(types::SegmentFlags::EXECUTABLE),
+        "headers and .rodata must not be executable");
+    assert!(load_segments[1].flags.contains(types::SegmentFlags::EXECUTABLE));
+    assert_eq!(load_segments[1].offset % 0x1000, 0,
+        "code must start on a page of its own");
+    assert_eq!(load_segments[2].offset % 0x1000, 0,
+        "code must end on a page of its own");
+
+    let stack = elf.segments.iter().find(|x| x.phtype == types::SegmentType::GNU_STACK)
+        .expect("expect a stack segment");
+    assert!(stack.flags.contains(types::SegmentFlags::EXECUTABLE));
+}
+
+#[test]
+fn layout_tls() {
+    let mut elf = Elf::default();
+    elf.sections.push(Section::default());
+    let mut tbss = fixture_section_bss();
+    tbss.name = b".tbss".to_vec();
+    tbss.header.flags.insert(types::SectionFlags::TLS);
+    tbss.header.addralign = 64;
+    elf.sections.push(tbss);
+    elf.sections.push(fixture_section_data());
+    elf.sections.push(fixture_section_text());
+    let mut tdata = fixture_section_data();
+    tdata.name = b".tdata".to_vec();
+    tdata.header.flags.insert(types::SectionFlags::TLS);
+    elf.sections.push(tdata);
+    elf.reorder().unwrap();
+    assert_eq!(elf.sections[2].name, b".tdata");
+    assert_eq!(elf.sections[3].name, b".tbss",
+        ".tbss must directly follow .tdata");
+    elf.layout().unwrap();
+
+    let tls :Vec<&segment::SegmentHeader> = elf.segments.iter().filter(|x| x.phtype == types::SegmentType::TLS).collect();
+    assert_eq!(tls.len(), 1,
+        "expect exactly 1 tls segment");
+    let tls = tls[0];
+    assert_eq!(tls.vaddr, elf.sections[2].header.addr);
+    assert_eq!(tls.vaddr % 64, 0,
+        "the template must be aligned for .tbss too");
+    assert_eq!(tls.align, 64);
+    assert_eq!(tls.filesz, elf.sections[2].header.size);
+    assert_eq!(tls.memsz, elf.sections[3].header.addr + elf.sections[3].header.size - tls.vaddr);
+    assert_eq!(elf.sections[4].header.addr, elf.sections[3].header.addr,
+        ".tbss takes no space outside of the template");
+}
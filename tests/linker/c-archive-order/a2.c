@@ -0,0 +1,2 @@
+/* only referenced from libb.a, which comes after liba.a */
+int depth(void) { return 100; }
@@ -0,0 +1,4 @@
+int ping(int n);
+int depth(void);
+
+int pong(int n) { return n ? ping(n - 1) + 1 : depth(); }
@@ -0,0 +1,10 @@
+#include <stdio.h>
+
+int ping(int n);
+int whole(void) __attribute__((weak));
+
+int main() {
+    printf("ping %d\n", ping(5));
+    printf("whole %d\n", whole ? whole() : 0);
+    return 0;
+}
@@ -0,0 +1,2 @@
+/* nothing references this strongly, so only --whole-archive pulls it in */
+int whole(void) { return 7; }
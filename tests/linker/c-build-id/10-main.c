@@ -0,0 +1,13 @@
+// freestanding, so the static link doesn't depend on a static libc
+
+static long sys_write(int fd, const char *buf, unsigned long len) {
+    long r;
+    __asm__ volatile ("syscall" : "=a"(r) : "a"(1), "D"(fd), "S"(buf), "d"(len) : "rcx", "r11", "memory");
+    return r;
+}
+
+void _start(void) {
+    sys_write(1, "hello\n", 6);
+    __asm__ volatile ("syscall" : : "a"(60), "D"(0) : "rcx", "r11", "memory");
+    for (;;) {}
+}
@@ -0,0 +1,6 @@
+//smaller and less aligned than the one in main.c, the linker needs to pick the larger one
+int counters[4];
+
+void count(int i){
+    counters[i]++;
+}
@@ -3,15 +3,22 @@
 
 
 void init();
+void count(int i);
 
 //this will be emitted as COMMON
 char *hello;
 
+//so is this, with a larger size and alignment than in counters.c
+int counters[16] __attribute__((aligned(64)));
+
 int main(int argc, char**argv){
     init();
 
     struct utsname unameData;
     uname(&unameData);
     printf(hello);
+
+    count(15);
+    printf("\n%d %d\n", counters[15], (int)((unsigned long)counters % 64));
     return 42;
 }
@@ -0,0 +1,19 @@
+#include <stdio.h>
+
+extern int counter;
+extern const char *greeting;
+int  bump(int by);
+int  twice(int x);
+
+// a tail call, jmp *twice@GOTPCREL(%rip) with -fno-plt
+__attribute__((noinline)) int tail(int x) {
+    return twice(x + 1);
+}
+
+int main() {
+    int *c = &counter;
+    bump(2);
+    *c += 1;
+    printf("%s %d %d\n", greeting, counter, tail(counter));
+    return 0;
+}
@@ -0,0 +1,11 @@
+int counter = 40;
+const char *greeting = "hello";
+
+int bump(int by) {
+    counter += by;
+    return counter;
+}
+
+int twice(int x) {
+    return x * 2;
+}
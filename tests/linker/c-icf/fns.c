@@ -0,0 +1,19 @@
+// pairs of functions that compile to the same code
+
+// only called, so even safe folds them
+int add_a(int a, int b) { return a + b * 3; }
+int add_b(int a, int b) { return a + b * 3; }
+
+// recursive, each calling itself
+int fact_a(int n) { return n < 2 ? 1 : n * fact_a(n - 1); }
+int fact_b(int n) { return n < 2 ? 1 : n * fact_b(n - 1); }
+
+// the same code, but calling different functions, which can't be folded
+int sub_x(int a) { return a - 1; }
+int sub_y(int a) { return a - 2; }
+int call_x(int a) { return sub_x(a) * 7; }
+int call_y(int a) { return sub_y(a) * 7; }
+
+// main takes their address, so only all folds them
+int twice_a(int a) { return a * 2 + 1; }
+int twice_b(int a) { return a * 2 + 1; }
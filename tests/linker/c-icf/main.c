@@ -0,0 +1,18 @@
+#include <stdio.h>
+
+int add_a(int a, int b);
+int add_b(int a, int b);
+int fact_a(int n);
+int fact_b(int n);
+int call_x(int a);
+int call_y(int a);
+int twice_a(int a);
+int twice_b(int a);
+
+int main(){
+    int (*volatile ta)(int) = twice_a;
+    int (*volatile tb)(int) = twice_b;
+    printf("%d %d %d %d %d %d %d %d\n", add_a(1, 2), add_b(3, 4), fact_a(5), fact_b(6),
+           call_x(10), call_y(10), ta(3) + tb(4), ta == tb);
+    return 0;
+}
@@ -0,0 +1,28 @@
+static int resolved;
+
+static int add_generic(int a, int b) {
+    return a + b;
+}
+
+static int add_broken(int a, int b) {
+    return a - b;
+}
+
+// a call that reaches the resolver instead of what it picked returns a pointer
+static int (*resolve_add(void))(int, int) {
+    resolved++;
+    return resolved == 1 ? add_generic : add_broken;
+}
+
+int add(int a, int b) __attribute__((ifunc("resolve_add")));
+
+// an address taken in data and in code must be the same
+int (*add_ptr)(int, int) = add;
+
+int add_ptr_equal(void) {
+    return add_ptr == add;
+}
+
+int resolver_calls(void) {
+    return resolved;
+}
@@ -0,0 +1,11 @@
+#include <stdio.h>
+
+int add(int, int);
+extern int (*add_ptr)(int, int);
+int add_ptr_equal(void);
+int resolver_calls(void);
+
+int main() {
+    printf("%d %d %d %d %d\n", add(2, 3), add_ptr(4, 5), add_ptr == add, add_ptr_equal(), resolver_calls());
+    return 0;
+}
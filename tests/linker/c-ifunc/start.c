@@ -0,0 +1,42 @@
+// freestanding, so this does what a static libc does with the IRELATIVE relocations
+
+typedef struct {
+    unsigned long offset;
+    unsigned long info;
+    long          addend;
+} rela;
+
+extern const rela __rela_iplt_start[] __attribute__((weak));
+extern const rela __rela_iplt_end[] __attribute__((weak));
+
+static long sys_write(int fd, const char *buf, unsigned long len) {
+    long r;
+    __asm__ volatile ("syscall" : "=a"(r) : "a"(1), "D"(fd), "S"(buf), "d"(len) : "rcx", "r11", "memory");
+    return r;
+}
+
+static void sys_exit(int code) {
+    __asm__ volatile ("syscall" : : "a"(60), "D"(code) : "rcx", "r11", "memory");
+    for (;;) {}
+}
+
+int add(int, int);
+extern int (*add_ptr)(int, int);
+int add_ptr_equal(void);
+int resolver_calls(void);
+
+void _start(void) {
+    for (const rela *r = __rela_iplt_start; r < __rela_iplt_end; r++) {
+        unsigned long (*resolver)(void) = (unsigned long (*)(void))r->addend;
+        *(unsigned long *)r->offset = resolver();
+    }
+
+    char line[] = "5 9 1 1 1\n";
+    line[0] = '0' + add(2, 3);
+    line[2] = '0' + add_ptr(4, 5);
+    line[4] = '0' + (add_ptr == add);
+    line[6] = '0' + add_ptr_equal();
+    line[8] = '0' + resolver_calls();
+    sys_write(1, line, sizeof(line) - 1);
+    sys_exit(0);
+}
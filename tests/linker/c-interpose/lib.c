@@ -0,0 +1,4 @@
+int value(void) { return 1; }
+__attribute__((visibility("protected"))) int pvalue(void) { return 10; }
+__attribute__((visibility("hidden"))) int hvalue(void) { return 100; }
+int call_value(void) { return value() + pvalue() + hvalue(); }
@@ -0,0 +1,13 @@
+#include <stdio.h>
+
+int call_value(void);
+
+/* interposes the one in the library, unless it was linked with -Bsymbolic */
+int value(void) { return 2; }
+int pvalue(void) { return 20; }
+int hvalue(void) { return 200; }
+
+int main() {
+    printf("%d\n", call_value());
+    return 0;
+}
@@ -0,0 +1,18 @@
+#include <stdio.h>
+#include <wchar.h>
+
+const char *other(void);
+const char *tail(void);
+const wchar_t *wide(void);
+double half(void);
+
+// the same literals as in other.c, which the linker keeps only once
+const char *greeting = "hello world";
+
+int main(){
+    puts("hello world");
+    puts("world");
+    printf("%s|%s|%s|%s|%ls|%ls|%g %g\n", greeting, other(), tail(), "hello world" + 6,
+           L"wide world", wide(), 1.5, half());
+    return 0;
+}
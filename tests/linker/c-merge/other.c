@@ -0,0 +1,7 @@
+#include <wchar.h>
+
+const char *other(void){ return "hello world"; }
+// ends "hello world", so it is stored as part of it
+const char *tail(void){ return "lo world"; }
+const wchar_t *wide(void){ return L"world"; }
+double half(void){ return 1.5 / 3.0; }
@@ -0,0 +1,32 @@
+#include <stdio.h>
+
+// pointers that need relocating, so this goes to .data.rel.ro
+static const char *const words[] = {"read", "only"};
+static int counter = 1;
+
+// permissions of the mapping addr is in
+static void perms(const char *what, const void *addr) {
+    FILE *f = fopen("/proc/self/maps", "r");
+    char line[512];
+    while (fgets(line, sizeof(line), f)) {
+        unsigned long lo, hi;
+        char p[5];
+        if (sscanf(line, "%lx-%lx %4s", &lo, &hi, p) == 3 &&
+            (unsigned long)addr >= lo && (unsigned long)addr < hi) {
+            printf("%s %s\n", what, p);
+        }
+    }
+    fclose(f);
+}
+
+int main() {
+    int local = 0;
+    counter++;
+    printf("%s %s %d\n", words[0], words[1], counter);
+    perms("words", words);
+    perms("counter", &counter);
+    perms("string", words[0]);
+    perms("main", main);
+    perms("stack", &local);
+    return local;
+}
@@ -0,0 +1,61 @@
+// freestanding, linked with the script only
+
+static long sys_write(int fd, const char *buf, unsigned long len) {
+    long r;
+    __asm__ volatile ("syscall" : "=a"(r) : "a"(1), "D"(fd), "S"(buf), "d"(len) : "rcx", "r11", "memory");
+    return r;
+}
+
+static void sys_exit(int code) {
+    __asm__ volatile ("syscall" : : "a"(60), "D"(code) : "rcx", "r11", "memory");
+    for (;;) {}
+}
+
+static void say(const char *s) {
+    unsigned long len = 0;
+    while (s[len]) {
+        len++;
+    }
+    sys_write(1, s, len);
+}
+
+static void hex(unsigned long v) {
+    char buf[19] = "0x";
+    for (int i = 0; i < 16; i++) {
+        buf[2 + i] = "0123456789abcdef"[(v >> (60 - 4 * i)) & 0xf];
+    }
+    buf[18] = '\n';
+    sys_write(1, buf, 19);
+}
+
+// nothing refers to these, KEEP holds on to them
+__attribute__((section(".table"), used)) const char *table_a = "The quick brown fox ";
+__attribute__((section(".table"), used)) const char *table_b = "jumps over the lazy dog\n";
+
+extern const char *table_start[], *table_end[];
+const char *merged_hello(void);
+const char *merged_tail(void);
+extern char bss_start[], stack_top[], stack_size[], end_of_ram[];
+
+long counter = 42;
+long zeroes[64];
+
+__attribute__((section(".text.entry")))
+void entry(void) {
+    for (const char **s = table_start; s < table_end; s++) {
+        say(*s);
+    }
+    hex(counter);
+    long sum = 0;
+    for (int i = 0; i < 64; i++) {
+        sum += zeroes[i];
+    }
+    hex(sum);
+    hex(stack_top - bss_start >= (long)stack_size);
+    hex((unsigned long)end_of_ram);
+    hex((unsigned long)stack_top % 16);
+    say(merged_hello());
+    hex(merged_hello() == (const char *)"hello merged\n");
+    hex(merged_tail() == merged_hello() + 6);
+    sys_exit(0);
+}
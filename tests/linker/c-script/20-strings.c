@@ -0,0 +1,3 @@
+// the same literals as 10-main.c, so they are merged into one
+const char *merged_hello(void) { return "hello merged\n"; }
+const char *merged_tail(void) { return "merged\n"; }
@@ -0,0 +1,7 @@
+#include <stdio.h>
+int counter = 40;
+static int helper(int x) { return x * 2; }
+int bump(void) { return ++counter; }
+int twice(int x) { return helper(x) + bump() - counter; }
+void greet(const char *who) { printf("hello %s, counter %d\n", who, counter); }
+int (*bump_ptr)(void) = bump;
@@ -0,0 +1,15 @@
+#include <stdio.h>
+
+extern int counter;
+extern int (*bump_ptr)(void);
+int  bump(void);
+int  twice(int);
+void greet(const char *);
+
+int main() {
+    greet("main");
+    bump();
+    bump_ptr();
+    printf("%d %d\n", counter, twice(5));
+    return 0;
+}
@@ -0,0 +1,12 @@
+#include <stdio.h>
+#include <string.h>
+
+int (*say)(const char *) = puts;
+
+int main() {
+    char buf[32];
+    strcpy(buf, "hello libc.so");
+    say(buf);
+    fprintf(stdout, "%zu\n", strlen(buf));
+    return 0;
+}
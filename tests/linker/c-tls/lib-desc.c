@@ -0,0 +1,5 @@
+extern __thread int lib_counter;
+
+long lib_desc(void) {
+    return lib_counter + 100;
+}
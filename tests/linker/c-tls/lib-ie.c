@@ -0,0 +1,6 @@
+extern __thread int lib_counter;
+static __thread int lib_ie_local = 3;
+
+int lib_ie(void) {
+    return lib_counter * 2 + lib_ie_local++;
+}
@@ -0,0 +1,8 @@
+__thread int lib_counter = 5;
+__thread char lib_name[16] = "lib";
+static __thread int lib_hits;
+
+int lib_bump(void) {
+    lib_hits++;
+    return ++lib_counter + lib_hits;
+}
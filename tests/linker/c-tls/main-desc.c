@@ -0,0 +1,6 @@
+extern __thread int counter;
+extern __thread int lib_counter;
+
+long main_desc(void) {
+    return counter + lib_counter;
+}
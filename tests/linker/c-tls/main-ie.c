@@ -0,0 +1,6 @@
+extern __thread int counter;
+extern __thread int lib_counter;
+
+int main_ie(void) {
+    return counter + lib_counter * 10;
+}
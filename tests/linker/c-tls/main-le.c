@@ -0,0 +1,6 @@
+extern __thread int counter;
+static __thread int zeroes;
+
+int main_le(void) {
+    return counter + ++zeroes;
+}
@@ -0,0 +1,45 @@
+#include <stdio.h>
+#include <pthread.h>
+
+extern __thread int lib_counter;
+extern __thread char lib_name[16];
+__thread int counter = 40;
+static __thread int hits;
+// .tbss, with more alignment than .tdata has
+__thread char buf[100] __attribute__((aligned(64)));
+
+int  lib_bump(void);
+long lib_desc(void);
+int  lib_ie(void);
+long main_desc(void);
+int  main_ie(void);
+int  main_le(void);
+
+static void report(const char *who) {
+    hits++;
+    counter += hits;
+    snprintf(buf, sizeof buf, "%s:%s", who, lib_name);
+    int  bump  = lib_bump();
+    long ldesc = lib_desc();
+    long mdesc = main_desc();
+    int  lie   = lib_ie();
+    int  mie   = main_ie();
+    int  mle   = main_le();
+    printf("%s counter=%d hits=%d lib=%d bump=%d desc=%ld/%ld ie=%d/%d le=%d aligned=%d\n",
+           buf, counter, hits, lib_counter, bump, ldesc, mdesc, lie, mie, mle,
+           (int)((unsigned long)buf % 64));
+}
+
+static void *thread(void *arg) {
+    report("thread");
+    return arg;
+}
+
+int main() {
+    pthread_t t;
+    report("main");
+    pthread_create(&t, 0, thread, 0);
+    pthread_join(t, 0);
+    report("main");
+    return 0;
+}
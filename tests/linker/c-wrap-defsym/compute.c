@@ -0,0 +1,5 @@
+const char greeting[] = "hello world";
+
+int compute(int x) {
+    return x * 2;
+}
@@ -0,0 +1,3 @@
+int extra(void) {
+    return 7;
+}
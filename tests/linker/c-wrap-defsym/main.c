@@ -0,0 +1,23 @@
+#include <stdio.h>
+
+int compute(int x);
+int extra(void) __attribute__((weak));
+
+extern const char greeting[];
+extern const char greeting_tail[];
+
+int __real_compute(int x);
+
+int __wrap_compute(int x) {
+    return __real_compute(x) + 1;
+}
+
+// only linked because it's the entry point. weak, which is still good enough for one
+__attribute__((weak, naked)) void alt_start(void) {
+    __asm__ volatile ("jmp _start");
+}
+
+int main() {
+    printf("%d %s %d\n", compute(20), greeting_tail, extra ? extra() : -1);
+    return 0;
+}
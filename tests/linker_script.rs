@@ -0,0 +1,137 @@
+extern crate elfkit;
+
+use elfkit::{Elf, Error, Header, Section, SectionContent, types};
+use elfkit::linker_script::{self, Command, Expr, OutputCommand, Script};
+
+const SCRIPT : &[u8] = b"
+/* flash and ram */
+ENTRY(reset)
+MEMORY
+{
+    ROM (rx)  : ORIGIN = 0x1000, LENGTH = 4K
+    RAM (rwx) : ORIGIN = 0x8000, LENGTH = 0x100
+}
+stack_size = 0x40;
+SECTIONS
+{
+    .text : { KEEP(*(.vectors)) *(.text .text.*) } >ROM
+    .data : ALIGN(16) { *(.data*) } >RAM
+    .bss (NOLOAD) : {
+        *(.bss)
+        . = . + stack_size;
+        stack_top = .;
+    } >RAM
+    PROVIDE(ram_end = ORIGIN(RAM) + LENGTH(RAM));
+    text_end = ADDR(.text) + SIZEOF(.text);
+    /DISCARD/ : { *(.comment) }
+}
+";
+
+fn section(name: &[u8], shtype: types::SectionType, size: usize) -> Section {
+    let mut sec = Section::new(name.to_vec(), shtype.clone(), types::SectionFlags::ALLOC,
+    SectionContent::Raw(vec![0; size]), 0, 0);
+    sec.header.addralign = 4;
+    if shtype == types::SectionType::NOBITS {
+        sec.content = SectionContent::None;
+        sec.header.size = size as u64;
+    }
+    sec
+}
+
+fn elf(text: usize) -> Elf {
+    let header = Header{etype: types::ElfType::EXEC, ..Default::default()};
+    let mut elf = Elf::from_header(header);
+    elf.sections.push(Section::default());
+    elf.sections.push(section(b".text", types::SectionType::PROGBITS, text));
+    elf.sections.push(section(b".data", types::SectionType::PROGBITS, 0x10));
+    elf.sections.push(section(b".bss", types::SectionType::NOBITS, 0x20));
+    elf
+}
+
+#[test]
+fn linker_script_parse() {
+    let script = Script::parse(SCRIPT).unwrap();
+    assert_eq!(script.entry, Some(b"reset".to_vec()));
+    assert_eq!(script.memory.len(), 2);
+    assert_eq!(script.memory[0].length, 0x1000);
+    assert_eq!(script.constants().get(&b"stack_size"[..]), Some(&0x40));
+
+    let text = match script.commands[1] {
+        Command::Output(ref out) => out.clone(),
+        ref any => panic!("expected .text, got {:?}", any),
+    };
+    assert_eq!(text.region, Some(b"ROM".to_vec()));
+    match text.commands[0] {
+        OutputCommand::Input(ref input) => assert!(input.keep),
+        ref any => panic!("expected KEEP, got {:?}", any),
+    }
+
+    assert_eq!(script.input_section(b"a.o", b".text.main"), Some((1, 1)));
+    assert_eq!(script.input_section(b"lib.a::b.o", b".data.rel"), Some((2, 0)));
+    assert!(script.keep(b"a.o", b".vectors"));
+    assert!(!script.keep(b"a.o", b".text"));
+    match script.input_section(b"a.o", b".comment") {
+        Some((i, _)) => match script.commands[i] {
+            Command::Output(ref out) => assert!(out.is_discard()),
+            _ => unreachable!(),
+        },
+        None => panic!(".comment should be discarded"),
+    }
+    assert_eq!(script.input_section(b"a.o", b".rodata"), None);
+
+    let provide = script.assignments().into_iter().find(|a| a.symbol == b"ram_end").unwrap();
+    assert!(provide.provide);
+    assert!(provide.expr.symbols().is_empty());
+    assert_eq!(Expr::Dot.eval_at(0x10).unwrap(), 0x10);
+
+    // what --defsym parses
+    let defsym = Script::parse(b"tail = greeting + 6;").unwrap();
+    assert_eq!(defsym.assignments()[0].expr.symbols(), vec![&b"greeting"[..]]);
+}
+
+#[test]
+fn linker_script_syntax_error() {
+    match Script::parse(b"SECTIONS\n{\n    .text : { *(.text) \n}\n") {
+        Err(Error::LinkerScriptSyntax{line, ..}) => assert_eq!(line, 5),
+        any => panic!("expected a syntax error, got {:?}", any),
+    }
+}
+
+#[test]
+fn linker_script_glob() {
+    assert!(linker_script::glob(b"*", b""));
+    assert!(linker_script::glob(b".text.*", b".text.main"));
+    assert!(!linker_script::glob(b".text.*", b".text"));
+    assert!(linker_script::glob(b".data?", b".data1"));
+    assert!(linker_script::glob(b"*crt[in].o", b"crtn.o"));
+    assert!(!linker_script::glob(b"*crt[in].o", b"crt1.o"));
+}
+
+#[test]
+fn linker_script_place() {
+    let script = Script::parse(SCRIPT).unwrap();
+    let mut elf = elf(0x22);
+    let symbols = script.place(&mut elf).unwrap();
+
+    assert_eq!(elf.section_start.get(&b".text"[..]), Some(&0x1000));
+    assert_eq!(elf.section_start.get(&b".data"[..]), Some(&0x8000));
+    assert_eq!(elf.section_start.get(&b".bss"[..]), Some(&0x8010));
+    assert_eq!(symbols.get(&b"ram_end"[..]), Some(&0x8100));
+    assert_eq!(symbols.get(&b"text_end"[..]), Some(&0x1022));
+    // relative to .bss, resolved while merging
+    assert_eq!(symbols.get(&b"stack_top"[..]), None);
+}
+
+#[test]
+fn linker_script_region_overflow() {
+    let script = Script::parse(SCRIPT).unwrap();
+    let mut elf = elf(0x1010);
+    match script.place(&mut elf) {
+        Err(Error::RegionOverflow{region, sec, by}) => {
+            assert_eq!(region, "ROM");
+            assert_eq!(sec, ".text");
+            assert_eq!(by, 0x10);
+        },
+        any => panic!("expected an overflow, got {:?}", any),
+    }
+}
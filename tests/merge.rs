@@ -0,0 +1,41 @@
+extern crate elfkit;
+
+use elfkit::merge;
+
+#[test]
+fn merge_strings() {
+    let a = b"hello world\0%s\n\0";
+    let b = b"world\0hello world\0lo\0";
+    let m = merge::merge(&[&a[..], &b[..]], 1, true);
+
+    // "world" is the end of "hello world", "lo" isn't the end of anything
+    assert_eq!(m.content, b"hello world\0%s\n\0lo\0".to_vec());
+    assert_eq!(m.offsets[0], vec![(0, 0), (12, 12)]);
+    assert_eq!(m.offsets[1], vec![(0, 6), (6, 0), (18, 16)]);
+
+    // into the middle of an entry, and before the first one
+    assert_eq!(m.offset(1, 2), 8);
+    assert_eq!(m.offset(1, 8), 2);
+    assert_eq!(m.offset(0, -4), -4);
+}
+
+#[test]
+fn merge_wide_strings() {
+    // a nul byte inside a character doesn't end the string
+    let a = b"a\0d\0\0\0";
+    let b = b"\0d\0\0";
+    let c = b"d\0\0\0";
+    let m = merge::merge(&[&a[..], &b[..], &c[..]], 2, true);
+    assert_eq!(m.content, b"a\0d\0\0\0\0d\0\0".to_vec());
+    assert_eq!(m.offset(2, 0), 2);
+    assert_eq!(m.offset(1, 0), 6);
+}
+
+#[test]
+fn merge_constants() {
+    let a = [1, 0, 0, 0, 2, 0, 0, 0];
+    let b = [2, 0, 0, 0, 3, 0, 0, 0];
+    let m = merge::merge(&[&a[..], &b[..]], 4, false);
+    assert_eq!(m.content, vec![1, 0, 0, 0, 2, 0, 0, 0, 3, 0, 0, 0]);
+    assert_eq!(m.offsets[1], vec![(0, 4), (4, 8)]);
+}
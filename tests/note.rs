@@ -0,0 +1,118 @@
+extern crate elfkit;
+
+use elfkit::{Elf, ElfRef, Header, Note, NoteContent, Section, SectionContent, types};
+use elfkit::note::{self, GnuProperty};
+use std::fs::{self, File};
+use std::io::Cursor;
+
+// .note.gnu.property of a x86_64 binary, 8 byte aligned
+const PROPERTY_NOTE: &[u8] = &[
+    0x04, 0, 0, 0,  0x20, 0, 0, 0,  0x05, 0, 0, 0,  b'G', b'N', b'U', 0,
+    0x02, 0, 0, 0xc0,  0x04, 0, 0, 0,  0x03, 0, 0, 0,  0, 0, 0, 0,
+    0x02, 0x80, 0, 0xc0,  0x04, 0, 0, 0,  0x01, 0, 0, 0,  0, 0, 0, 0,
+];
+
+// .note.ABI-tag followed by a note we don't know, 4 byte aligned
+const ABI_TAG_NOTE: &[u8] = &[
+    0x04, 0, 0, 0,  0x10, 0, 0, 0,  0x01, 0, 0, 0,  b'G', b'N', b'U', 0,
+    0, 0, 0, 0,  3, 0, 0, 0,  2, 0, 0, 0,  0, 0, 0, 0,
+    0x06, 0, 0, 0,  0x03, 0, 0, 0,  0x2a, 0, 0, 0,  b'G', b'o', b' ', b'x', b'x', 0, 0, 0,
+    1, 2, 3, 0,
+];
+
+fn write(notes: &Vec<Note>, eh: &Header, align: u64) -> Vec<u8> {
+    let mut r = Vec::new();
+    for n in notes {
+        n.to_writer(&mut r, eh, align).unwrap();
+    }
+    r
+}
+
+#[test]
+fn note_gnu_property_8_aligned() {
+    let eh = Header::default();
+    let notes = Note::from_reader(PROPERTY_NOTE, &eh, 8).unwrap();
+    assert_eq!(notes.len(), 1);
+    assert_eq!(notes[0].name, b"GNU");
+    assert_eq!(notes[0].ntype(), note::NT_GNU_PROPERTY_TYPE_0);
+    assert_eq!(notes[0].content, NoteContent::GnuProperties(vec![
+        GnuProperty::X86Feature1And(types::X86Feature1::IBT | types::X86Feature1::SHSTK),
+        GnuProperty::X86Isa1Needed(types::X86Isa1::BASELINE),
+    ]));
+    assert_eq!(notes[0].size(&eh, 8), PROPERTY_NOTE.len());
+    assert_eq!(write(&notes, &eh, 8), PROPERTY_NOTE);
+}
+
+#[test]
+fn note_abi_tag_and_raw_4_aligned() {
+    let eh = Header::default();
+    let notes = Note::from_reader(ABI_TAG_NOTE, &eh, 4).unwrap();
+    assert_eq!(notes.len(), 2);
+    assert_eq!(notes[0].content, NoteContent::GnuAbiTag{os: 0, major: 3, minor: 2, subminor: 0});
+    assert_eq!(notes[1].name, b"Go xx");
+    assert_eq!(notes[1].content, NoteContent::Raw{ntype: 0x2a, desc: vec![1, 2, 3]});
+    assert_eq!(write(&notes, &eh, 4), ABI_TAG_NOTE);
+}
+
+#[test]
+fn note_unknown_property_flags_stay_raw() {
+    let eh = Header::default();
+    let mut bytes = PROPERTY_NOTE.to_vec();
+    bytes[24] = 0x80;
+    let notes = Note::from_reader(bytes.as_slice(), &eh, 8).unwrap();
+    match notes[0].content {
+        NoteContent::GnuProperties(ref props) => {
+            assert_eq!(props[0], GnuProperty::Raw{ptype: 0xc0000002, data: vec![0x80, 0, 0, 0]});
+        },
+        ref any => panic!("expected properties, got {:?}", any),
+    }
+    assert_eq!(write(&notes, &eh, 8), bytes);
+}
+
+#[test]
+fn note_section_roundtrip() {
+    let eh = Header::default();
+    let mut sec = Section::new(b".note.gnu.build-id".to_vec(), types::SectionType::NOTE,
+                               types::SectionFlags::ALLOC,
+                               SectionContent::Notes(vec![Note{
+                                   name:    b"GNU".to_vec(),
+                                   content: NoteContent::GnuBuildId(vec![0xab; 20]),
+                               }, Note{
+                                   name:    b"GNU".to_vec(),
+                                   content: NoteContent::GnuGoldVersion(b"gold 1.16".to_vec()),
+                               }]), 0, 0);
+    sec.header.addralign = 4;
+    sec.sync(&eh, None).unwrap();
+    assert_eq!(sec.header.size, 16 + 20 + 16 + 12);
+
+    let mut io = Cursor::new(Vec::new());
+    sec.to_writer(&mut io, &eh).unwrap();
+    let bytes = io.into_inner();
+    let notes = Note::from_reader(bytes.as_slice(), &eh, 4).unwrap();
+    assert_eq!(&notes, sec.content.as_notes().unwrap());
+}
+
+#[test]
+fn note_fixture_section_and_segment() {
+    let mut f = File::open("tests/ld.so").unwrap();
+    let mut elf = Elf::from_reader(&mut f).unwrap();
+    elf.load_all(&mut f).unwrap();
+
+    let sec = elf.sections.iter().find(|s| s.name == b".note.gnu.build-id").unwrap();
+    let notes = sec.content.as_notes().unwrap();
+    assert_eq!(notes.len(), 1);
+    let id = match notes[0].content {
+        NoteContent::GnuBuildId(ref id) => id.clone(),
+        ref any => panic!("expected build id, got {:?}", any),
+    };
+    assert_eq!(id.len(), 20);
+
+    let i = elf.segments.iter().position(|s| s.phtype == types::SegmentType::NOTE).unwrap();
+    assert_eq!(&elf.segment_notes(i, &mut f).unwrap(), notes);
+
+    let data = fs::read("tests/ld.so").unwrap();
+    let r = ElfRef::from_bytes(&data).unwrap();
+    let seg = r.segments().map(|s| s.unwrap())
+        .find(|s| s.phtype == types::SegmentType::NOTE).unwrap();
+    assert_eq!(&r.segment_notes(&seg).unwrap(), notes);
+}
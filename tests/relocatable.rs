@@ -0,0 +1,81 @@
+extern crate elfkit;
+
+use elfkit::{Elf, Header, Section, SectionContent, types, symbol, strtab};
+use elfkit::relocation::{Relocation, RelocationType};
+use std::io::Cursor;
+
+fn sym(name: &[u8], bind: types::SymbolBind, shndx: symbol::SymbolSectionIndex) -> symbol::Symbol {
+    symbol::Symbol{
+        shndx,
+        name:   name.to_vec(),
+        stype:  types::SymbolType::NOTYPE,
+        bind,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn relocatable_layout() {
+    let header = Header{etype: types::ElfType::REL, ..Default::default()};
+    let mut elf = Elf::from_header(header);
+
+    elf.sections.push(Section::default());
+    let mut text = Section::new(b".text".to_vec(), types::SectionType::PROGBITS,
+    types::SectionFlags::ALLOC | types::SectionFlags::EXECINSTR,
+    SectionContent::Raw(vec![0xe8, 0, 0, 0, 0, 0xc3]), 0, 0);
+    text.header.addralign = 16;
+    elf.sections.push(text);
+    let mut bss = Section::new(b".bss".to_vec(), types::SectionType::NOBITS,
+    types::SectionFlags::ALLOC | types::SectionFlags::WRITE, SectionContent::None, 0, 0);
+    bss.header.size = 0x1000;
+    bss.header.addralign = 8;
+    elf.sections.push(bss);
+    elf.sections.push(Section::new(b".strtab".to_vec(), types::SectionType::STRTAB,
+    types::SectionFlags::empty(),
+    SectionContent::Strtab(strtab::Strtab::default()), 0, 0));
+    elf.sections.push(Section::new(b".symtab".to_vec(), types::SectionType::SYMTAB,
+    types::SectionFlags::empty(),
+    SectionContent::Symbols(vec![
+        symbol::Symbol::default(),
+        sym(b"local", types::SymbolBind::LOCAL, symbol::SymbolSectionIndex::Section(1)),
+        sym(b"weak", types::SymbolBind::WEAK, symbol::SymbolSectionIndex::Section(1)),
+        sym(b"undef", types::SymbolBind::GLOBAL, symbol::SymbolSectionIndex::Undefined),
+    ]), 3, 0));
+    elf.sections.push(Section::new(b".rela.text".to_vec(), types::SectionType::RELA,
+    types::SectionFlags::INFO_LINK,
+    SectionContent::Relocations(vec![Relocation{
+        addr:   1,
+        sym:    3,
+        rtype:  RelocationType::R_X86_64_PLT32,
+        addend: -4,
+    }]), 4, 1));
+    elf.sections.push(Section::new(b".shstrtab".to_vec(), types::SectionType::STRTAB,
+    types::SectionFlags::empty(),
+    SectionContent::Strtab(strtab::Strtab::default()), 0, 0));
+    elf.layout().unwrap();
+
+    assert_eq!(elf.segments.len(), 0);
+    assert_eq!(elf.header.phnum, 0);
+    assert_eq!(elf.header.phoff, 0);
+    for sec in &elf.sections {
+        assert_eq!(sec.header.addr, 0);
+    }
+    assert_eq!(elf.sections[1].header.offset % 16, 0);
+    // .bss takes no space in the file
+    assert_eq!(elf.sections[2].header.offset, elf.sections[3].header.offset);
+    // first non-local symbol, weak ones included
+    assert_eq!(elf.sections[4].header.info, 2);
+
+    let mut io = Cursor::new(Vec::new());
+    elf.to_writer(&mut io).unwrap();
+    io.set_position(0);
+    let mut elf = Elf::from_reader(&mut io).unwrap();
+    elf.load_all(&mut io).unwrap();
+    assert_eq!(elf.header.etype, types::ElfType::REL);
+    let rela = &elf.sections[5];
+    assert_eq!(rela.header.link, 4);
+    assert_eq!(rela.header.info, 1);
+    let relocs = rela.content.as_relocations().unwrap();
+    assert_eq!(relocs[0].sym, 3);
+    assert_eq!(elf.sections[4].content.as_symbols().unwrap()[3].name, b"undef");
+}
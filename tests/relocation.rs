@@ -0,0 +1,167 @@
+extern crate elfkit;
+
+use elfkit::{Header, Section, SectionContent, Relocation, types};
+use elfkit::relocation::RelocationType;
+use std::io::Cursor;
+
+fn header(class: types::Class, machine: types::Machine) -> Header {
+    Header{ident_class: class, machine, ..Default::default()}
+}
+
+#[test]
+fn rel_roundtrip_i386() {
+    let eh = header(types::Class::Class32, types::Machine::EM386);
+
+    let mut sec = Section::new(b".rel.text".to_vec(), types::SectionType::REL,
+                               types::SectionFlags::empty(),
+                               SectionContent::Relocations(vec![
+                                   Relocation{
+                                       addr:   4,
+                                       sym:    5,
+                                       rtype:  RelocationType::R_386_PC32,
+                                       addend: 0,
+                                   },
+                                   Relocation{
+                                       addr:   9,
+                                       sym:    0x123456,
+                                       rtype:  RelocationType::R_386_GOTPC,
+                                       addend: 0,
+                                   },
+                               ]), 0, 1);
+    sec.sync(&eh, None).unwrap();
+    assert_eq!(sec.header.entsize, 8);
+    assert_eq!(sec.header.size, 16);
+
+    let mut io = Cursor::new(Vec::new());
+    sec.to_writer(&mut io, &eh).unwrap();
+    let bytes = io.into_inner();
+    assert_eq!(bytes.len(), 16);
+    assert_eq!(&bytes[4..8], &[0x02, 0x05, 0, 0]);
+
+    let relocs = Relocation::from_reader_rel(bytes.as_slice(), None, &eh).unwrap()
+        .into_relocations().unwrap();
+    assert_eq!(relocs.len(), 2);
+    assert_eq!(relocs[0].addr, 4);
+    assert_eq!(relocs[0].sym, 5);
+    assert_eq!(relocs[0].rtype, RelocationType::R_386_PC32);
+    assert_eq!(relocs[1].sym, 0x123456);
+    assert_eq!(relocs[1].rtype, RelocationType::R_386_GOTPC);
+    assert_eq!(relocs[1].rtype.typename(&eh), Some("386_GOTPC"));
+}
+
+#[test]
+fn relocation_type_debug() {
+    assert_eq!(format!("{:?}", RelocationType::R_X86_64_PC32), "R_X86_64_PC32");
+    assert_eq!(format!("{:?}", RelocationType(200)), "RelocationType(200)");
+}
+
+#[test]
+fn rela_entsize_by_class() {
+    let eh32 = header(types::Class::Class32, types::Machine::EM386);
+    let eh64 = header(types::Class::Class64, types::Machine::X86_64);
+    assert_eq!(Relocation::entsize(&eh32), 12);
+    assert_eq!(Relocation::entsize_rel(&eh32), 8);
+    assert_eq!(Relocation::entsize(&eh64), 24);
+    assert_eq!(Relocation::entsize_rel(&eh64), 16);
+}
+
+#[test]
+fn implicit_addend_i386() {
+    let eh = header(types::Class::Class32, types::Machine::EM386);
+    // call rel32 with -4 in the immediate
+    let mut text = vec![0xe8, 0xfc, 0xff, 0xff, 0xff, 0x90];
+    let mut reloc = Relocation{
+        addr:   1,
+        sym:    1,
+        rtype:  RelocationType::R_386_PC32,
+        addend: 0,
+    };
+    assert_eq!(reloc.implicit_addend(&text, &eh).unwrap(), -4);
+
+    reloc.addend = 0x10;
+    reloc.write_implicit_addend(&mut text, &eh).unwrap();
+    assert_eq!(text, vec![0xe8, 0x10, 0, 0, 0, 0x90]);
+
+    reloc.addr = 4;
+    assert!(reloc.implicit_addend(&text, &eh).is_err());
+}
+
+#[test]
+fn implicit_addend_arm_call() {
+    let eh = header(types::Class::Class32, types::Machine::ARM);
+    // bl with imm24 = -2 words, which is the usual -8 for pc bias
+    let mut text = vec![0xfe, 0xff, 0xff, 0xeb];
+    let mut reloc = Relocation{
+        addr:   0,
+        sym:    1,
+        rtype:  RelocationType::R_ARM_CALL,
+        addend: 0,
+    };
+    assert_eq!(reloc.implicit_addend(&text, &eh).unwrap(), -8);
+
+    reloc.addend = 16;
+    reloc.write_implicit_addend(&mut text, &eh).unwrap();
+    assert_eq!(text, vec![0x04, 0, 0, 0xeb]);
+}
+
+#[test]
+fn implicit_addend_thumb_branch() {
+    let eh = header(types::Class::Class32, types::Machine::ARM);
+    // bl . (f000 f800) has no offset, bl with the usual -4 for pc bias is f7ff fffe
+    let mut text = vec![0x00, 0xf0, 0x00, 0xf8, 0xff, 0xf7, 0xfe, 0xff];
+    let mut reloc = Relocation{
+        addr:   0,
+        sym:    1,
+        rtype:  RelocationType::R_ARM_THM_CALL,
+        addend: 0,
+    };
+    assert_eq!(reloc.implicit_addend(&text, &eh).unwrap(), 0);
+    reloc.addr = 4;
+    assert_eq!(reloc.implicit_addend(&text, &eh).unwrap(), -4);
+
+    reloc.addr   = 0;
+    reloc.addend = -4;
+    reloc.write_implicit_addend(&mut text, &eh).unwrap();
+    assert_eq!(&text[..4], &[0xff, 0xf7, 0xfe, 0xff]);
+
+    // b.w keeps bit 12 of the second halfword clear
+    let mut text = vec![0x00, 0xf0, 0x00, 0xb8];
+    reloc.rtype  = RelocationType::R_ARM_THM_JUMP24;
+    reloc.addend = 0x123456;
+    reloc.write_implicit_addend(&mut text, &eh).unwrap();
+    assert_eq!(text[3] & 0xd0, 0x90);
+    assert_eq!(reloc.implicit_addend(&text, &eh).unwrap(), 0x123456);
+}
+
+#[test]
+fn implicit_addend_prel31() {
+    let eh = header(types::Class::Class32, types::Machine::ARM);
+    // the top bit of an exception index entry is not part of the offset
+    let mut text = vec![0xf0, 0xff, 0xff, 0xff];
+    let mut reloc = Relocation{
+        addr:   0,
+        sym:    1,
+        rtype:  RelocationType::R_ARM_PREL31,
+        addend: 0,
+    };
+    assert_eq!(reloc.implicit_addend(&text, &eh).unwrap(), -16);
+
+    reloc.addend = 16;
+    reloc.write_implicit_addend(&mut text, &eh).unwrap();
+    assert_eq!(text, vec![0x10, 0, 0, 0x80]);
+}
+
+#[test]
+fn implicit_addend_mips_lo16() {
+    let mut eh = header(types::Class::Class32, types::Machine::MIPS);
+    eh.ident_endianness = types::Endianness::BigEndian;
+    // addiu $a0, $a0, -16
+    let text = vec![0x24, 0x84, 0xff, 0xf0];
+    let reloc = Relocation{
+        addr:   0,
+        sym:    1,
+        rtype:  RelocationType::R_MIPS_LO16,
+        addend: 0,
+    };
+    assert_eq!(reloc.implicit_addend(&text, &eh).unwrap(), -16);
+}
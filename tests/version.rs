@@ -0,0 +1,159 @@
+extern crate elfkit;
+
+use elfkit::{Elf, ElfRef, Header, Section, SectionContent, Symbol, SymbolSectionIndex, types, strtab};
+use elfkit::hash;
+use elfkit::version::{self, Verdaux, Verdef, Verneed, Vernaux, Versym};
+use std::fs::{self, File};
+use std::io::Cursor;
+
+#[test]
+fn version_fixture() {
+    let mut f = File::open("tests/ld.so").unwrap();
+    let mut elf = Elf::from_reader(&mut f).unwrap();
+    elf.load_all(&mut f).unwrap();
+
+    let find = |name: &[u8]| elf.sections.iter().find(|s| s.name == name).unwrap();
+    let syms    = find(b".dynsym").content.as_symbols().unwrap();
+    let versyms = find(b".gnu.version").content.as_versym().unwrap();
+    let verdefs = find(b".gnu.version_d").content.as_verdef().unwrap();
+    assert_eq!(versyms.len(), syms.len());
+    assert_eq!(versyms[0], Versym{index: version::VER_NDX_LOCAL, hidden: false});
+
+    assert_eq!(verdefs.len(), 5);
+    assert_eq!(verdefs[0].flags, types::VersionFlags::BASE);
+    assert_eq!(verdefs[0].names[0].name, b"ld-linux-x86-64.so.2");
+    assert_eq!(verdefs[2].index, 3);
+    assert_eq!(verdefs[2].names.len(), 2);
+    assert_eq!(verdefs[2].names[0].name, b"GLIBC_2.3");
+    assert_eq!(verdefs[2].names[1].name, b"GLIBC_2.2.5");
+    for vd in verdefs {
+        assert_eq!(vd.hash, hash::sysv_hash(&vd.names[0].name));
+    }
+
+    let calloc = syms.iter().position(|s| s.name == b"calloc").unwrap();
+    assert_eq!(version::version_name(versyms[calloc].index, verdefs, &[]), Some(&b"GLIBC_2.2.5"[..]));
+
+    let data = fs::read("tests/ld.so").unwrap();
+    let r = ElfRef::from_bytes(&data).unwrap();
+    assert_eq!(&r.verdefs(&r.section_by_name(b".gnu.version_d").unwrap()).unwrap(), verdefs);
+    assert_eq!(&r.versyms(&r.section_by_name(b".gnu.version").unwrap()).unwrap(), versyms);
+
+    for name in [&b".gnu.version"[..], &b".gnu.version_d"[..]] {
+        let sec = r.section_by_name(name).unwrap();
+        let mut io = Cursor::new(Vec::new());
+        find(name).to_writer(&mut io, &elf.header).unwrap();
+        let written = io.into_inner();
+        assert_eq!(&written[sec.header.offset as usize..], sec.data);
+    }
+}
+
+fn verneed_fixture() -> Elf {
+    let header = Header{etype: types::ElfType::DYN, ..Default::default()};
+    let mut elf = Elf::from_header(header);
+    elf.sections.push(Section::default());
+
+    elf.sections.push(Section::new(b".dynstr".to_vec(), types::SectionType::STRTAB,
+    types::SectionFlags::ALLOC,
+    SectionContent::Strtab(strtab::Strtab::default()), 0, 0));
+
+    let mut symbols = vec![Symbol::default()];
+    for name in [&b"printf"[..], &b"sqrt"[..]] {
+        symbols.push(Symbol{
+            name:   name.to_vec(),
+            stype:  types::SymbolType::FUNC,
+            bind:   types::SymbolBind::GLOBAL,
+            shndx:  SymbolSectionIndex::Undefined,
+            ..Default::default()
+        });
+    }
+    elf.sections.push(Section::new(b".dynsym".to_vec(), types::SectionType::DYNSYM,
+    types::SectionFlags::ALLOC, SectionContent::Symbols(symbols), 1, 0));
+
+    elf.sections.push(Section::new(b".gnu.version".to_vec(), types::SectionType::GNU_VERSYM,
+    types::SectionFlags::ALLOC, SectionContent::Versym(vec![
+        Versym{index: 0, hidden: false},
+        Versym{index: 2, hidden: false},
+        Versym{index: 3, hidden: true},
+    ]), 2, 0));
+
+    let need = |file: &[u8], names: Vec<(&[u8], u16)>| Verneed {
+        version: 1,
+        file:    file.to_vec(),
+        aux:     names.into_iter().map(|(name, other)| Vernaux {
+            other,
+            name:  name.to_vec(),
+            ..Default::default()
+        }).collect(),
+        ..Default::default()
+    };
+    elf.sections.push(Section::new(b".gnu.version_r".to_vec(), types::SectionType::GNU_VERNEED,
+    types::SectionFlags::ALLOC, SectionContent::Verneed(vec![
+        need(b"libc.so.6", vec![(b"GLIBC_2.2.5", 2)]),
+        need(b"libm.so.6", vec![(b"GLIBC_2.2.5", 3), (b"GLIBC_2.29", 4)]),
+    ]), 1, 0));
+
+    elf.sections.push(Section::new(b".gnu.version_d".to_vec(), types::SectionType::GNU_VERDEF,
+    types::SectionFlags::ALLOC, SectionContent::Verdef(vec![Verdef {
+        version: 1,
+        flags:   types::VersionFlags::BASE,
+        index:   1,
+        names:   vec![Verdaux{name: b"libfoo.so".to_vec(), _name: 0}],
+        ..Default::default()
+    }]), 1, 0));
+
+    elf.sections.push(Section::new(b".shstrtab".to_vec(), types::SectionType::STRTAB,
+    types::SectionFlags::empty(),
+    SectionContent::Strtab(strtab::Strtab::default()), 0, 0));
+
+    elf.sync_all().unwrap();
+
+    let mut off = elf.header.size() as u64;
+    for sec in &mut elf.sections[1..] {
+        sec.header.offset = off;
+        off += sec.header.size;
+    }
+    elf
+}
+
+#[test]
+fn version_roundtrip() {
+    let mut elf = verneed_fixture();
+    let r = elf.sections[4].content.as_verneed().unwrap().clone();
+    let d = elf.sections[5].content.as_verdef().unwrap().clone();
+    assert_eq!(elf.sections[4].header.info, 2);
+    assert_eq!(elf.sections[4].header.size, 2 * 16 + 3 * 16);
+    assert_eq!(elf.sections[5].header.size, 20 + 8);
+    assert_eq!(r[1].aux[1].hash, hash::sysv_hash(b"GLIBC_2.29"));
+
+    let mut io = Cursor::new(Vec::new());
+    elf.to_writer(&mut io).unwrap();
+    let bytes = io.into_inner();
+
+    let mut io = Cursor::new(&bytes);
+    let mut elf2 = Elf::from_reader(&mut io).unwrap();
+    elf2.load_all(&mut io).unwrap();
+    assert_eq!(elf2.sections[4].content.as_verneed().unwrap(), &r);
+    assert_eq!(elf2.sections[5].content.as_verdef().unwrap(), &d);
+    let versyms = elf2.sections[3].content.as_versym().unwrap();
+    assert_eq!(versyms[2], Versym{index: 3, hidden: true});
+    assert_eq!(version::version_name(versyms[2].index, &[], &r), Some(&b"GLIBC_2.2.5"[..]));
+    assert_eq!(version::version_name(4, &[], &r), Some(&b"GLIBC_2.29"[..]));
+
+    let e = ElfRef::from_bytes(&bytes).unwrap();
+    assert_eq!(e.verneeds(&e.section_by_name(b".gnu.version_r").unwrap()).unwrap(), r);
+}
+
+#[test]
+fn version_edit() {
+    let mut elf = verneed_fixture();
+    elf.sections[4].content.as_verneed_mut().unwrap()[0].aux[0].name = b"GLIBC_2.34".to_vec();
+    elf.sync_all().unwrap();
+
+    let vn = &elf.sections[4].content.as_verneed().unwrap()[0].aux[0];
+    assert_eq!(vn.hash, hash::sysv_hash(b"GLIBC_2.34"));
+    let dynstr = match elf.sections[1].content {
+        SectionContent::Strtab(ref s) => s,
+        _ => unreachable!(),
+    };
+    assert_eq!(dynstr.get(vn._name as usize), b"GLIBC_2.34");
+}